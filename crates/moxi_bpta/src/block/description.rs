@@ -1,5 +1,5 @@
 use bevy_ecs::prelude::Bundle;
-use moxi_mesh_utils::prelude::{BlockMesh, BlockMeshType};
+use moxi_mesh_utils::prelude::{BlockMesh, BlockMeshType, BlockShape};
 
 #[allow(unused_imports)]
 use crate::{
@@ -14,6 +14,13 @@ pub trait Block {
     fn get_mesh() -> BlockMesh {
         BlockMesh::Air
     }
+    /// The shape of the block, it decides which of the block's faces hide the faces of its
+    /// neighbors, and the block's colliders. Partial blocks like slabs and stairs should have a
+    /// [`custom`](BlockMeshType::Custom) mesh and a shape. `None` means full cubes are full, and
+    /// the rest of the blocks are empty.
+    fn get_shape() -> Option<BlockShape> {
+        None
+    }
     fn get_static_properties() -> impl Bundle {
         ()
     }
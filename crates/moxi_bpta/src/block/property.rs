@@ -1,4 +1,5 @@
 use bevy_ecs::component::Component;
+use moxi_utils::prelude::Orientation;
 
 #[derive(Component)]
 pub struct DynamicBlock;
@@ -6,7 +7,47 @@ pub struct DynamicBlock;
 #[derive(Component)]
 pub struct StaticBlock;
 
+/// A static property: the light level (0 - [`MAX_LIGHT`](moxi_utils::prelude::MAX_LIGHT)) the
+/// block emits, like a torch or glowstone. Blocks don't emit light by default.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct LightEmission(pub u8);
+
+/// A static property: how much light (0 - [`MAX_LIGHT`](moxi_utils::prelude::MAX_LIGHT)) the
+/// block absorbs, glass lets all of the light through (0) while leaves only dim it. By default,
+/// cube blocks absorb all of the light and the rest of the blocks let it through.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct LightOpacity(pub u8);
+
+/// A static property: the block is a fluid, it flows into the air around it (see
+/// [`BlockWorldMut::with_fluid`](crate::prelude::BlockWorldMut::with_fluid)). Fluid blocks should
+/// have a [`fluid`](moxi_mesh_utils::prelude::BlockMeshType::Fluid) mesh.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Fluid {
+    /// How far (in blocks) the fluid flows sideways from a source, at most
+    /// [`MAX_FLUID_LEVEL`](moxi_mesh_utils::prelude::MAX_FLUID_LEVEL) - 1.
+    pub flow_distance: u8,
+    /// How many fluid ticks (see [`FluidTicks`](crate::prelude::FluidTicks)) it takes the fluid to
+    /// flow one block.
+    pub tick_delay: u32,
+}
+
+/// A static property: the block receives random ticks, [`RANDOM_TICK`](crate::prelude::RANDOM_TICK)
+/// updates sent to random blocks of the loaded chunks (see
+/// [`RandomTicks`](crate::prelude::RandomTicks)). Random ticks that land on blocks without this
+/// property are dropped, so their triggers don't run for nothing.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct RandomlyTicked;
+
 pub trait DynamicProperty: 'static {
+    /// The name the values of the property are saved with, it must be unique. Renaming the type
+    /// of the property loses its saved values, unless this keeps returning the old name.
+    fn get_name() -> &'static str
+    where
+        Self: Sized,
+    {
+        std::any::type_name::<Self>()
+    }
+
     fn encode(&self) -> u8
     where
         Self: Sized + Into<u8> + Copy,
@@ -22,6 +63,21 @@ pub trait DynamicProperty: 'static {
     }
 }
 
+/// The orientation of a block rotates its mesh, see
+/// [`set_oriented_block_at_id`](crate::prelude::_BlocksMut::set_oriented_block_at_id).
+impl DynamicProperty for Orientation {}
+
+/// The id the values of the property are stored with, a hash of its
+/// [`name`](DynamicProperty::get_name) that doesn't change between builds.
+pub(crate) fn property_id<P: DynamicProperty>() -> u64 {
+    // FNV-1a
+    P::get_name()
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
 pub struct DynamicProperties(pub Vec<BoxedDynamicProperty>);
 
 impl Default for DynamicProperties {
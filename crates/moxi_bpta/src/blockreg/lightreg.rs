@@ -0,0 +1,48 @@
+use bevy_ecs::system::Resource;
+use moxi_utils::prelude::{BlockId, LightRegistry, MAX_LIGHT};
+
+/// How every block interacts with light, by block id. Set with the
+/// [`LightEmission`](crate::prelude::LightEmission) and
+/// [`LightOpacity`](crate::prelude::LightOpacity) static properties. Blocks are registered without
+/// any light emission, opaque cube blocks block light completely and the rest of the blocks let it
+/// through.
+#[derive(Resource, Default, Clone)]
+pub struct LightReg {
+    pub(crate) emission: Vec<u8>,
+    pub(crate) opacity: Vec<u8>,
+}
+
+impl LightRegistry<BlockId> for LightReg {
+    fn light_emission(&self, block: &BlockId) -> u8 {
+        self.emission.get(*block as usize).copied().unwrap_or(0)
+    }
+
+    fn light_opacity(&self, block: &BlockId) -> u8 {
+        self.opacity.get(*block as usize).copied().unwrap_or(0)
+    }
+}
+
+impl LightReg {
+    pub fn new() -> Self {
+        Self {
+            emission: Vec::new(),
+            opacity: Vec::new(),
+        }
+    }
+
+    /// Set the light level (0 - [`MAX_LIGHT`]) the block emits. Only affects the light of chunks
+    /// that are loaded after the change.
+    pub fn set_light_emission(&mut self, block_id: BlockId, light_emission: u8) {
+        if let Some(emission) = self.emission.get_mut(block_id as usize) {
+            *emission = light_emission.min(MAX_LIGHT);
+        }
+    }
+
+    /// Set how much light (0 - [`MAX_LIGHT`]) the block absorbs. Only affects the light of
+    /// chunks that are loaded after the change.
+    pub fn set_light_opacity(&mut self, block_id: BlockId, light_opacity: u8) {
+        if let Some(opacity) = self.opacity.get_mut(block_id as usize) {
+            *opacity = light_opacity.min(MAX_LIGHT);
+        }
+    }
+}
@@ -1,13 +1,14 @@
 use bevy_asset::Handle;
 use bevy_ecs::system::Resource;
 use bevy_render::mesh::Mesh;
-use moxi_mesh_utils::prelude::{BlockMesh, BlockMeshRef, BlockMeshType, MeshRegistry};
+use moxi_mesh_utils::prelude::{BlockMesh, BlockMeshRef, BlockMeshType, BlockShape, MeshRegistry};
 use moxi_utils::prelude::BlockId;
 
 #[derive(Resource, Default, Clone)]
 pub struct MeshReg {
     pub(crate) meshes: Vec<BlockMesh>,
     pub(crate) handles: Vec<Handle<Mesh>>,
+    pub(crate) shapes: Vec<BlockShape>,
 }
 
 impl MeshRegistry<BlockId> for MeshReg {
@@ -22,6 +23,10 @@ impl MeshRegistry<BlockId> for MeshReg {
     fn get_block_mesh_type(&self, block: &BlockId) -> BlockMeshType {
         self.meshes[*block as usize].get_type()
     }
+
+    fn get_block_shape(&self, block: &BlockId) -> &BlockShape {
+        &self.shapes[*block as usize]
+    }
 }
 
 impl MeshReg {
@@ -29,6 +34,7 @@ impl MeshReg {
         Self {
             meshes: Vec::new(),
             handles: Vec::new(),
+            shapes: Vec::new(),
         }
     }
 }
@@ -1,9 +1,11 @@
+pub(crate) mod lightreg;
 pub(crate) mod meshreg;
 use bevy_ecs::{
     prelude::{Res, Resource},
     query::{ROQueryItem, WorldQuery},
     system::{Query, SystemParam},
 };
+pub use lightreg::*;
 pub use meshreg::*;
 use moxi_utils::prelude::BlockId;
 use std::collections::HashSet;
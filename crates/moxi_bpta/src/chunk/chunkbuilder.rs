@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use crate::*;
+use blockworld::BLOCKS_GLOBAL;
 use moxi_utils::prelude::*;
 
 pub trait ChunkBuilder<const N: usize, B: BlockInGrid>: Send + Sync {
@@ -17,3 +18,333 @@ impl<const N: usize> ChunkBuilder<N, BlockId> for BoxedBuilder<N> {
         self.builder.build_chunk(chunk_cods)
     }
 }
+
+/// A layer of the heightmap noise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseLayer {
+    /// How many times the noise changes direction per block, smaller is smoother.
+    pub frequency: f32,
+    /// How many blocks the layer moves the height up and down by.
+    pub amplitude: f32,
+}
+
+impl NoiseLayer {
+    pub const fn new(frequency: f32, amplitude: f32) -> Self {
+        Self {
+            frequency,
+            amplitude,
+        }
+    }
+}
+
+/// The blocks a biome's columns are made of, by their names (see [`BLOCKS_GLOBAL`]). Names that
+/// aren't registered are air.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Biome {
+    /// The top block of each column.
+    pub surface: &'static str,
+    /// The blocks under the surface block.
+    pub filler: &'static str,
+    /// The rest of the column.
+    pub stone: &'static str,
+    /// How many filler blocks are under the surface block.
+    pub filler_depth: u32,
+}
+
+impl Default for Biome {
+    fn default() -> Self {
+        Self {
+            surface: "Grass",
+            filler: "Dirt",
+            stone: "Stone",
+            filler_depth: 3,
+        }
+    }
+}
+
+/// A [`ChunkBuilder`] that builds terrain from a heightmap made of layers of noise, with a biome
+/// map that picks the blocks of each column. The terrain only depends on the seed and the chunk's
+/// cords, so the same chunk is always built the same way.
+#[derive(Clone, Debug)]
+pub struct NoiseTerrainBuilder {
+    pub seed: u64,
+    pub chunk_dims: Dimensions,
+    /// The height (in blocks from the origin of the world) the noise layers move the terrain
+    /// around.
+    pub base_height: f32,
+    /// The layers of the heightmap, they are added together.
+    pub height_layers: Vec<NoiseLayer>,
+    /// The frequency of the biome map, see [`NoiseLayer::frequency`].
+    pub biome_frequency: f32,
+    /// The biomes, picked by the value of the biome map. Every block is air if there are none.
+    pub biomes: Vec<Biome>,
+}
+
+impl NoiseTerrainBuilder {
+    /// Rolling hills of a single [`Biome::default`] biome.
+    pub fn new(seed: u64, chunk_dims: Dimensions) -> Self {
+        Self {
+            seed,
+            chunk_dims,
+            base_height: chunk_dims.y as f32 / 2.0,
+            height_layers: vec![NoiseLayer::new(0.01, 8.0), NoiseLayer::new(0.05, 2.0)],
+            biome_frequency: 0.005,
+            biomes: vec![Biome::default()],
+        }
+    }
+
+    pub fn with_height_layers(mut self, height_layers: Vec<NoiseLayer>) -> Self {
+        self.height_layers = height_layers;
+        self
+    }
+
+    pub fn with_biomes(mut self, biomes: Vec<Biome>) -> Self {
+        self.biomes = biomes;
+        self
+    }
+
+    /// The height of the top block of the column at (`x`, `z`), in blocks from the origin of the
+    /// world.
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        let height = self
+            .height_layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                let seed = self.seed.wrapping_add(i as u64 + 1);
+                layer.amplitude
+                    * value_noise(seed, x as f32 * layer.frequency, z as f32 * layer.frequency)
+            })
+            .sum::<f32>();
+        (self.base_height + height).floor() as i32
+    }
+
+    /// The index of the biome of the column at (`x`, `z`), `None` if there are no biomes. The biome
+    /// map is smooth, so a biome only borders the biomes next to it in [`biomes`](Self::biomes).
+    pub fn biome_at(&self, x: i32, z: i32) -> Option<usize> {
+        if self.biomes.is_empty() {
+            return None;
+        }
+        let noise = value_noise(
+            self.seed,
+            x as f32 * self.biome_frequency,
+            z as f32 * self.biome_frequency,
+        );
+        let index = ((noise + 1.0) / 2.0 * self.biomes.len() as f32) as usize;
+        Some(index.min(self.biomes.len() - 1))
+    }
+
+    /// Build the chunk with the blocks of each biome, by the index of the biome.
+    fn build_chunk_from_ids<const N: usize>(
+        &self,
+        chunk_cords: ChunkCords,
+        biome_blocks: &[BiomeBlocks],
+    ) -> Grid<BlockId, N> {
+        let dims = self.chunk_dims;
+        let mut grid = Grid::new([0; N], dims);
+        let origin = chunk_cords * dims.as_ivec3();
+        for z in 0..dims.z {
+            for x in 0..dims.x {
+                let (global_x, global_z) = (origin.x + x as i32, origin.z + z as i32);
+                let Some(biome) = self.biome_at(global_x, global_z) else {
+                    continue;
+                };
+                let BiomeBlocks {
+                    surface,
+                    filler,
+                    stone,
+                    filler_depth,
+                } = biome_blocks[biome];
+                let height = self.height_at(global_x, global_z);
+                for y in 0..dims.y {
+                    let global_y = origin.y + y as i32;
+                    let block_id = match height - global_y {
+                        depth if depth < 0 => continue,
+                        0 => surface,
+                        depth if depth <= filler_depth as i32 => filler,
+                        _ => stone,
+                    };
+                    let _ = grid.set_block(block_id, BlockPos::new(x, y, z));
+                }
+            }
+        }
+        grid
+    }
+}
+
+impl<const N: usize> ChunkBuilder<N, BlockId> for NoiseTerrainBuilder {
+    fn build_chunk(&self, chunk_cords: ChunkCords) -> Grid<BlockId, N> {
+        let biome_blocks: Vec<_> = self.biomes.iter().map(BiomeBlocks::from).collect();
+        self.build_chunk_from_ids(chunk_cords, &biome_blocks)
+    }
+}
+
+/// The blocks of a [`Biome`], by their ids.
+#[derive(Clone, Copy, Debug)]
+struct BiomeBlocks {
+    surface: BlockId,
+    filler: BlockId,
+    stone: BlockId,
+    filler_depth: u32,
+}
+
+impl From<&Biome> for BiomeBlocks {
+    fn from(biome: &Biome) -> Self {
+        Self {
+            surface: BLOCKS_GLOBAL::id(biome.surface),
+            filler: BLOCKS_GLOBAL::id(biome.filler),
+            stone: BLOCKS_GLOBAL::id(biome.stone),
+            filler_depth: biome.filler_depth,
+        }
+    }
+}
+
+/// Smooth noise in [-1, 1], interpolated between random values at the integer points.
+fn value_noise(seed: u64, x: f32, z: f32) -> f32 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, tz) = (smooth(x - x0), smooth(z - z0));
+    let (x0, z0) = (x0 as i32, z0 as i32);
+    let corner = |dx: i32, dz: i32| {
+        // The top 24 bits of the hash, mapped to [-1, 1].
+        (hash(seed, x0 + dx, z0 + dz) >> 40) as f32 / (1 << 23) as f32 - 1.0
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    lerp(
+        lerp(corner(0, 0), corner(1, 0), tx),
+        lerp(corner(0, 1), corner(1, 1), tx),
+        tz,
+    )
+}
+
+/// Mix the seed and the point into a random looking value (SplitMix64's finalizer).
+fn hash(seed: u64, x: i32, z: i32) -> u64 {
+    let mut h = seed ^ ((x as u32 as u64) << 32 | z as u32 as u64);
+    h = h.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^ (h >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRASS: BiomeBlocks = BiomeBlocks {
+        surface: 1,
+        filler: 2,
+        stone: 3,
+        filler_depth: 2,
+    };
+    const SAND: BiomeBlocks = BiomeBlocks {
+        surface: 4,
+        filler: 4,
+        stone: 3,
+        filler_depth: 4,
+    };
+    const SNOW: BiomeBlocks = BiomeBlocks {
+        surface: 5,
+        filler: 2,
+        stone: 3,
+        filler_depth: 1,
+    };
+
+    fn build(
+        builder: &NoiseTerrainBuilder,
+        cords: [i32; 3],
+        biome_blocks: &[BiomeBlocks],
+    ) -> Grid<BlockId, 2048> {
+        builder.build_chunk_from_ids(cords.into(), biome_blocks)
+    }
+
+    fn block_ids(grid: Grid<BlockId, 2048>) -> Vec<BlockId> {
+        grid.enumerate_blocks()
+            .map(|(_, block_id)| block_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_noise_terrain_builder() {
+        let dims = Dimensions::new(8, 32, 8);
+        let builder = NoiseTerrainBuilder::new(42, dims);
+        let ids = |builder: &NoiseTerrainBuilder, cords| block_ids(build(builder, cords, &[GRASS]));
+
+        // The same seed and cords always build the same chunk.
+        assert_eq!(ids(&builder, [3, 0, -2]), ids(&builder, [3, 0, -2]));
+        assert_eq!(ids(&builder.clone(), [3, 0, -2]), ids(&builder, [3, 0, -2]));
+        assert_ne!(ids(&builder, [0, 0, 0]), ids(&builder, [7, 0, 7]));
+        let other_seed = NoiseTerrainBuilder {
+            seed: 7,
+            ..builder.clone()
+        };
+        assert_ne!(ids(&builder, [0, 0, 0]), ids(&other_seed, [0, 0, 0]));
+
+        // Each column is stone, then filler, then the surface block at the height of the column.
+        let grid = build(&builder, [1, 0, 1], &[GRASS]);
+        for (x, z) in [(0, 0), (3, 5), (7, 7)] {
+            let height = builder.height_at(8 + x as i32, 8 + z as i32) as u32;
+            assert!(height > 2 && height < 31);
+            let block_at = |y| grid.get_block(BlockPos::new(x, y, z)).unwrap();
+            assert_eq!(block_at(height + 1), 0);
+            assert_eq!(block_at(height), 1);
+            assert_eq!(block_at(height - 2), 2);
+            assert_eq!(block_at(height - 3), 3);
+        }
+
+        // The names of the blocks that aren't registered are air.
+        let unregistered = builder.clone().with_biomes(vec![Biome {
+            surface: "TerrainTestUnregisteredSurface",
+            filler: "TerrainTestUnregisteredFiller",
+            stone: "TerrainTestUnregisteredStone",
+            filler_depth: 2,
+        }]);
+        let grid: Grid<BlockId, 2048> = unregistered.build_chunk([0, 0, 0].into());
+        assert!(block_ids(grid).iter().all(|block_id| *block_id == 0));
+    }
+
+    #[test]
+    fn test_noise_terrain_biomes() {
+        let dims = Dimensions::new(8, 32, 8);
+        let mut builder = NoiseTerrainBuilder::new(42, dims)
+            .with_biomes(vec![Biome::default(); 3])
+            .with_height_layers(vec![]);
+        builder.biome_frequency = 0.05;
+
+        // Every biome is picked somewhere, and the biomes only border the biomes next to them.
+        let mut picked = [0; 3];
+        for z in -64..64 {
+            for x in -64..64 {
+                let biome = builder.biome_at(x, z).unwrap();
+                picked[biome] += 1;
+                for (nx, nz) in [(x + 1, z), (x, z + 1)] {
+                    let neighbor = builder.biome_at(nx, nz).unwrap();
+                    assert!(biome.abs_diff(neighbor) <= 1);
+                }
+            }
+        }
+        assert!(picked.iter().all(|count| *count > 0));
+
+        // The blocks of each column are the blocks of its biome.
+        let biome_blocks = [GRASS, SAND, SNOW];
+        let height = builder.base_height as u32;
+        for cords in [[0, 0, 0], [-3, 0, 2], [5, 0, -6]] {
+            let grid = build(&builder, cords, &biome_blocks);
+            for (x, z) in [(0, 0), (2, 6), (7, 3)] {
+                let biome = builder.biome_at(cords[0] * 8 + x as i32, cords[2] * 8 + z as i32);
+                let blocks = biome_blocks[biome.unwrap()];
+                let block_at = |y| grid.get_block(BlockPos::new(x, y, z)).unwrap();
+                assert_eq!(block_at(height + 1), 0);
+                assert_eq!(block_at(height), blocks.surface);
+                assert_eq!(block_at(height - blocks.filler_depth), blocks.filler);
+                assert_eq!(block_at(height - blocks.filler_depth - 1), blocks.stone);
+            }
+        }
+
+        // Without biomes every block is air.
+        let no_biomes = builder.clone().with_biomes(vec![]);
+        assert_eq!(no_biomes.biome_at(0, 0), None);
+        assert!(block_ids(build(&no_biomes, [0, 0, 0], &[]))
+            .iter()
+            .all(|block_id| *block_id == 0));
+    }
+}
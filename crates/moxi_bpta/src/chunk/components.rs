@@ -1,18 +1,91 @@
+use crate::block::property_id;
+use crate::prelude::DynamicProperty;
 use bevy_ecs::{component::Component, entity::Entity};
 use moxi_mesh_utils::prelude::BlockMeshType;
-use moxi_utils::prelude::{BlockId, ChunkCords, Face, Grid};
+use moxi_mesh_utils::prelude::MAX_FLUID_LEVEL;
+use moxi_utils::prelude::{BlockId, BlockPos, ChunkCords, Face, LightGrid, PalettedGrid};
+use std::collections::HashMap;
+
+use super::resources::{VerticalChunkRange, DEFAULT_VERTICAL_LOAD_DISTANCE};
 
 #[derive(Component)]
 pub struct Chunk {
     pub cords: ChunkCords,
 }
 
+/// The blocks of a chunk, stored palette compressed. The blocks are accessed through the
+/// [`BlockGrid`](`moxi_utils::prelude::BlockGrid`) trait.
+#[derive(Component)]
+pub struct ChunkGrid<const N: usize>(pub PalettedGrid<BlockId, N>);
+
+/// The light levels of the blocks of a chunk.
 #[derive(Component)]
-pub struct ChunkGrid<const N: usize>(pub Grid<BlockId, N>);
+pub struct ChunkLight<const N: usize>(pub LightGrid<N>);
+
+/// The levels of the (flowing) fluid blocks of a chunk, the rest of the fluid blocks are sources.
+/// The levels are saved with the chunk.
+#[derive(Component, Default)]
+pub struct ChunkFluidLevels(pub HashMap<BlockPos, u8>);
+
+/// The [`dynamic properties`](`DynamicProperty`) of the blocks of a chunk, like the stage of a crop
+/// or whether a door is open. Each block has at most one value of each property, and its values
+/// are removed when the block is broken. The values are stored by the id of their property (a hash
+/// of [`DynamicProperty::get_name`]), and saved with the chunk.
+#[derive(Component, Default)]
+pub struct ChunkBlockStates(pub HashMap<BlockPos, HashMap<u64, u8>>);
+
+/// The [`block entities`](`crate::prelude::BlockEntity`) of the blocks of a chunk.
+#[derive(Component, Default)]
+pub struct ChunkBlockEntities(pub HashMap<BlockPos, Entity>);
 
 #[derive(Component)]
 pub struct ToUpdate;
 
+/// Marks a chunk whose meshes are generated again from its grid, instead of being updated block
+/// by block. Used after many of the chunk's blocks changed at once, like in a region edit.
+#[derive(Component)]
+pub struct ToRemesh;
+
+/// Keeps the chunks around the entity loaded. The entity must have a
+/// [`GlobalTransform`](`bevy_transform::prelude::GlobalTransform`). The chunks that are loaded are
+/// the union of the areas of all of the loaders, the [`CurrentChunk`](`super::CurrentChunk`) and
+/// the [`ChunkTickets`](`super::ChunkTickets`).
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ChunkLoader {
+    /// Chunks within this (horizontal) distance (in chunks) of the loader's chunk are loaded.
+    /// They are unloaded once they are further than `radius` +
+    /// [`unload_hysteresis`](`super::RenderDistance`).
+    pub radius: i32,
+    /// Like `radius`, but vertically.
+    pub vertical_radius: i32,
+    pub(crate) cords: Option<ChunkCords>,
+}
+
+impl ChunkLoader {
+    pub fn new(radius: i32) -> Self {
+        Self {
+            radius,
+            vertical_radius: DEFAULT_VERTICAL_LOAD_DISTANCE,
+            cords: None,
+        }
+    }
+
+    pub fn with_vertical_radius(mut self, vertical_radius: i32) -> Self {
+        self.vertical_radius = vertical_radius;
+        self
+    }
+
+    /// The chunk the loader is in, `None` until the loader's position is first processed.
+    pub fn cords(&self) -> Option<ChunkCords> {
+        self.cords
+    }
+}
+
+/// Marks a chunk that was modified since it was loaded, modified chunks are saved when they are
+/// unloaded (if the [`ChunkStorage`](`super::ChunkStorage`) resource exists).
+#[derive(Component)]
+pub struct ModifiedChunk;
+
 #[derive(Component)]
 pub struct MeshChunk {
     pub parent_chunk: Entity,
@@ -21,19 +94,27 @@ pub struct MeshChunk {
 #[derive(Component)]
 pub struct ChildMeshChunks {
     pub cube_mesh_chunk: Entity,
+    pub translucent_mesh_chunk: Entity,
     pub xsprite_mesh_chunk: Entity,
     pub custom_mesh_chunk: Entity,
+    pub fluid_mesh_chunk: Entity,
 }
 
 #[derive(Component)]
 pub struct CubeMeshChunk;
 
+#[derive(Component)]
+pub struct TranslucentMeshChunk;
+
 #[derive(Component)]
 pub struct XSpriteMeshChunk;
 
 #[derive(Component)]
 pub struct CustomMeshChunk;
 
+#[derive(Component)]
+pub struct FluidMeshChunk;
+
 #[derive(Component)]
 pub struct ToIntroduce {
     pub cords: ChunkCords,
@@ -43,22 +124,26 @@ pub struct ToIntroduce {
 #[derive(Component)]
 pub enum ChunkMeshType {
     Cube,
+    TranslucentCube,
     XSprite,
     Custom,
+    Fluid,
 }
 
 impl From<BlockMeshType> for ChunkMeshType {
     fn from(mesh_type: BlockMeshType) -> Self {
         match mesh_type {
+            BlockMeshType::TranslucentCube => Self::TranslucentCube,
             BlockMeshType::XSprite => Self::XSprite,
             BlockMeshType::Custom => Self::Custom,
+            BlockMeshType::Fluid => Self::Fluid,
             _ => Self::Cube,
         }
     }
 }
 
 impl<const N: usize> std::ops::Deref for ChunkGrid<N> {
-    type Target = Grid<BlockId, N>;
+    type Target = PalettedGrid<BlockId, N>;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
@@ -71,20 +156,102 @@ impl<const N: usize> std::ops::DerefMut for ChunkGrid<N> {
 }
 
 impl ChildMeshChunks {
+    /// All of the mesh chunks.
+    pub fn iter(&self) -> impl Iterator<Item = Entity> {
+        [
+            self.cube_mesh_chunk,
+            self.translucent_mesh_chunk,
+            self.xsprite_mesh_chunk,
+            self.custom_mesh_chunk,
+            self.fluid_mesh_chunk,
+        ]
+        .into_iter()
+    }
+
     pub fn get_from_type(&self, mesh_type: ChunkMeshType) -> Entity {
         match mesh_type {
             ChunkMeshType::Cube => self.cube_mesh_chunk,
+            ChunkMeshType::TranslucentCube => self.translucent_mesh_chunk,
             ChunkMeshType::XSprite => self.xsprite_mesh_chunk,
             ChunkMeshType::Custom => self.custom_mesh_chunk,
+            ChunkMeshType::Fluid => self.fluid_mesh_chunk,
+        }
+    }
+}
+
+impl ChunkFluidLevels {
+    /// The level of the fluid block at the position.
+    pub fn level(&self, block_pos: BlockPos) -> u8 {
+        self.0.get(&block_pos).copied().unwrap_or(MAX_FLUID_LEVEL)
+    }
+
+    /// Set the level of the fluid block at the position.
+    pub fn set_level(&mut self, block_pos: BlockPos, level: u8) {
+        if level >= MAX_FLUID_LEVEL {
+            self.0.remove(&block_pos);
+        } else {
+            self.0.insert(block_pos, level);
+        }
+    }
+}
+
+impl ChunkBlockStates {
+    /// The value of the property of the block at the position, if it has one.
+    pub fn get<P>(&self, block_pos: BlockPos) -> Option<P>
+    where
+        P: DynamicProperty + From<u8> + Into<u8> + Copy,
+    {
+        self.0
+            .get(&block_pos)?
+            .get(&property_id::<P>())
+            .map(|value| P::decode(*value))
+    }
+
+    /// Set the value of the property of the block at the position, returns the previous value.
+    pub fn set<P>(&mut self, block_pos: BlockPos, property: P) -> Option<P>
+    where
+        P: DynamicProperty + From<u8> + Into<u8> + Copy,
+    {
+        self.0
+            .entry(block_pos)
+            .or_default()
+            .insert(property_id::<P>(), property.encode())
+            .map(P::decode)
+    }
+
+    /// Remove the property from the block at the position, returns its value.
+    pub fn remove<P>(&mut self, block_pos: BlockPos) -> Option<P>
+    where
+        P: DynamicProperty + From<u8> + Into<u8> + Copy,
+    {
+        let properties = self.0.get_mut(&block_pos)?;
+        let value = properties.remove(&property_id::<P>());
+        if properties.is_empty() {
+            self.0.remove(&block_pos);
         }
+        value.map(P::decode)
+    }
+
+    /// Remove all of the properties of the block at the position.
+    pub fn clear(&mut self, block_pos: BlockPos) {
+        self.0.remove(&block_pos);
     }
 }
 
 impl ToIntroduce {
-    pub fn new(chunk_cords: ChunkCords) -> Self {
+    /// Introduce the chunk to all of its neighbors, except the ones outside of the
+    /// `vertical_range`, which will never be loaded.
+    pub fn new(chunk_cords: ChunkCords, vertical_range: &VerticalChunkRange) -> Self {
+        let mut adj_chunks_to_introduce = vec![Face::Right, Face::Left, Face::Front, Face::Back];
+        if vertical_range.contains(chunk_cords.y + 1) {
+            adj_chunks_to_introduce.push(Face::Top);
+        }
+        if vertical_range.contains(chunk_cords.y - 1) {
+            adj_chunks_to_introduce.push(Face::Bottom);
+        }
         Self {
             cords: chunk_cords,
-            adj_chunks_to_introduce: vec![Face::Right, Face::Left, Face::Front, Face::Back],
+            adj_chunks_to_introduce,
         }
     }
 }
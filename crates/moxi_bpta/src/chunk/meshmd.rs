@@ -7,6 +7,7 @@ pub enum ChunkMeshMd {
     Cube(CubeMD<BlockId>),
     Xsprite(XSpriteMD<BlockId>),
     Custom(CustomMD<BlockId>),
+    Fluid(FluidMD<BlockId>),
 }
 
 impl ChunkMeshMd {
@@ -25,6 +26,7 @@ impl ChunkMeshMd {
             ),
             ChunkMeshMd::Xsprite(md) => md.log_break(block_id, block_pos),
             ChunkMeshMd::Custom(md) => md.log_break(block_id, block_pos),
+            ChunkMeshMd::Fluid(md) => md.log_break(block_id, block_pos),
         }
     }
 
@@ -43,6 +45,7 @@ impl ChunkMeshMd {
             ),
             ChunkMeshMd::Xsprite(md) => md.log_add(placed_block_id, block_pos),
             ChunkMeshMd::Custom(md) => md.log_add(placed_block_id, block_pos),
+            ChunkMeshMd::Fluid(md) => md.log_add(placed_block_id, block_pos),
         }
     }
 
@@ -55,6 +58,7 @@ impl ChunkMeshMd {
     ) {
         match self {
             ChunkMeshMd::Cube(md) => md.log(update_type, block_pos, block_id, surrounding_blocks),
+            ChunkMeshMd::Fluid(md) => md.log_update(block_id, block_pos),
             ChunkMeshMd::Xsprite(_) | ChunkMeshMd::Custom(_) => {}
         }
     }
@@ -1,31 +1,51 @@
 pub(crate) mod chunkbuilder;
 pub(crate) mod components;
 pub(crate) mod meshmd;
+pub(crate) mod pipeline;
 pub(crate) mod resources;
+pub(crate) mod storage;
 pub(crate) mod systems;
 
 use self::systems::*;
-use bevy_app::{prelude::Plugin, Update};
+use bevy_app::{prelude::Plugin, Last, Update};
 use bevy_asset::Handle;
-use bevy_ecs::{
-    prelude::{apply_deferred, resource_changed},
-    schedule::IntoSystemConfigs,
-    system::Resource,
-};
+use bevy_ecs::{prelude::apply_deferred, schedule::IntoSystemConfigs, system::Resource};
 use bevy_pbr::StandardMaterial;
 
-pub use components::{Chunk, MeshChunk};
+use crate::world::{scheduled_ticks::BlockTicks, structure::StructurePlacements};
+pub use components::{
+    Chunk, ChunkBlockEntities, ChunkBlockStates, ChunkFluidLevels, ChunkLight, ChunkLoader,
+    MeshChunk, ModifiedChunk, ToRemesh,
+};
 use moxi_utils::prelude::ChunkCords;
-pub use resources::CurrentChunk;
+pub use pipeline::{
+    GenerationPipeline, GenerationStage, StageNeighbors, DEFAULT_PIPELINE_CACHE_CAPACITY,
+};
+pub use resources::{
+    ChunkBudget, ChunkTickets, CurrentChunk, LightUpdates, RenderDistance, TranslucentSorting,
+    VerticalChunkRange, DEFAULT_VERTICAL_LOAD_DISTANCE,
+};
+pub use storage::{ChunkStorage, StoredBlockEntities};
 
+#[derive(Clone, Copy)]
 pub struct MoxiChunkPlugin<const N: usize> {
     pub starting_chunk: ChunkCords,
+    /// The initial [`RenderDistance`], it can be changed at runtime through the resource.
+    pub render_distance: RenderDistance,
+    /// The initial [`ChunkBudget`], it can be changed at runtime through the resource.
+    pub budget: ChunkBudget,
+    /// The layers of chunks that make up the world, it can be changed at runtime through the
+    /// resource.
+    pub vertical_chunks: VerticalChunkRange,
 }
 
 impl<const N: usize> Default for MoxiChunkPlugin<N> {
     fn default() -> Self {
         Self {
-            starting_chunk: [0, 0].into(),
+            starting_chunk: [0, 0, 0].into(),
+            render_distance: RenderDistance::default(),
+            budget: ChunkBudget::default(),
+            vertical_chunks: VerticalChunkRange::default(),
         }
     }
 }
@@ -34,28 +54,47 @@ impl<const N: usize> Plugin for MoxiChunkPlugin<N> {
     fn build(&self, app: &mut bevy_app::App) {
         app.init_resource::<resources::ChunkMap>()
             .init_resource::<resources::ChunkQueue>()
-            .insert_resource(CurrentChunk(self.starting_chunk));
+            .init_resource::<ChunkTickets>()
+            .init_resource::<resources::LightUpdates>()
+            .init_resource::<TranslucentSorting>()
+            .init_resource::<StructurePlacements>()
+            .init_resource::<BlockTicks>()
+            .insert_resource(CurrentChunk(self.starting_chunk))
+            .insert_resource(self.render_distance)
+            .insert_resource(self.budget)
+            .insert_resource(self.vertical_chunks);
         app.add_systems(
             Update,
             (
+                update_chunk_loaders,
                 (
-                    (queue_chunks_to_spawn, despawn_chunks)
-                        .run_if(resource_changed::<CurrentChunk>()),
+                    queue_chunks_to_spawn,
+                    despawn_chunks::<N>,
                     build_chunks::<N>,
                     spawn_chunks::<N>,
-                    handle_chunk_updates,
+                    handle_chunk_updates::<N>,
                     introduce_adj_chunks::<N>,
                 ),
+                remesh_chunks::<N>,
                 apply_deferred,
+                process_light_updates::<N>,
+                relight_chunks::<N>,
+                sort_translucent_chunks,
             )
                 .chain(),
         );
+        app.add_systems(Last, save_modified_chunks_on_exit::<N>);
     }
 }
 
 #[derive(Resource)]
 pub struct CubeMeshMaterial(pub Handle<StandardMaterial>);
 
+/// The material of the [`translucent cubes`](`moxi_mesh_utils::prelude::BlockMeshType::TranslucentCube`),
+/// it should be alpha blended.
+#[derive(Resource)]
+pub struct TranslucentMeshMaterial(pub Handle<StandardMaterial>);
+
 #[derive(Resource)]
 pub struct XSpriteMeshMaterial(pub Handle<StandardMaterial>);
 
@@ -0,0 +1,282 @@
+//! Building chunks in ordered stages (terrain, caves, ores, decorations, ...), each stage mutates
+//! the grid left by the previous ones. A stage can look at the neighboring chunks, as they were
+//! after an earlier stage, so it can carry features across chunk borders.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::chunk::chunkbuilder::{ChunkBuilder, NoiseTerrainBuilder};
+use bevy_math::IVec3;
+use moxi_utils::prelude::{block_to_global_block_pos, BlockId, ChunkCords, Dimensions, Grid};
+
+/// How many neighbor grids the [`GenerationPipeline`] keeps by default, see
+/// [`GenerationPipeline::with_cache_capacity`].
+pub const DEFAULT_PIPELINE_CACHE_CAPACITY: usize = 1024;
+
+/// A stage of a [`GenerationPipeline`]. Stages must be deterministic, the stages of the
+/// neighboring chunks might run more than once.
+pub trait GenerationStage<const N: usize>: Send + Sync {
+    fn generate(
+        &self,
+        chunk_cords: ChunkCords,
+        grid: &mut Grid<BlockId, N>,
+        neighbors: &StageNeighbors<N>,
+    );
+}
+
+impl<const N: usize, F> GenerationStage<N> for F
+where
+    F: Fn(ChunkCords, &mut Grid<BlockId, N>, &StageNeighbors<N>) + Send + Sync,
+{
+    fn generate(
+        &self,
+        chunk_cords: ChunkCords,
+        grid: &mut Grid<BlockId, N>,
+        neighbors: &StageNeighbors<N>,
+    ) {
+        self(chunk_cords, grid, neighbors)
+    }
+}
+
+/// The terrain replaces the whole grid, so it should be the first stage.
+impl<const N: usize> GenerationStage<N> for NoiseTerrainBuilder {
+    fn generate(
+        &self,
+        chunk_cords: ChunkCords,
+        grid: &mut Grid<BlockId, N>,
+        _neighbors: &StageNeighbors<N>,
+    ) {
+        *grid = self.build_chunk(chunk_cords);
+    }
+}
+
+/// The grids of the neighboring chunks a stage asked for, as they were after the stage it
+/// depends on. Empty for stages that don't depend on their neighbors.
+pub struct StageNeighbors<const N: usize> {
+    dims: Dimensions,
+    grids: HashMap<ChunkCords, Grid<BlockId, N>>,
+}
+
+impl<const N: usize> StageNeighbors<N> {
+    pub fn get(&self, chunk_cords: ChunkCords) -> Option<&Grid<BlockId, N>> {
+        self.grids.get(&chunk_cords)
+    }
+
+    /// The block at the position (in blocks from the origin of the world), `None` if it isn't in
+    /// one of the neighbors.
+    pub fn get_block(&self, block: IVec3) -> Option<BlockId> {
+        let global_pos = block_to_global_block_pos(block, self.dims);
+        self.get(global_pos.cords)?.get_block(global_pos.pos)
+    }
+}
+
+struct PipelineStage<const N: usize> {
+    name: &'static str,
+    stage: Box<dyn GenerationStage<N>>,
+    /// The index of the stage the neighbors must have reached, and the (horizontal) distance of
+    /// the neighbors.
+    neighbors: Option<(usize, i32)>,
+}
+
+/// A [`ChunkBuilder`] that runs its stages in order on an empty (air) grid. The stages of the
+/// neighbors a stage depends on run without the chunks being built, their grids are cached so
+/// they aren't generated again for each of their neighbors.
+pub struct GenerationPipeline<const N: usize> {
+    chunk_dims: Dimensions,
+    stages: Vec<PipelineStage<N>>,
+    cache_capacity: usize,
+    cache: Mutex<StageCache<N>>,
+}
+
+/// The grids of the chunks after a number of stages, and their keys in the order they were
+/// added.
+type StageCache<const N: usize> = (
+    HashMap<(ChunkCords, usize), Grid<BlockId, N>>,
+    VecDeque<(ChunkCords, usize)>,
+);
+
+impl<const N: usize> GenerationPipeline<N> {
+    pub fn new(chunk_dims: Dimensions) -> Self {
+        Self {
+            chunk_dims,
+            stages: Vec::new(),
+            cache_capacity: DEFAULT_PIPELINE_CACHE_CAPACITY,
+            cache: Default::default(),
+        }
+    }
+
+    /// Add a stage, after the stages that were already added.
+    pub fn with_stage(
+        mut self,
+        name: &'static str,
+        stage: impl GenerationStage<N> + 'static,
+    ) -> Self {
+        self.stages.push(PipelineStage {
+            name,
+            stage: Box::new(stage),
+            neighbors: None,
+        });
+        self
+    }
+
+    /// Add a stage that needs the chunks within `radius` (horizontally) to have reached the
+    /// stage called `neighbor_stage`, their grids are passed to the stage.
+    ///
+    /// # Panics
+    /// If there is no earlier stage called `neighbor_stage`.
+    pub fn with_stage_after_neighbors(
+        mut self,
+        name: &'static str,
+        stage: impl GenerationStage<N> + 'static,
+        neighbor_stage: &'static str,
+        radius: i32,
+    ) -> Self {
+        let neighbor_stage = self
+            .stages
+            .iter()
+            .position(|stage| stage.name == neighbor_stage)
+            .unwrap_or_else(|| panic!("No stage called {neighbor_stage} before {name}"));
+        self.stages.push(PipelineStage {
+            name,
+            stage: Box::new(stage),
+            neighbors: Some((neighbor_stage, radius)),
+        });
+        self
+    }
+
+    /// How many neighbor grids are kept, the oldest grids are dropped first. Dropped grids are
+    /// generated again if they are needed.
+    pub fn with_cache_capacity(mut self, cache_capacity: usize) -> Self {
+        self.cache_capacity = cache_capacity;
+        self
+    }
+
+    /// The names of the stages, in order.
+    pub fn stage_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.stages.iter().map(|stage| stage.name)
+    }
+
+    /// The grid of the chunk after its first `stages` stages.
+    fn grid_after(&self, chunk_cords: ChunkCords, stages: usize) -> Grid<BlockId, N> {
+        if stages == 0 {
+            return Grid::new([0; N], self.chunk_dims);
+        }
+        if let Some(grid) = self.cache.lock().unwrap().0.get(&(chunk_cords, stages)) {
+            return grid.clone();
+        }
+
+        let mut grid = self.grid_after(chunk_cords, stages - 1);
+        let stage = &self.stages[stages - 1];
+        let mut neighbors = StageNeighbors {
+            dims: self.chunk_dims,
+            grids: HashMap::new(),
+        };
+        if let Some((neighbor_stage, radius)) = stage.neighbors {
+            for z in -radius..=radius {
+                for x in -radius..=radius {
+                    let neighbor = chunk_cords + IVec3::new(x, 0, z);
+                    if neighbor != chunk_cords {
+                        let neighbor_grid = self.grid_after(neighbor, neighbor_stage + 1);
+                        neighbors.grids.insert(neighbor, neighbor_grid);
+                    }
+                }
+            }
+        }
+        stage.stage.generate(chunk_cords, &mut grid, &neighbors);
+
+        let needed_by_neighbors = self
+            .stages
+            .iter()
+            .any(|stage| stage.neighbors.is_some_and(|(i, _)| i + 1 == stages));
+        if needed_by_neighbors && self.cache_capacity > 0 {
+            let (grids, order) = &mut *self.cache.lock().unwrap();
+            if grids.insert((chunk_cords, stages), grid.clone()).is_none() {
+                order.push_back((chunk_cords, stages));
+            }
+            while order.len() > self.cache_capacity {
+                let oldest = order.pop_front().unwrap();
+                grids.remove(&oldest);
+            }
+        }
+        grid
+    }
+}
+
+impl<const N: usize> ChunkBuilder<N, BlockId> for GenerationPipeline<N> {
+    fn build_chunk(&self, chunk_cords: ChunkCords) -> Grid<BlockId, N> {
+        self.grid_after(chunk_cords, self.stages.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use moxi_utils::prelude::BlockPos;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_generation_pipeline() {
+        let dims = Dimensions::new(4, 4, 4);
+        let terrain_runs = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&terrain_runs);
+        let pipeline = GenerationPipeline::<64>::new(dims)
+            .with_stage(
+                "terrain",
+                move |_: ChunkCords, grid: &mut Grid<BlockId, 64>, _: &StageNeighbors<64>| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    let _ = grid.set_block(1, BlockPos::ZERO);
+                },
+            )
+            .with_stage(
+                "ores",
+                |_: ChunkCords, grid: &mut Grid<BlockId, 64>, _: &StageNeighbors<64>| {
+                    let _ = grid.set_block(2, BlockPos::X);
+                },
+            )
+            .with_stage_after_neighbors(
+                "count",
+                |_: ChunkCords, grid: &mut Grid<BlockId, 64>, neighbors: &StageNeighbors<64>| {
+                    // The neighbors are after the terrain, without the ores.
+                    let count = neighbors
+                        .grids
+                        .values()
+                        .filter(|grid| grid.get_block(BlockPos::ZERO) == Some(1))
+                        .filter(|grid| grid.get_block(BlockPos::X) == Some(0))
+                        .count();
+                    let _ = grid.set_block(count as BlockId, BlockPos::Y);
+                },
+                "terrain",
+                1,
+            );
+        assert_eq!(
+            pipeline.stage_names().collect::<Vec<_>>(),
+            vec!["terrain", "ores", "count"]
+        );
+
+        let grid = pipeline.build_chunk([0, 0, 0].into());
+        assert_eq!(grid.get_block(BlockPos::ZERO), Some(1));
+        assert_eq!(grid.get_block(BlockPos::X), Some(2));
+        assert_eq!(grid.get_block(BlockPos::Y), Some(8));
+        assert_eq!(terrain_runs.load(Ordering::Relaxed), 9);
+
+        // The neighbors shared with the first chunk, and the chunk itself, come from the cache.
+        let other_grid = pipeline.build_chunk([1, 0, 0].into());
+        assert_eq!(other_grid.get_block(BlockPos::Y), Some(8));
+        assert_eq!(terrain_runs.load(Ordering::Relaxed), 12);
+
+        // Without a cache, the results are the same.
+        let terrain_runs_before = terrain_runs.load(Ordering::Relaxed);
+        let uncached = GenerationPipeline {
+            cache_capacity: 0,
+            cache: Default::default(),
+            ..pipeline
+        };
+        let uncached_grid = uncached.build_chunk([0, 0, 0].into());
+        assert_eq!(uncached_grid.get_block(BlockPos::Y), Some(8));
+        assert_eq!(
+            terrain_runs.load(Ordering::Relaxed),
+            terrain_runs_before + 9
+        );
+    }
+}
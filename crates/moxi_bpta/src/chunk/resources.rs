@@ -1,7 +1,9 @@
-use std::collections::hash_map::HashMap;
+use std::cmp::Reverse;
+use std::collections::{hash_map::HashMap, BinaryHeap, HashSet};
 
 use bevy_ecs::{entity::Entity, system::Resource};
-use moxi_utils::prelude::ChunkCords;
+use bevy_math::Vec3;
+use moxi_utils::prelude::{BlockGlobalPos, BlockPos, ChunkCords, Face};
 
 /// Resource that stores all the chunks in the world.
 /// The key is the chunk's cords, the value is the chunk's entity.
@@ -9,17 +11,168 @@ use moxi_utils::prelude::ChunkCords;
 #[derive(Resource)]
 pub struct ChunkMap(HashMap<ChunkCords, Entity>);
 
+/// Queue of the chunks that should be built, ordered by priority (lowest first). The priority of a
+/// chunk is its distance to the nearest loader, so the nearest chunks are built first.
 #[derive(Resource)]
-pub(crate) struct ChunkQueue(Vec<ChunkCords>);
+pub(crate) struct ChunkQueue {
+    heap: BinaryHeap<Reverse<(i32, i32, i32, i32)>>,
+    queued: HashSet<ChunkCords>,
+}
+
+/// How much chunk work is done each frame, to avoid stalling the frame when a lot of chunks need
+/// to be loaded at once (when teleporting for example).
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkBudget {
+    /// The maximum amount of chunks that start building (generating and meshing) each frame.
+    pub max_builds_per_frame: usize,
+    /// The maximum amount of built chunks that are spawned (and their meshes uploaded) each
+    /// frame.
+    pub max_uploads_per_frame: usize,
+}
+
+impl Default for ChunkBudget {
+    fn default() -> Self {
+        Self {
+            max_builds_per_frame: 16,
+            max_uploads_per_frame: 8,
+        }
+    }
+}
+
+/// The vertical distance (in chunks) chunks are loaded within by default.
+pub const DEFAULT_VERTICAL_LOAD_DISTANCE: i32 = 4;
+
+/// The layers of chunks (the y chunk coordinates) the world is made of, chunks outside of this
+/// range are never loaded. By default the world is a single layer of chunks (`0..=0`).
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VerticalChunkRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl VerticalChunkRange {
+    pub fn new(min: i32, max: i32) -> Self {
+        Self { min, max }
+    }
+
+    pub fn contains(&self, chunk_y: i32) -> bool {
+        (self.min..=self.max).contains(&chunk_y)
+    }
+
+    /// The closest layer in the range to `chunk_y`.
+    pub fn clamp(&self, chunk_y: i32) -> i32 {
+        chunk_y.clamp(self.min, self.max)
+    }
+}
 
 /// The current chunk is a resource that the plugin will refer to for the player's position.
 /// Chunks will be loaded and unloaded based on the `CurrentChunk` resource.
 #[derive(Resource)]
 pub struct CurrentChunk(pub ChunkCords);
 
+/// How far (in chunks) from the [`CurrentChunk`] chunks are loaded and unloaded. Can be changed
+/// at runtime.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderDistance {
+    /// Chunks within this (horizontal) distance of the current chunk are loaded.
+    pub load_distance: i32,
+    /// Chunks within this vertical distance of the current chunk are loaded, only matters if the
+    /// [`VerticalChunkRange`] has more than one layer.
+    pub vertical_load_distance: i32,
+    /// Loaded chunks are only unloaded once they are further than `load_distance +
+    /// unload_hysteresis` from the current chunk, so walking back and forth across a chunk border
+    /// doesn't unload and reload the chunks on the edge of the render distance.
+    pub unload_hysteresis: i32,
+}
+
+impl Default for RenderDistance {
+    fn default() -> Self {
+        Self {
+            load_distance: 10,
+            vertical_load_distance: DEFAULT_VERTICAL_LOAD_DISTANCE,
+            unload_hysteresis: 2,
+        }
+    }
+}
+
+impl RenderDistance {
+    pub fn new(load_distance: i32) -> Self {
+        Self {
+            load_distance,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_vertical_load_distance(mut self, vertical_load_distance: i32) -> Self {
+        self.vertical_load_distance = vertical_load_distance;
+        self
+    }
+
+    pub fn with_unload_hysteresis(mut self, unload_hysteresis: i32) -> Self {
+        self.unload_hysteresis = unload_hysteresis;
+        self
+    }
+
+    /// Chunks further than this (horizontal) distance from the current chunk are unloaded.
+    pub fn unload_distance(&self) -> i32 {
+        self.load_distance + self.unload_hysteresis.max(0)
+    }
+
+    /// Chunks further than this vertical distance from the current chunk are unloaded.
+    pub fn vertical_unload_distance(&self) -> i32 {
+        self.vertical_load_distance + self.unload_hysteresis.max(0)
+    }
+}
+
+/// Named sets of chunks that are kept loaded no matter where the loaders are, for example the
+/// spawn area. A chunk stays loaded as long as any ticket holds it.
+#[derive(Resource, Default)]
+pub struct ChunkTickets(HashMap<String, HashSet<ChunkCords>>);
+
+impl ChunkTickets {
+    /// Add chunks to the ticket with the given name, the ticket is created if needed.
+    pub fn add_ticket(
+        &mut self,
+        name: impl Into<String>,
+        chunks: impl IntoIterator<Item = ChunkCords>,
+    ) {
+        self.0.entry(name.into()).or_default().extend(chunks);
+    }
+
+    /// Add all of the chunks within `radius` of `center`, in the layer of `center`, to the ticket
+    /// with the given name.
+    pub fn add_area_ticket(&mut self, name: impl Into<String>, center: ChunkCords, radius: i32) {
+        self.add_ticket(
+            name,
+            (-radius..=radius)
+                .flat_map(|x| (-radius..=radius).map(move |z| center + ChunkCords::new(x, 0, z))),
+        );
+    }
+
+    /// Remove a ticket, its chunks will be unloaded if nothing else keeps them loaded. Returns
+    /// whether the ticket existed.
+    pub fn remove_ticket(&mut self, name: &str) -> bool {
+        self.0.remove(name).is_some()
+    }
+
+    pub fn get_ticket(&self, name: &str) -> Option<&HashSet<ChunkCords>> {
+        self.0.get(name)
+    }
+
+    /// Whether any ticket holds the chunk.
+    pub fn contains_chunk(&self, cords: ChunkCords) -> bool {
+        self.0.values().any(|chunks| chunks.contains(&cords))
+    }
+
+    /// All of the chunks held by tickets, a chunk can appear more than once.
+    pub fn iter_chunks(&self) -> impl Iterator<Item = ChunkCords> + '_ {
+        self.0.values().flatten().copied()
+    }
+}
+
 impl Default for CurrentChunk {
     fn default() -> Self {
-        Self([0, 0].into())
+        Self([0, 0, 0].into())
     }
 }
 
@@ -55,6 +208,10 @@ impl ChunkMap {
         self.0.insert(cords, entity);
     }
 
+    pub fn remove_chunk(&mut self, cords: ChunkCords) -> Option<Entity> {
+        self.0.remove(&cords)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (ChunkCords, Entity)> + '_ {
         self.0.iter().map(|(cords, entity)| (*cords, *entity))
     }
@@ -75,17 +232,105 @@ impl ChunkMap {
 
 impl Default for ChunkQueue {
     fn default() -> Self {
-        Self(Vec::with_capacity(100))
+        Self {
+            heap: BinaryHeap::with_capacity(100),
+            queued: HashSet::with_capacity(100),
+        }
     }
 }
 
 impl ChunkQueue {
-    pub fn push(&mut self, cords: ChunkCords) {
-        self.0.push(cords);
+    /// Queue a chunk, does nothing if the chunk is already queued.
+    pub fn push(&mut self, cords: ChunkCords, priority: i32) {
+        if self.queued.insert(cords) {
+            self.heap
+                .push(Reverse((priority, cords.x, cords.y, cords.z)));
+        }
+    }
+
+    /// Pop the chunk with the lowest priority.
+    pub fn pop(&mut self) -> Option<ChunkCords> {
+        let Reverse((_, x, y, z)) = self.heap.pop()?;
+        let cords = ChunkCords::new(x, y, z);
+        self.queued.remove(&cords);
+        Some(cords)
+    }
+
+    pub fn contains(&self, cords: ChunkCords) -> bool {
+        self.queued.contains(&cords)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
     }
 
-    pub fn drain(&mut self) -> impl Iterator<Item = ChunkCords> + '_ {
-        self.0.drain(..)
+    /// Recompute the priority of all of the queued chunks, chunks that `priority` returns `None`
+    /// for are removed from the queue.
+    pub fn reprioritize(&mut self, mut priority: impl FnMut(ChunkCords) -> Option<i32>) {
+        let heap = std::mem::take(&mut self.heap);
+        self.heap = heap
+            .into_iter()
+            .filter_map(|Reverse((_, x, y, z))| {
+                let cords = ChunkCords::new(x, y, z);
+                match priority(cords) {
+                    Some(priority) => Some(Reverse((priority, x, y, z))),
+                    None => {
+                        self.queued.remove(&cords);
+                        None
+                    }
+                }
+            })
+            .collect();
+    }
+}
+
+/// When the quads of the [`translucent`](`moxi_mesh_utils::prelude::CubeLayer::Translucent`)
+/// meshes are sorted back to front again. Meshes are sorted when they change, and all of them are
+/// sorted again when the camera moves far enough.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct TranslucentSorting {
+    /// How far (in blocks) the camera can move before the meshes are sorted again.
+    pub resort_distance: f32,
+    /// Where the camera was when all of the meshes were last sorted.
+    pub(crate) last_view_pos: Option<Vec3>,
+}
+
+impl Default for TranslucentSorting {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl TranslucentSorting {
+    pub fn new(resort_distance: f32) -> Self {
+        Self {
+            resort_distance,
+            last_view_pos: None,
+        }
+    }
+}
+
+/// The changes to the light that weren't processed yet, and the chunks whose meshes need to be
+/// lit again.
+#[derive(Resource, Default)]
+pub struct LightUpdates {
+    /// Chunk borders the light should spread across, by the chunk and the face of the border.
+    pub(crate) borders: Vec<(ChunkCords, Face)>,
+    /// Blocks that were changed.
+    pub(crate) blocks: Vec<BlockGlobalPos>,
+    pub(crate) relight: HashSet<ChunkCords>,
+}
+
+impl LightUpdates {
+    /// Update the light around a block that was changed.
+    pub fn push_block(&mut self, chunk_cords: ChunkCords, block_pos: BlockPos) {
+        self.blocks
+            .push(BlockGlobalPos::new(block_pos, chunk_cords));
+    }
+
+    /// Bake the light into the meshes of the chunk again.
+    pub fn relight_chunk(&mut self, chunk_cords: ChunkCords) {
+        self.relight.insert(chunk_cords);
     }
 }
 
@@ -97,22 +342,44 @@ mod tests {
     #[test]
     fn test_chunk_map() {
         let mut map = ChunkMap::default();
-        map.insert_chunk([0, 0].into(), Entity::PLACEHOLDER);
-        map.insert_chunk([0, 1].into(), Entity::from_raw(1));
-        assert_eq!(map.get_chunk([0, 0].into()), None);
+        map.insert_chunk([0, 0, 0].into(), Entity::PLACEHOLDER);
+        map.insert_chunk([0, 0, 1].into(), Entity::from_raw(1));
+        assert_eq!(map.get_chunk([0, 0, 0].into()), None);
         assert!(map
-            .get_chunk([0, 1].into())
+            .get_chunk([0, 0, 1].into())
             .map_or(false, |e| e.index() == 1));
     }
 
+    #[test]
+    fn test_chunk_tickets() {
+        let mut tickets = super::ChunkTickets::default();
+        tickets.add_area_ticket("spawn", [0, 0, 0].into(), 1);
+        tickets.add_ticket("portal", [[10, 0, 10].into()]);
+        assert_eq!(tickets.get_ticket("spawn").unwrap().len(), 9);
+        assert!(tickets.contains_chunk([-1, 0, 1].into()));
+        assert!(tickets.contains_chunk([10, 0, 10].into()));
+        assert!(!tickets.contains_chunk([2, 0, 0].into()));
+        assert!(tickets.remove_ticket("spawn"));
+        assert!(!tickets.remove_ticket("spawn"));
+        assert!(!tickets.contains_chunk([0, 0, 0].into()));
+        assert_eq!(tickets.iter_chunks().count(), 1);
+    }
+
     #[test]
     fn test_chunk_queue() {
         let mut queue = super::ChunkQueue::default();
-        queue.push([0, 0].into());
-        queue.push([0, 1].into());
+        queue.push([0, 0, 0].into(), 0);
+        queue.push([0, 0, 1].into(), 1);
+        queue.push([5, 0, 5].into(), 5);
+        queue.push([0, 0, 1].into(), 1);
+        queue.push([-2, 0, 0].into(), 2);
+        assert!(queue.contains([-2, 0, 0].into()));
+        queue.reprioritize(|cords| (cords.x >= 0).then_some(5 - cords.x - cords.z));
+        assert!(!queue.contains([-2, 0, 0].into()));
         assert_eq!(
-            queue.drain().collect::<Vec<_>>(),
-            vec![[0, 0].into(), [0, 1].into()]
+            std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>(),
+            vec![[5, 0, 5].into(), [0, 0, 1].into(), [0, 0, 0].into()]
         );
+        assert!(queue.is_empty());
     }
 }
@@ -0,0 +1,921 @@
+//! Persistence for chunks. Chunks are stored in region files, each region file holds the chunks
+//! of a [`REGION_SIZE`] x [`REGION_SIZE`] area of a single layer of chunks. The grids of the
+//! chunks, their [`scheduled ticks`](`crate::prelude::BlockTicks`), the
+//! [`states`](`crate::prelude::DynamicProperty`) of their blocks, their
+//! [`saved block entities`](`crate::prelude::SavedBlockEntity`) and the levels of their fluid blocks
+//! are stored in separate region files (`r.*.moxi`, `t.*.moxi`, `s.*.moxi`, `e.*.moxi` and
+//! `f.*.moxi`), with the same layout.
+//!
+//! Region file layout:
+//! - Header: [`REGION_SIZE`]^2 entries of (offset: u32, length: u32), little-endian. An entry with a
+//!   length of 0 means the chunk is not stored in the region.
+//! - The stored chunks, at the offsets from the header.
+//!
+//! Stored chunk layout:
+//! - The encoding of the chunk (u8), [`RAW_ENCODING`] or [`PALETTE_ENCODING`].
+//! - The dimensions of the chunk (3 x u32), little-endian.
+//! - Raw encoding: the blocks of the chunk, in index order, as little-endian [`BlockId`]s.
+//! - Palette encoding: the length of the palette (u32), the palette as little-endian
+//!   [`BlockId`]s, the bits per block (u8), and the packed data of the
+//!   [`PalettedGrid`] as little-endian u64s.
+//!
+//! Stored ticks layout:
+//! - The amount of ticks (u32), little-endian.
+//! - The ticks: the position of the block (3 x u32), the id of the update (u128) and the amount of
+//!   ticks left until the update (u64), little-endian.
+//!
+//! Stored block states layout:
+//! - The amount of values (u32), little-endian.
+//! - The values: the position of the block (3 x u32), the id of the property (u64) and the value
+//!   (u8), little-endian.
+//!
+//! Stored block entities layout:
+//! - The amount of block entities (u32), little-endian.
+//! - The block entities: the position of the block (3 x u32) and the length of the saved data
+//!   (u32), little-endian, followed by the saved data.
+//!
+//! Stored fluid levels layout:
+//! - The amount of levels (u32), little-endian.
+//! - The levels: the position of the block (3 x u32), little-endian, and its level (u8).
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bevy_ecs::system::Resource;
+use bevy_log::error;
+use bevy_math::IVec3;
+use bevy_tasks::IoTaskPool;
+use moxi_utils::prelude::{BlockId, BlockPos, ChunkCords, Dimensions, Grid, PalettedGrid};
+
+use crate::chunk::components::{ChunkBlockStates, ChunkFluidLevels};
+use crate::prelude::{BlockUpdateType, ScheduledTick};
+
+/// The width and length (in chunks) of the area of the world that each region file stores.
+pub const REGION_SIZE: i32 = 32;
+const HEADER_ENTRY_SIZE: usize = 8;
+const HEADER_SIZE: usize = (REGION_SIZE * REGION_SIZE) as usize * HEADER_ENTRY_SIZE;
+const BLOCK_ID_SIZE: usize = std::mem::size_of::<BlockId>();
+const TICK_SIZE: usize = 3 * 4 + 16 + 8;
+const BLOCK_STATE_SIZE: usize = 3 * 4 + 8 + 1;
+const FLUID_LEVEL_SIZE: usize = 3 * 4 + 1;
+const CHUNKS_PREFIX: &str = "r";
+const TICKS_PREFIX: &str = "t";
+const BLOCK_STATES_PREFIX: &str = "s";
+const BLOCK_ENTITIES_PREFIX: &str = "e";
+const FLUID_LEVELS_PREFIX: &str = "f";
+
+/// Every block is stored as is.
+pub const RAW_ENCODING: u8 = 0;
+/// The blocks are stored as a palette and bit-packed indices into it, see [`PalettedGrid`]. This
+/// is the encoding chunks are saved with.
+pub const PALETTE_ENCODING: u8 = 1;
+
+/// Resource that enables chunk persistence. When this resource is in the world, chunks that were
+/// modified are saved when they are unloaded, and chunks are loaded from the disk (if they were
+/// saved before) instead of being built by the [`ChunkBuilder`](`crate::prelude::ChunkBuilder`).
+///
+/// The chunks that are unloaded are written to the disk in the background, the data that wasn't
+/// written yet is loaded from memory. Clones of the storage share the data that waits to be
+/// written.
+#[derive(Resource, Clone)]
+pub struct ChunkStorage {
+    path: PathBuf,
+    /// The data that waits to be written to the region files, by the prefix of the region files
+    /// and the chunk. `None` removes the stored data of the chunk.
+    pending: Arc<Mutex<PendingEntries>>,
+    /// Held while the region files are read or written, so they are never read half-written.
+    io_lock: Arc<Mutex<()>>,
+}
+
+type PendingEntries = HashMap<(&'static str, ChunkCords), Option<Vec<u8>>>;
+
+/// The saved data of the [`block entities`](`crate::prelude::SavedBlockEntity`) of a chunk, by the
+/// position of their blocks.
+pub type StoredBlockEntities = HashMap<BlockPos, Vec<u8>>;
+
+impl ChunkStorage {
+    /// Store the region files in the directory at `path`, the directory will be created if
+    /// needed.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            pending: Default::default(),
+            io_lock: Default::default(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the grid of a chunk, returns `Ok(None)` if the chunk was never saved.
+    pub fn load_chunk<const N: usize>(
+        &self,
+        chunk_cords: ChunkCords,
+    ) -> io::Result<Option<PalettedGrid<BlockId, N>>> {
+        self.load_entry(CHUNKS_PREFIX, chunk_cords)?
+            .map(|data| decode_grid(&data))
+            .transpose()
+    }
+
+    /// Save the grid of a chunk, overwriting the previously saved grid of the chunk.
+    pub fn save_chunk<const N: usize>(
+        &self,
+        chunk_cords: ChunkCords,
+        grid: &PalettedGrid<BlockId, N>,
+    ) -> io::Result<()> {
+        self.save_chunks([(chunk_cords, grid)])
+    }
+
+    /// Save the grids of multiple chunks, each region file is only rewritten once.
+    pub fn save_chunks<'a, const N: usize>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a PalettedGrid<BlockId, N>)>,
+    ) -> io::Result<()> {
+        self.queue_chunks(chunks);
+        self.flush()
+    }
+
+    /// Queue the grids of the chunks to be written by the next [`ChunkStorage::flush`].
+    pub(crate) fn queue_chunks<'a, const N: usize>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a PalettedGrid<BlockId, N>)>,
+    ) {
+        self.queue_entries(
+            CHUNKS_PREFIX,
+            chunks
+                .into_iter()
+                .map(|(chunk_cords, grid)| (chunk_cords, Some(encode_grid(grid)))),
+        );
+    }
+
+    /// Load the scheduled ticks of a chunk, the delays of the ticks are relative to when they
+    /// were saved.
+    pub fn load_ticks(&self, chunk_cords: ChunkCords) -> io::Result<Vec<ScheduledTick>> {
+        self.load_entry(TICKS_PREFIX, chunk_cords)?
+            .map_or(Ok(vec![]), |data| decode_ticks(&data))
+    }
+
+    /// Save the scheduled ticks of multiple chunks, overwriting their previously saved ticks.
+    /// Chunks without ticks have their saved ticks removed.
+    pub fn save_ticks<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a [ScheduledTick])>,
+    ) -> io::Result<()> {
+        self.queue_ticks(chunks);
+        self.flush()
+    }
+
+    /// Queue the scheduled ticks of the chunks to be written by the next
+    /// [`ChunkStorage::flush`].
+    pub(crate) fn queue_ticks<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a [ScheduledTick])>,
+    ) {
+        self.queue_entries(
+            TICKS_PREFIX,
+            chunks.into_iter().map(|(chunk_cords, ticks)| {
+                (
+                    chunk_cords,
+                    (!ticks.is_empty()).then(|| encode_ticks(ticks)),
+                )
+            }),
+        );
+    }
+
+    /// Load the block states of a chunk, they are empty if the chunk was saved without any.
+    pub fn load_block_states(&self, chunk_cords: ChunkCords) -> io::Result<ChunkBlockStates> {
+        self.load_entry(BLOCK_STATES_PREFIX, chunk_cords)?
+            .map_or(Ok(ChunkBlockStates::default()), |data| {
+                decode_block_states(&data)
+            })
+    }
+
+    /// Save the block states of multiple chunks, overwriting their previously saved block states.
+    /// Chunks without block states have their saved block states removed.
+    pub fn save_block_states<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a ChunkBlockStates)>,
+    ) -> io::Result<()> {
+        self.queue_block_states(chunks);
+        self.flush()
+    }
+
+    /// Queue the block states of the chunks to be written by the next
+    /// [`ChunkStorage::flush`].
+    pub(crate) fn queue_block_states<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a ChunkBlockStates)>,
+    ) {
+        self.queue_entries(
+            BLOCK_STATES_PREFIX,
+            chunks.into_iter().map(|(chunk_cords, block_states)| {
+                (
+                    chunk_cords,
+                    (!block_states.0.is_empty()).then(|| encode_block_states(block_states)),
+                )
+            }),
+        );
+    }
+
+    /// Load the saved data of the block entities of a chunk.
+    pub fn load_block_entities(&self, chunk_cords: ChunkCords) -> io::Result<StoredBlockEntities> {
+        self.load_entry(BLOCK_ENTITIES_PREFIX, chunk_cords)?
+            .map_or(Ok(StoredBlockEntities::new()), |data| {
+                decode_block_entities(&data)
+            })
+    }
+
+    /// Save the data of the block entities of multiple chunks, overwriting their previously saved
+    /// block entities. Chunks without block entities have their saved block entities removed.
+    pub fn save_block_entities<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a StoredBlockEntities)>,
+    ) -> io::Result<()> {
+        self.queue_block_entities(chunks);
+        self.flush()
+    }
+
+    /// Queue the data of the block entities of the chunks to be written by the next
+    /// [`ChunkStorage::flush`].
+    pub(crate) fn queue_block_entities<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a StoredBlockEntities)>,
+    ) {
+        self.queue_entries(
+            BLOCK_ENTITIES_PREFIX,
+            chunks.into_iter().map(|(chunk_cords, block_entities)| {
+                (
+                    chunk_cords,
+                    (!block_entities.is_empty()).then(|| encode_block_entities(block_entities)),
+                )
+            }),
+        );
+    }
+
+    /// Load the levels of the fluid blocks of a chunk, the fluid blocks without a level are
+    /// sources.
+    pub fn load_fluid_levels(&self, chunk_cords: ChunkCords) -> io::Result<ChunkFluidLevels> {
+        self.load_entry(FLUID_LEVELS_PREFIX, chunk_cords)?
+            .map_or(Ok(ChunkFluidLevels::default()), |data| {
+                decode_fluid_levels(&data)
+            })
+    }
+
+    /// Save the levels of the fluid blocks of multiple chunks, overwriting their previously saved
+    /// levels. Chunks without levels have their saved levels removed.
+    pub fn save_fluid_levels<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a ChunkFluidLevels)>,
+    ) -> io::Result<()> {
+        self.queue_fluid_levels(chunks);
+        self.flush()
+    }
+
+    /// Queue the levels of the fluid blocks of the chunks to be written by the next
+    /// [`ChunkStorage::flush`].
+    pub(crate) fn queue_fluid_levels<'a>(
+        &self,
+        chunks: impl IntoIterator<Item = (ChunkCords, &'a ChunkFluidLevels)>,
+    ) {
+        self.queue_entries(
+            FLUID_LEVELS_PREFIX,
+            chunks.into_iter().map(|(chunk_cords, fluid_levels)| {
+                (
+                    chunk_cords,
+                    (!fluid_levels.0.is_empty()).then(|| encode_fluid_levels(fluid_levels)),
+                )
+            }),
+        );
+    }
+
+    /// Write all of the queued data to the region files, each region file is only rewritten once.
+    pub fn flush(&self) -> io::Result<()> {
+        let _io_lock = self.io_lock.lock().unwrap();
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let mut regions: HashMap<(&str, IVec3), RegionEntries> = HashMap::new();
+        for ((prefix, chunk_cords), data) in pending {
+            let (region, index) = region_of(chunk_cords);
+            regions
+                .entry((prefix, region))
+                .or_default()
+                .push((index, data));
+        }
+
+        fs::create_dir_all(&self.path)?;
+        for ((prefix, region), chunks) in regions {
+            let path = self.region_path(prefix, region);
+            let mut stored_chunks = match fs::read(&path) {
+                Ok(region_data) => decode_region(&region_data)?,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+                Err(err) => return Err(err),
+            };
+            for (index, data) in chunks {
+                match data {
+                    Some(data) => stored_chunks.insert(index, data),
+                    None => stored_chunks.remove(&index),
+                };
+            }
+            fs::write(path, encode_region(&stored_chunks))?;
+        }
+        Ok(())
+    }
+
+    /// [`Flush`](ChunkStorage::flush) the queued data in an [`IoTaskPool`] task, errors are
+    /// logged.
+    pub(crate) fn flush_in_background(&self) {
+        if self.pending.lock().unwrap().is_empty() {
+            return;
+        }
+        let chunk_storage = self.clone();
+        IoTaskPool::get()
+            .spawn(async move {
+                if let Err(err) = chunk_storage.flush() {
+                    error!(
+                        "Failed to save chunks to {}: {}",
+                        chunk_storage.path().display(),
+                        err
+                    );
+                }
+            })
+            .detach();
+    }
+
+    /// Load the data stored for a chunk in the region files with the prefix.
+    fn load_entry(
+        &self,
+        prefix: &'static str,
+        chunk_cords: ChunkCords,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let _io_lock = self.io_lock.lock().unwrap();
+        if let Some(data) = self.pending.lock().unwrap().get(&(prefix, chunk_cords)) {
+            return Ok(data.clone());
+        }
+        let (region, index) = region_of(chunk_cords);
+        let mut file = match fs::File::open(self.region_path(prefix, region)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let mut entry = [0; HEADER_ENTRY_SIZE];
+        file.seek(SeekFrom::Start((index * HEADER_ENTRY_SIZE) as u64))?;
+        file.read_exact(&mut entry)?;
+        let (offset, len) = decode_header_entry(&entry);
+        if len == 0 {
+            return Ok(None);
+        }
+
+        let mut data = vec![0; len as usize];
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    /// Queue the data of the chunks to be stored in the region files with the prefix, `None`
+    /// removes the data of the chunk.
+    fn queue_entries(
+        &self,
+        prefix: &'static str,
+        chunks: impl IntoIterator<Item = (ChunkCords, Option<Vec<u8>>)>,
+    ) {
+        let mut pending = self.pending.lock().unwrap();
+        for (chunk_cords, data) in chunks {
+            pending.insert((prefix, chunk_cords), data);
+        }
+    }
+
+    fn region_path(&self, prefix: &str, region: IVec3) -> PathBuf {
+        self.path.join(format!(
+            "{}.{}.{}.{}.moxi",
+            prefix, region.x, region.y, region.z
+        ))
+    }
+}
+
+/// The data to store for chunks of a region, by the index of the chunk in the region. `None`
+/// removes the stored data of the chunk.
+type RegionEntries = Vec<(usize, Option<Vec<u8>>)>;
+
+/// The region the chunk belongs to, and the index of the chunk in the region.
+fn region_of(chunk_cords: ChunkCords) -> (IVec3, usize) {
+    let region_size = IVec3::new(REGION_SIZE, 1, REGION_SIZE);
+    let region = chunk_cords.div_euclid(region_size);
+    let local = chunk_cords.rem_euclid(region_size);
+    (region, (local.z * REGION_SIZE + local.x) as usize)
+}
+
+fn decode_header_entry(entry: &[u8]) -> (u32, u32) {
+    (
+        u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+        u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+    )
+}
+
+fn decode_region(region_data: &[u8]) -> io::Result<BTreeMap<usize, Vec<u8>>> {
+    if region_data.len() < HEADER_SIZE {
+        return Err(invalid_data("Region file is too short"));
+    }
+    let mut chunks = BTreeMap::new();
+    for (index, entry) in region_data[..HEADER_SIZE]
+        .chunks(HEADER_ENTRY_SIZE)
+        .enumerate()
+    {
+        let (offset, len) = decode_header_entry(entry);
+        if len == 0 {
+            continue;
+        }
+        let data = offset
+            .checked_add(len)
+            .and_then(|end| region_data.get(offset as usize..end as usize))
+            .ok_or_else(|| invalid_data("Chunk is out of the region file's bounds"))?;
+        chunks.insert(index, data.to_vec());
+    }
+    Ok(chunks)
+}
+
+fn encode_region(chunks: &BTreeMap<usize, Vec<u8>>) -> Vec<u8> {
+    let mut header = vec![0; HEADER_SIZE];
+    let mut body = vec![];
+    for (index, data) in chunks {
+        let offset = (HEADER_SIZE + body.len()) as u32;
+        let entry = &mut header[index * HEADER_ENTRY_SIZE..(index + 1) * HEADER_ENTRY_SIZE];
+        entry[0..4].copy_from_slice(&offset.to_le_bytes());
+        entry[4..8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+    }
+    header.extend(body);
+    header
+}
+
+fn encode_grid<const N: usize>(grid: &PalettedGrid<BlockId, N>) -> Vec<u8> {
+    let mut grid = grid.clone();
+    grid.compact();
+    let mut data = Vec::with_capacity(
+        1 + 3 * 4 + 4 + grid.palette().len() * BLOCK_ID_SIZE + 1 + grid.packed_data().len() * 8,
+    );
+    data.push(PALETTE_ENCODING);
+    for d in grid.dims.to_array() {
+        data.extend_from_slice(&d.to_le_bytes());
+    }
+    data.extend_from_slice(&(grid.palette().len() as u32).to_le_bytes());
+    for block_id in grid.palette() {
+        data.extend_from_slice(&block_id.to_le_bytes());
+    }
+    data.push(grid.bits_per_block() as u8);
+    for word in grid.packed_data() {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+    data
+}
+
+fn decode_grid<const N: usize>(data: &[u8]) -> io::Result<PalettedGrid<BlockId, N>> {
+    if data.len() < 1 + 3 * 4 {
+        return Err(invalid_data("Stored chunk is too short"));
+    }
+    let dims = Dimensions::new(
+        u32::from_le_bytes(data[1..5].try_into().unwrap()),
+        u32::from_le_bytes(data[5..9].try_into().unwrap()),
+        u32::from_le_bytes(data[9..13].try_into().unwrap()),
+    );
+    let volume = dims
+        .x
+        .checked_mul(dims.y)
+        .and_then(|area| area.checked_mul(dims.z));
+    if volume.map(|volume| volume as usize) != Some(N) {
+        return Err(invalid_data(
+            "Stored chunk's dimensions don't match the chunk size",
+        ));
+    }
+    match data[0] {
+        RAW_ENCODING => {
+            let blocks = &data[13..];
+            if blocks.len() != N * BLOCK_ID_SIZE {
+                return Err(invalid_data("Stored chunk has the wrong amount of blocks"));
+            }
+            let grid: [BlockId; N] = blocks
+                .chunks(BLOCK_ID_SIZE)
+                .map(|b| BlockId::from_le_bytes(b.try_into().unwrap()))
+                .collect::<Vec<_>>()
+                .try_into()
+                .unwrap();
+            Ok(PalettedGrid::from_grid(&Grid::new(grid, dims)))
+        }
+        PALETTE_ENCODING => {
+            let data = &data[13..];
+            let palette_len = data
+                .get(0..4)
+                .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+                .ok_or_else(|| invalid_data("Stored chunk is too short"))?;
+            let palette_end = 4 + palette_len * BLOCK_ID_SIZE;
+            let (Some(palette), Some(&bits_per_block), Some(packed_data)) = (
+                data.get(4..palette_end),
+                data.get(palette_end),
+                data.get(palette_end + 1..),
+            ) else {
+                return Err(invalid_data("Stored chunk is too short"));
+            };
+            if packed_data.len() % 8 != 0 {
+                return Err(invalid_data("Stored chunk has the wrong amount of blocks"));
+            }
+            let palette = palette
+                .chunks(BLOCK_ID_SIZE)
+                .map(|b| BlockId::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            let packed_data = packed_data
+                .chunks(8)
+                .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+                .collect();
+            PalettedGrid::from_raw_parts(dims, palette, bits_per_block as u32, packed_data)
+                .ok_or_else(|| invalid_data("Stored chunk's palette is invalid"))
+        }
+        _ => Err(invalid_data("Unknown chunk encoding")),
+    }
+}
+
+fn encode_ticks(ticks: &[ScheduledTick]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(4 + ticks.len() * TICK_SIZE);
+    data.extend_from_slice(&(ticks.len() as u32).to_le_bytes());
+    for tick in ticks {
+        for c in tick.block_pos.to_array() {
+            data.extend_from_slice(&c.to_le_bytes());
+        }
+        data.extend_from_slice(&tick.update.to_u128().to_le_bytes());
+        data.extend_from_slice(&tick.delay.to_le_bytes());
+    }
+    data
+}
+
+fn decode_ticks(data: &[u8]) -> io::Result<Vec<ScheduledTick>> {
+    let len = data
+        .get(0..4)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .ok_or_else(|| invalid_data("Stored ticks are too short"))?;
+    let ticks = &data[4..];
+    if ticks.len() != len * TICK_SIZE {
+        return Err(invalid_data("Stored ticks have the wrong length"));
+    }
+    Ok(ticks
+        .chunks(TICK_SIZE)
+        .map(|tick| {
+            let u32_at =
+                |i: usize| u32::from_le_bytes(tick[i * 4..(i + 1) * 4].try_into().unwrap());
+            ScheduledTick {
+                block_pos: BlockPos::new(u32_at(0), u32_at(1), u32_at(2)),
+                update: BlockUpdateType::from_u128(u128::from_le_bytes(
+                    tick[12..28].try_into().unwrap(),
+                )),
+                delay: u64::from_le_bytes(tick[28..36].try_into().unwrap()),
+            }
+        })
+        .collect())
+}
+
+fn encode_block_states(block_states: &ChunkBlockStates) -> Vec<u8> {
+    let values = block_states
+        .0
+        .iter()
+        .flat_map(|(block_pos, properties)| {
+            properties
+                .iter()
+                .map(move |(property_id, value)| (*block_pos, *property_id, *value))
+        })
+        .collect::<Vec<_>>();
+    let mut data = Vec::with_capacity(4 + values.len() * BLOCK_STATE_SIZE);
+    data.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for (block_pos, property_id, value) in values {
+        for c in block_pos.to_array() {
+            data.extend_from_slice(&c.to_le_bytes());
+        }
+        data.extend_from_slice(&property_id.to_le_bytes());
+        data.push(value);
+    }
+    data
+}
+
+fn decode_block_states(data: &[u8]) -> io::Result<ChunkBlockStates> {
+    let len = data
+        .get(0..4)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .ok_or_else(|| invalid_data("Stored block states are too short"))?;
+    let values = &data[4..];
+    if values.len() != len * BLOCK_STATE_SIZE {
+        return Err(invalid_data("Stored block states have the wrong length"));
+    }
+    let mut block_states = ChunkBlockStates::default();
+    for value in values.chunks(BLOCK_STATE_SIZE) {
+        let u32_at = |i: usize| u32::from_le_bytes(value[i * 4..(i + 1) * 4].try_into().unwrap());
+        block_states
+            .0
+            .entry(BlockPos::new(u32_at(0), u32_at(1), u32_at(2)))
+            .or_default()
+            .insert(
+                u64::from_le_bytes(value[12..20].try_into().unwrap()),
+                value[20],
+            );
+    }
+    Ok(block_states)
+}
+
+fn encode_block_entities(block_entities: &StoredBlockEntities) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&(block_entities.len() as u32).to_le_bytes());
+    for (block_pos, saved) in block_entities {
+        for c in block_pos.to_array() {
+            data.extend_from_slice(&c.to_le_bytes());
+        }
+        data.extend_from_slice(&(saved.len() as u32).to_le_bytes());
+        data.extend_from_slice(saved);
+    }
+    data
+}
+
+fn decode_block_entities(data: &[u8]) -> io::Result<StoredBlockEntities> {
+    let too_short = || invalid_data("Stored block entities are too short");
+    let u32_at = |i: usize| {
+        data.get(i..i + 4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .ok_or_else(too_short)
+    };
+    let len = u32_at(0)? as usize;
+    let mut block_entities = StoredBlockEntities::with_capacity(len);
+    let mut i = 4;
+    for _ in 0..len {
+        let block_pos = BlockPos::new(u32_at(i)?, u32_at(i + 4)?, u32_at(i + 8)?);
+        let saved_len = u32_at(i + 12)? as usize;
+        i += 16;
+        let saved = data.get(i..i + saved_len).ok_or_else(too_short)?;
+        block_entities.insert(block_pos, saved.to_vec());
+        i += saved_len;
+    }
+    if i != data.len() {
+        return Err(invalid_data("Stored block entities have the wrong length"));
+    }
+    Ok(block_entities)
+}
+
+fn encode_fluid_levels(fluid_levels: &ChunkFluidLevels) -> Vec<u8> {
+    let mut data = Vec::with_capacity(4 + fluid_levels.0.len() * FLUID_LEVEL_SIZE);
+    data.extend_from_slice(&(fluid_levels.0.len() as u32).to_le_bytes());
+    for (block_pos, level) in fluid_levels.0.iter() {
+        for c in block_pos.to_array() {
+            data.extend_from_slice(&c.to_le_bytes());
+        }
+        data.push(*level);
+    }
+    data
+}
+
+fn decode_fluid_levels(data: &[u8]) -> io::Result<ChunkFluidLevels> {
+    let len = data
+        .get(0..4)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .ok_or_else(|| invalid_data("Stored fluid levels are too short"))?;
+    let levels = &data[4..];
+    if levels.len() != len * FLUID_LEVEL_SIZE {
+        return Err(invalid_data("Stored fluid levels have the wrong length"));
+    }
+    Ok(ChunkFluidLevels(
+        levels
+            .chunks(FLUID_LEVEL_SIZE)
+            .map(|level| {
+                let u32_at =
+                    |i: usize| u32::from_le_bytes(level[i * 4..(i + 1) * 4].try_into().unwrap());
+                (BlockPos::new(u32_at(0), u32_at(1), u32_at(2)), level[12])
+            })
+            .collect(),
+    ))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use moxi_utils::prelude::BlockGrid;
+
+    #[test]
+    fn test_chunk_storage() {
+        let path = std::env::temp_dir().join(format!("moxi_storage_test_{}", std::process::id()));
+        let storage = ChunkStorage::new(&path);
+        let dims = Dimensions::new(2, 2, 2);
+        let grid1 =
+            PalettedGrid::from_grid(&Grid::<BlockId, 8>::new([0, 1, 2, 3, 4, 5, 6, 7], dims));
+        let grid2 = PalettedGrid::<BlockId, 8>::new(7, dims);
+
+        assert!(storage.load_chunk::<8>([0, 0, 0].into()).unwrap().is_none());
+        storage.save_chunk([0, 0, 0].into(), &grid1).unwrap();
+        storage.save_chunk([-1, 0, 33].into(), &grid2).unwrap();
+        storage.save_chunk([1, 0, 0].into(), &grid2).unwrap();
+        storage.save_chunk([0, 0, 0].into(), &grid2).unwrap();
+        storage.save_chunk([1, 0, 0].into(), &grid1).unwrap();
+        storage.save_chunk([1, -1, 0].into(), &grid2).unwrap();
+
+        let blocks = |grid: PalettedGrid<BlockId, 8>| {
+            grid.enumerate_blocks().map(|(_, b)| b).collect::<Vec<_>>()
+        };
+        let load = |cords: [i32; 3]| storage.load_chunk::<8>(cords.into()).unwrap().unwrap();
+        assert_eq!(blocks(load([0, 0, 0])), vec![7; 8]);
+        assert_eq!(blocks(load([-1, 0, 33])), vec![7; 8]);
+        assert_eq!(blocks(load([1, 0, 0])), (0..8).collect::<Vec<BlockId>>());
+        assert_eq!(blocks(load([1, -1, 0])), vec![7; 8]);
+        assert!(storage.load_chunk::<8>([2, 0, 0].into()).unwrap().is_none());
+        assert!(storage.load_chunk::<8>([0, 1, 0].into()).unwrap().is_none());
+
+        // Chunks saved with the raw encoding can still be loaded
+        let mut raw = vec![RAW_ENCODING];
+        for d in dims.to_array() {
+            raw.extend_from_slice(&d.to_le_bytes());
+        }
+        for block_id in (0..8 as BlockId).rev() {
+            raw.extend_from_slice(&block_id.to_le_bytes());
+        }
+        let grid = decode_grid::<8>(&raw).unwrap();
+        assert_eq!(blocks(grid), (0..8).rev().collect::<Vec<BlockId>>());
+
+        // Dimensions whose volume overflows, and chunks whose end overflows, are invalid
+        let mut overflowing_dims = raw.clone();
+        overflowing_dims[1..5].copy_from_slice(&((1u32 << 29) + 1).to_le_bytes());
+        overflowing_dims[5..9].copy_from_slice(&8u32.to_le_bytes());
+        overflowing_dims[9..13].copy_from_slice(&1u32.to_le_bytes());
+        let err = decode_grid::<8>(&overflowing_dims).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut region = vec![0; HEADER_SIZE];
+        region[0..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        region[4..8].copy_from_slice(&2u32.to_le_bytes());
+        let err = decode_region(&region).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Queued chunks are loaded from memory until they are written.
+        storage.queue_chunks([([2, 0, 0].into(), &grid1)]);
+        assert_eq!(blocks(load([2, 0, 0])), (0..8).collect::<Vec<BlockId>>());
+        storage.clone().flush().unwrap();
+        assert!(storage.pending.lock().unwrap().is_empty());
+        assert_eq!(blocks(load([2, 0, 0])), (0..8).collect::<Vec<BlockId>>());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_tick_storage() {
+        let path =
+            std::env::temp_dir().join(format!("moxi_tick_storage_test_{}", std::process::id()));
+        let storage = ChunkStorage::new(&path);
+        let ticks = [
+            ScheduledTick {
+                block_pos: BlockPos::new(1, 2, 3),
+                update: BlockUpdateType::from_u128(u128::MAX - 1),
+                delay: 20,
+            },
+            ScheduledTick {
+                block_pos: BlockPos::new(15, 0, 0),
+                update: BlockUpdateType::from_u128(7),
+                delay: 1,
+            },
+        ];
+
+        assert!(storage.load_ticks([0, 0, 0].into()).unwrap().is_empty());
+        storage
+            .save_ticks([
+                ([0, 0, 0].into(), &ticks[..]),
+                ([1, 0, 0].into(), &ticks[1..]),
+            ])
+            .unwrap();
+        assert_eq!(storage.load_ticks([0, 0, 0].into()).unwrap(), ticks);
+        assert_eq!(storage.load_ticks([1, 0, 0].into()).unwrap(), &ticks[1..]);
+        // The ticks don't overwrite the grids of the chunks.
+        assert!(storage.load_chunk::<8>([0, 0, 0].into()).unwrap().is_none());
+
+        // Saving no ticks removes the saved ticks.
+        storage.save_ticks([([0, 0, 0].into(), &[][..])]).unwrap();
+        assert!(storage.load_ticks([0, 0, 0].into()).unwrap().is_empty());
+        assert_eq!(storage.load_ticks([1, 0, 0].into()).unwrap(), &ticks[1..]);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_block_state_storage() {
+        let path = std::env::temp_dir().join(format!(
+            "moxi_block_state_storage_test_{}",
+            std::process::id()
+        ));
+        let storage = ChunkStorage::new(&path);
+        let mut block_states = ChunkBlockStates::default();
+        block_states.0.insert(
+            BlockPos::new(1, 2, 3),
+            HashMap::from([(7, 1), (u64::MAX, 255)]),
+        );
+        block_states
+            .0
+            .insert(BlockPos::new(15, 0, 0), HashMap::from([(7, 0)]));
+
+        assert!(storage
+            .load_block_states([0, 0, 0].into())
+            .unwrap()
+            .0
+            .is_empty());
+        storage
+            .save_block_states([([0, 0, 0].into(), &block_states)])
+            .unwrap();
+        assert_eq!(
+            storage.load_block_states([0, 0, 0].into()).unwrap().0,
+            block_states.0
+        );
+
+        // Saving no block states removes the saved block states.
+        storage
+            .save_block_states([([0, 0, 0].into(), &ChunkBlockStates::default())])
+            .unwrap();
+        assert!(storage
+            .load_block_states([0, 0, 0].into())
+            .unwrap()
+            .0
+            .is_empty());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_block_entity_storage() {
+        let path = std::env::temp_dir().join(format!(
+            "moxi_block_entity_storage_test_{}",
+            std::process::id()
+        ));
+        let storage = ChunkStorage::new(&path);
+        let block_entities = StoredBlockEntities::from([
+            (BlockPos::new(1, 2, 3), vec![1, 2, 3]),
+            (BlockPos::new(15, 0, 0), vec![]),
+        ]);
+
+        assert!(storage
+            .load_block_entities([0, 0, 0].into())
+            .unwrap()
+            .is_empty());
+        storage
+            .save_block_entities([([0, 0, 0].into(), &block_entities)])
+            .unwrap();
+        assert_eq!(
+            storage.load_block_entities([0, 0, 0].into()).unwrap(),
+            block_entities
+        );
+        assert!(decode_block_entities(&encode_block_entities(&block_entities)[..20]).is_err());
+
+        // Saving no block entities removes the saved block entities.
+        storage
+            .save_block_entities([([0, 0, 0].into(), &StoredBlockEntities::new())])
+            .unwrap();
+        assert!(storage
+            .load_block_entities([0, 0, 0].into())
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_fluid_level_storage() {
+        let path = std::env::temp_dir().join(format!(
+            "moxi_fluid_level_storage_test_{}",
+            std::process::id()
+        ));
+        let storage = ChunkStorage::new(&path);
+        let fluid_levels = ChunkFluidLevels(HashMap::from([
+            (BlockPos::new(1, 2, 3), 4),
+            (BlockPos::new(15, 0, 0), 1),
+        ]));
+
+        assert!(storage
+            .load_fluid_levels([0, 0, 0].into())
+            .unwrap()
+            .0
+            .is_empty());
+        storage
+            .save_fluid_levels([([0, 0, 0].into(), &fluid_levels)])
+            .unwrap();
+        assert_eq!(
+            storage.load_fluid_levels([0, 0, 0].into()).unwrap().0,
+            fluid_levels.0
+        );
+
+        // Saving no levels removes the saved levels.
+        storage
+            .save_fluid_levels([([0, 0, 0].into(), &ChunkFluidLevels::default())])
+            .unwrap();
+        assert!(storage
+            .load_fluid_levels([0, 0, 0].into())
+            .unwrap()
+            .0
+            .is_empty());
+
+        fs::remove_dir_all(path).unwrap();
+    }
+}
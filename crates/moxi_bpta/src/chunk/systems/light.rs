@@ -0,0 +1,242 @@
+use std::collections::HashSet;
+
+use bevy_asset::{Assets, Handle};
+use bevy_ecs::{prelude::*, system::SystemParam};
+use bevy_math::IVec3;
+use bevy_render::mesh::Mesh;
+use moxi_mesh_utils::prelude::{
+    bake_cube_light, bake_custom_light, bake_fluid_light, bake_xsprite_light,
+};
+use moxi_utils::prelude::{
+    adj_chunk, is_block_pos_on_edge, light_chunk_border, update_light, BlockGlobalPos, BlockGrid,
+    BlockId, BlockPos, ChunkCords, Dimensions, LightChannel, LightGrid, LightWorld, FACES,
+    MAX_LIGHT,
+};
+
+use crate::{
+    blockreg::{lightreg::LightReg, meshreg::MeshReg},
+    chunk::{
+        components::{ChildMeshChunks, ChunkGrid, ChunkLight},
+        meshmd::ChunkMeshMd,
+        resources::{ChunkMap, LightUpdates, VerticalChunkRange},
+    },
+    prelude::PLACEHOLDER_DIMS,
+};
+
+/// Spread the light across the borders of the chunks that were spawned, and around the blocks
+/// that were changed.
+pub fn process_light_updates<const N: usize>(
+    mut light_updates: ResMut<LightUpdates>,
+    light_registry: Res<LightReg>,
+    chunk_map: Res<ChunkMap>,
+    vertical_range: Res<VerticalChunkRange>,
+    mut chunks: Query<ChunksLightQuery<N>>,
+) {
+    let LightUpdates {
+        borders,
+        blocks,
+        relight,
+    } = light_updates.as_mut();
+    if borders.is_empty() && blocks.is_empty() {
+        return;
+    }
+    let light_registry = light_registry.into_inner();
+    let mut world = LoadedChunksLight {
+        chunk_map: &chunk_map,
+        chunks: &mut chunks,
+        vertical_range: *vertical_range,
+        dims: unsafe { PLACEHOLDER_DIMS },
+        relight,
+    };
+    borders.retain(|(chunk_cords, face)| {
+        // The chunk was spawned this frame, and can't be accessed yet.
+        if !world.is_loaded(*chunk_cords) {
+            return chunk_map.contains_chunk(*chunk_cords);
+        }
+        // If the adjacent chunk isn't loaded, the light will spread across the border when it is.
+        let adj_chunk_cords = adj_chunk(*chunk_cords, *face);
+        if world.is_loaded(adj_chunk_cords) {
+            light_chunk_border(&mut world, light_registry, *chunk_cords, *face);
+            // The faces on the border are lit by the blocks across it.
+            world.relight.insert(*chunk_cords);
+            world.relight.insert(adj_chunk_cords);
+        }
+        false
+    });
+    for pos in blocks.drain(..) {
+        update_light(&mut world, light_registry, pos);
+    }
+}
+
+/// The meshes of the chunks, and the registries needed to bake the light into them.
+#[derive(SystemParam)]
+pub struct ChunkMeshesToLight<'w, 's> {
+    mesh_chunks: Query<'w, 's, (&'static Handle<Mesh>, &'static ChunkMeshMd)>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    mesh_registry: Res<'w, MeshReg>,
+    light_registry: Res<'w, LightReg>,
+}
+
+/// Bake the light into the meshes of the chunks that need to be lit again.
+pub fn relight_chunks<const N: usize>(
+    mut light_updates: ResMut<LightUpdates>,
+    chunk_map: Res<ChunkMap>,
+    vertical_range: Res<VerticalChunkRange>,
+    chunks: Query<(&ChunkGrid<N>, &ChunkLight<N>, &ChildMeshChunks)>,
+    chunk_meshes: ChunkMeshesToLight,
+) {
+    let ChunkMeshesToLight {
+        mesh_chunks,
+        mut meshes,
+        mesh_registry,
+        light_registry,
+    } = chunk_meshes;
+    let mesh_registry = mesh_registry.into_inner();
+    let light_registry = light_registry.into_inner();
+    for chunk_cords in light_updates.relight.drain() {
+        let Some(Ok((chunk_grid, chunk_light, child_mesh_chunks))) = chunk_map
+            .get_chunk(chunk_cords)
+            .map(|chunk_entity| chunks.get(chunk_entity))
+        else {
+            continue;
+        };
+        let light_at = |pos: IVec3| {
+            light_at(
+                chunk_cords,
+                &chunk_light.0,
+                pos,
+                &vertical_range,
+                |adj_chunk_cords, adj_block_pos| {
+                    let adj_chunk_entity = chunk_map.get_chunk(adj_chunk_cords)?;
+                    let (_, adj_chunk_light, _) = chunks.get(adj_chunk_entity).ok()?;
+                    adj_chunk_light.0.get_max_light(adj_block_pos)
+                },
+            )
+        };
+        for mesh_chunk in child_mesh_chunks.iter() {
+            let Ok((mesh_handle, chunk_mesh_md)) = mesh_chunks.get(mesh_chunk) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get_mut(mesh_handle) else {
+                continue;
+            };
+            match chunk_mesh_md {
+                ChunkMeshMd::Cube(md) => bake_cube_light(
+                    mesh,
+                    md,
+                    mesh_registry,
+                    light_registry,
+                    &chunk_grid.0,
+                    light_at,
+                ),
+                ChunkMeshMd::Xsprite(md) => bake_xsprite_light(
+                    mesh,
+                    md,
+                    mesh_registry,
+                    light_registry,
+                    &chunk_grid.0,
+                    light_at,
+                ),
+                ChunkMeshMd::Custom(md) => bake_custom_light(
+                    mesh,
+                    md,
+                    mesh_registry,
+                    light_registry,
+                    &chunk_grid.0,
+                    light_at,
+                ),
+                ChunkMeshMd::Fluid(md) => bake_fluid_light(
+                    mesh,
+                    md,
+                    mesh_registry,
+                    light_registry,
+                    &chunk_grid.0,
+                    light_at,
+                ),
+            }
+        }
+    }
+}
+
+/// The light level at a position relative to the chunk, the position can be in one of the
+/// adjacent chunks, whose light is returned by `adj_light`. If the adjacent chunk isn't loaded,
+/// only the sky above the world is lit.
+pub(crate) fn light_at<const N: usize>(
+    chunk_cords: ChunkCords,
+    chunk_light: &LightGrid<N>,
+    pos: IVec3,
+    vertical_range: &VerticalChunkRange,
+    adj_light: impl Fn(ChunkCords, BlockPos) -> Option<u8>,
+) -> u8 {
+    let dims = chunk_light.dims.as_ivec3();
+    let chunk_offset = pos.div_euclid(dims);
+    let block_pos = pos.rem_euclid(dims).as_uvec3();
+    if chunk_offset == IVec3::ZERO {
+        return chunk_light.get_max_light(block_pos).unwrap_or(0);
+    }
+    let adj_chunk_cords = chunk_cords + chunk_offset;
+    adj_light(adj_chunk_cords, block_pos).unwrap_or(if adj_chunk_cords.y > vertical_range.max {
+        MAX_LIGHT
+    } else {
+        0
+    })
+}
+
+type ChunksLightQuery<const N: usize> = (&'static ChunkGrid<N>, &'static mut ChunkLight<N>);
+
+/// The light of all of the loaded chunks.
+struct LoadedChunksLight<'a, 'w, 's, const N: usize> {
+    chunk_map: &'a ChunkMap,
+    chunks: &'a mut Query<'w, 's, ChunksLightQuery<N>>,
+    vertical_range: VerticalChunkRange,
+    dims: Dimensions,
+    /// The chunks whose light was changed.
+    relight: &'a mut HashSet<ChunkCords>,
+}
+
+impl<'a, 'w, 's, const N: usize> LoadedChunksLight<'a, 'w, 's, N> {
+    fn is_loaded(&self, chunk_cords: ChunkCords) -> bool {
+        self.chunk_map
+            .get_chunk(chunk_cords)
+            .is_some_and(|chunk_entity| self.chunks.contains(chunk_entity))
+    }
+}
+
+impl<'a, 'w, 's, const N: usize> LightWorld<BlockId> for LoadedChunksLight<'a, 'w, 's, N> {
+    fn dims(&self) -> Dimensions {
+        self.dims
+    }
+
+    fn get_block(&self, pos: BlockGlobalPos) -> Option<BlockId> {
+        let chunk_entity = self.chunk_map.get_chunk(pos.cords)?;
+        let (chunk_grid, _) = self.chunks.get(chunk_entity).ok()?;
+        chunk_grid.0.get_block(pos.pos)
+    }
+
+    fn get_light(&self, pos: BlockGlobalPos, channel: LightChannel) -> Option<u8> {
+        let chunk_entity = self.chunk_map.get_chunk(pos.cords)?;
+        let (_, chunk_light) = self.chunks.get(chunk_entity).ok()?;
+        chunk_light.0.get_light(pos.pos, channel)
+    }
+
+    fn set_light(&mut self, pos: BlockGlobalPos, channel: LightChannel, level: u8) {
+        let Some(chunk_entity) = self.chunk_map.get_chunk(pos.cords) else {
+            return;
+        };
+        let Ok((_, mut chunk_light)) = self.chunks.get_mut(chunk_entity) else {
+            return;
+        };
+        let _ = chunk_light.0.set_light(pos.pos, channel, level);
+        self.relight.insert(pos.cords);
+        // The faces of the adjacent chunks that face the block are lit by it.
+        for face in FACES {
+            if is_block_pos_on_edge(pos.pos, face, self.dims) {
+                self.relight.insert(adj_chunk(pos.cords, face));
+            }
+        }
+    }
+
+    fn is_sky_exposed(&self, chunk_cords: ChunkCords) -> bool {
+        chunk_cords.y >= self.vertical_range.max
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{
+    chunk::{
+        components::ChunkLoader,
+        resources::{ChunkTickets, CurrentChunk, RenderDistance, VerticalChunkRange},
+    },
+    prelude::PLACEHOLDER_DIMS,
+};
+use bevy_ecs::{prelude::*, system::SystemParam};
+use bevy_transform::prelude::GlobalTransform;
+use moxi_utils::prelude::{
+    chunk_distance, point_to_chunk_cords, vertical_chunk_distance, ChunkCords,
+};
+
+/// All of the things that keep chunks loaded: the [`CurrentChunk`] (with the [`RenderDistance`]),
+/// the [`ChunkLoader`]s and the [`ChunkTickets`]. Only chunks in the [`VerticalChunkRange`] are
+/// loaded.
+#[derive(SystemParam)]
+pub struct ChunkLoaders<'w, 's> {
+    current_chunk: Res<'w, CurrentChunk>,
+    render_distance: Res<'w, RenderDistance>,
+    tickets: Res<'w, ChunkTickets>,
+    vertical_range: Res<'w, VerticalChunkRange>,
+    loaders: Query<'w, 's, Ref<'static, ChunkLoader>>,
+    removed_loaders: RemovedComponents<'w, 's, ChunkLoader>,
+}
+
+impl<'w, 's> ChunkLoaders<'w, 's> {
+    /// Whether the loaded area might have changed since the last time the system checked.
+    pub fn changed(&mut self) -> bool {
+        // Read all of the removed loaders so they aren't reported again the next time.
+        let loaders_removed = self.removed_loaders.read().count() > 0;
+        self.current_chunk.is_changed()
+            || self.render_distance.is_changed()
+            || self.tickets.is_changed()
+            || self.vertical_range.is_changed()
+            || self.loaders.iter().any(|loader| loader.is_changed())
+            || loaders_removed
+    }
+
+    /// The area of each loader.
+    pub fn iter_areas(&self) -> impl Iterator<Item = LoadArea> + '_ {
+        let current_chunk_area = LoadArea {
+            center: self.current_chunk.get(),
+            radius: self.render_distance.load_distance,
+            vertical_radius: self.render_distance.vertical_load_distance,
+        };
+        std::iter::once(current_chunk_area)
+            .chain(self.loaders.iter().filter_map(|loader| {
+                Some(LoadArea {
+                    center: loader.cords?,
+                    radius: loader.radius,
+                    vertical_radius: loader.vertical_radius,
+                })
+            }))
+            .map(|mut area| {
+                // Loaders above or below the world still load the layers closest to them.
+                area.center.y = self.vertical_range.clamp(area.center.y);
+                area
+            })
+    }
+
+    /// All of the chunks that should be loaded, a chunk can appear more than once.
+    pub fn iter_chunks_to_load(&self) -> impl Iterator<Item = ChunkCords> + '_ {
+        self.iter_areas()
+            .flat_map(|area| area.iter_chunks())
+            .chain(self.tickets.iter_chunks())
+            .filter(|cords| self.vertical_range.contains(cords.y))
+    }
+
+    /// The priority to build the chunk with: its distance (in chunks) to the nearest loader.
+    /// `None` if the chunk shouldn't be loaded.
+    pub fn load_priority(&self, cords: ChunkCords) -> Option<i32> {
+        if !self.vertical_range.contains(cords.y) {
+            return None;
+        }
+        let mut in_area = false;
+        let mut nearest = i32::MAX;
+        for area in self.iter_areas() {
+            in_area |= area.contains(cords, 0);
+            nearest = nearest.min(area.distance(cords));
+        }
+        (in_area || self.tickets.contains_chunk(cords)).then_some(nearest)
+    }
+
+    /// Whether the chunk should stay loaded, chunks are kept loaded up to
+    /// [`unload_hysteresis`](`RenderDistance::unload_hysteresis`) chunks outside of the loaders'
+    /// areas.
+    pub fn keeps_loaded(&self, cords: ChunkCords) -> bool {
+        let hysteresis = self.render_distance.unload_hysteresis.max(0);
+        self.vertical_range.contains(cords.y)
+            && (self
+                .iter_areas()
+                .any(|area| area.contains(cords, hysteresis))
+                || self.tickets.contains_chunk(cords))
+    }
+}
+
+/// The chunks around a loader.
+#[derive(Clone, Copy, Debug)]
+pub struct LoadArea {
+    pub center: ChunkCords,
+    pub radius: i32,
+    pub vertical_radius: i32,
+}
+
+impl LoadArea {
+    /// Whether the chunk is in the area, when it's extended by `extra` chunks in every direction.
+    pub fn contains(&self, cords: ChunkCords, extra: i32) -> bool {
+        chunk_distance(self.center, cords) <= self.radius + extra
+            && vertical_chunk_distance(self.center, cords) <= self.vertical_radius + extra
+    }
+
+    /// The distance (in chunks) from the center of the area to the chunk.
+    pub fn distance(&self, cords: ChunkCords) -> i32 {
+        chunk_distance(self.center, cords).max(vertical_chunk_distance(self.center, cords))
+    }
+
+    pub fn iter_chunks(self) -> impl Iterator<Item = ChunkCords> {
+        let LoadArea {
+            center,
+            radius,
+            vertical_radius,
+        } = self;
+        (-vertical_radius..=vertical_radius).flat_map(move |y| {
+            (-radius..=radius).flat_map(move |x| {
+                (-radius..=radius).map(move |z| center + ChunkCords::new(x, y, z))
+            })
+        })
+    }
+}
+
+/// Update the chunk each [`ChunkLoader`] is in.
+pub fn update_chunk_loaders(mut loaders: Query<(&mut ChunkLoader, &GlobalTransform)>) {
+    for (mut loader, transform) in loaders.iter_mut() {
+        let cords = point_to_chunk_cords(transform.translation(), unsafe { PLACEHOLDER_DIMS });
+        if loader.cords != Some(cords) {
+            loader.cords = Some(cords);
+        }
+    }
+}
@@ -1,7 +1,11 @@
+mod light;
+mod loaders;
 mod misc;
 mod spawn;
 mod update;
 
+pub(crate) use light::*;
+pub(crate) use loaders::*;
 pub(crate) use spawn::*;
 pub(crate) use update::*;
 
@@ -1,49 +1,86 @@
+use std::io;
 use std::sync::Arc;
 
 use crate::{
-    blockreg::meshreg::MeshReg,
+    blockreg::{lightreg::LightReg, meshreg::MeshReg},
     chunk::{
-        chunkbuilder::{BoxedBuilder, ChunkBuilder},
+        chunkbuilder::BoxedBuilder,
         components::{
-            ChildMeshChunks, Chunk, ChunkGrid, CubeMeshChunk, CustomMeshChunk, MeshChunk,
-            ToIntroduce, XSpriteMeshChunk,
+            ChildMeshChunks, Chunk, ChunkBlockEntities, ChunkBlockStates, ChunkFluidLevels,
+            ChunkGrid, ChunkLight, CubeMeshChunk, CustomMeshChunk, FluidMeshChunk, MeshChunk,
+            ModifiedChunk, ToIntroduce, TranslucentMeshChunk, XSpriteMeshChunk,
         },
         meshmd::ChunkMeshMd,
-        resources::{ChunkMap, ChunkQueue, CurrentChunk},
-        CubeMeshMaterial, CustomMeshMaterial, XSpriteMeshMaterial,
+        resources::{ChunkBudget, ChunkMap, ChunkQueue, LightUpdates, VerticalChunkRange},
+        storage::{ChunkStorage, StoredBlockEntities},
+        systems::{light_at, ChunkLoaders},
+        CubeMeshMaterial, CustomMeshMaterial, TranslucentMeshMaterial, XSpriteMeshMaterial,
     },
-    plugin::RENDER_DISTANCE,
     prelude::components::ChunkMeshType,
+    world::{
+        block_entity::{BlockEntity, BlockEntitySpawners},
+        scheduled_ticks::{BlockTicks, ScheduledTick},
+        structure::StructurePlacements,
+    },
 };
+use bevy_app::AppExit;
 use bevy_asset::Assets;
 use bevy_ecs::prelude::*;
+use bevy_ecs::system::SystemParam;
+use bevy_ecs::world::EntityRef;
 use bevy_hierarchy::{BuildChildren, DespawnRecursiveExt};
-use bevy_math::prelude::Vec3;
+use bevy_log::error;
+use bevy_math::prelude::{IVec3, Vec3};
 use bevy_pbr::PbrBundle;
 use bevy_render::mesh::Mesh;
 use bevy_render::prelude::SpatialBundle;
 use bevy_tasks::{prelude::AsyncComputeTaskPool, Task};
 use bevy_transform::prelude::Transform;
 use moxi_mesh_utils::prelude::{
-    meshify_cubic_voxels, meshify_custom_voxels, meshify_xsprite_voxels, MeshingAlgorithm,
+    bake_cube_light, bake_custom_light, bake_fluid_light, bake_xsprite_light, meshify_cubic_voxels,
+    meshify_custom_voxels, meshify_fluid_voxels, meshify_translucent_cubic_voxels,
+    meshify_xsprite_voxels, CubeMD, CustomMD, FluidMD, MeshingAlgorithm, XSpriteMD,
+};
+use moxi_utils::prelude::{
+    light_chunk, BlockGrid, BlockId, BlockPos, ChunkCords, Face, Grid, Orientation, PalettedGrid,
+    FACES,
 };
-use moxi_utils::prelude::{chunk_distance, ChunkCords, Face};
 
 const CHUNK_TRANSLATION_OFFSET: Vec3 = Vec3::splat(0.0);
 
 #[derive(Component)]
-pub struct ComputeChunk<const N: usize>(Task<Option<ChunkGenResult<N>>>);
+pub struct ComputeChunk<const N: usize> {
+    pub cords: ChunkCords,
+    task: Task<Option<ChunkGenResult<N>>>,
+}
 
 #[derive(Component)]
 pub struct ChunkGenResult<const N: usize> {
     pub cords: ChunkCords,
     pub cube_mesh: Mesh,
     pub cube_mesh_md: ChunkMeshMd,
+    pub translucent_mesh: Mesh,
+    pub translucent_mesh_md: ChunkMeshMd,
     pub xsprite_mesh: Mesh,
     pub xsprite_mesh_md: ChunkMeshMd,
     pub custom_mesh: Mesh,
     pub custom_mesh_md: ChunkMeshMd,
+    pub fluid_mesh: Mesh,
+    pub fluid_mesh_md: ChunkMeshMd,
     pub chunk_grid: ChunkGrid<N>,
+    pub chunk_light: ChunkLight<N>,
+    /// The amount of pending structure blocks that were set in the chunk after it was built, they
+    /// are removed from the [`StructurePlacements`] once the chunk is spawned, and the chunk is
+    /// saved like a chunk that was modified.
+    pub structure_blocks: usize,
+    /// The scheduled ticks the chunk was saved with.
+    pub scheduled_ticks: Vec<ScheduledTick>,
+    /// The block states the chunk was saved with.
+    pub block_states: ChunkBlockStates,
+    /// The levels of the fluid blocks the chunk was saved with.
+    pub fluid_levels: ChunkFluidLevels,
+    /// The saved data of the block entities the chunk was saved with.
+    pub saved_block_entities: StoredBlockEntities,
 }
 
 pub fn spawn_chunks<const N: usize>(
@@ -51,171 +88,643 @@ pub fn spawn_chunks<const N: usize>(
     mut chunks_tasks_query: Query<(Entity, &mut ComputeChunk<N>)>,
     mut meshes: ResMut<Assets<Mesh>>,
     cube_mesh_material: Res<CubeMeshMaterial>,
+    translucent_mesh_material: Res<TranslucentMeshMaterial>,
     xsprite_mesh_material: Res<XSpriteMeshMaterial>,
     custom_mesh_material: Res<CustomMeshMaterial>,
     mut chunk_map: ResMut<ChunkMap>,
+    chunk_budget: Res<ChunkBudget>,
+    vertical_range: Res<VerticalChunkRange>,
+    mut light_updates: ResMut<LightUpdates>,
+    block_entity_spawners: Res<BlockEntitySpawners>,
+    mut block_ticks: ResMut<BlockTicks>,
+    structure_placements: Res<StructurePlacements>,
 ) {
     chunks_tasks_query
         .iter_mut()
-        .for_each(|(entity, mut task)| {
-            if let Some(chunk_generation_results) =
-                futures_lite::future::block_on(futures_lite::future::poll_once(&mut task.0))
-            {
-                commands.entity(entity).despawn();
-
-                let ChunkGenResult {
-                    cords,
-                    cube_mesh,
+        .filter_map(|(entity, mut compute_chunk)| {
+            futures_lite::future::block_on(futures_lite::future::poll_once(&mut compute_chunk.task))
+                .map(|chunk_generation_results| (entity, chunk_generation_results))
+        })
+        .take(chunk_budget.max_uploads_per_frame)
+        .for_each(|(entity, chunk_generation_results)| {
+            commands.entity(entity).despawn();
+
+            let ChunkGenResult {
+                cords,
+                cube_mesh,
+                cube_mesh_md,
+                translucent_mesh,
+                translucent_mesh_md,
+                xsprite_mesh,
+                xsprite_mesh_md,
+                custom_mesh,
+                custom_mesh_md,
+                fluid_mesh,
+                fluid_mesh_md,
+                chunk_grid,
+                chunk_light,
+                structure_blocks,
+                scheduled_ticks,
+                block_states,
+                fluid_levels,
+                saved_block_entities,
+            } = chunk_generation_results.unwrap();
+            if !chunk_map.contains_chunk(cords) {
+                // The chunk was cancelled while it was being built, the structure blocks stay
+                // pending until it's built again.
+                return;
+            }
+            structure_placements.remove_applied(cords, structure_blocks);
+            let parent_transform = Transform::from_translation(
+                Vec3 {
+                    x: cords.x as f32 * chunk_grid.dims.x as f32,
+                    y: cords.y as f32 * chunk_grid.dims.y as f32,
+                    z: cords.z as f32 * chunk_grid.dims.z as f32,
+                } + CHUNK_TRANSLATION_OFFSET,
+            );
+
+            let block_entity_positions: Vec<_> = if block_entity_spawners.is_empty() {
+                vec![]
+            } else {
+                chunk_grid
+                    .enumerate_blocks()
+                    .filter(|(_, block_id)| block_entity_spawners.has_block_entity(*block_id))
+                    .collect()
+            };
+            let parent_chunk = commands
+                .spawn((
+                    Chunk { cords },
+                    chunk_grid,
+                    chunk_light,
+                    fluid_levels,
+                    block_states,
+                    SpatialBundle::from_transform(parent_transform),
+                    ToIntroduce::new(cords, &vertical_range),
+                ))
+                .id();
+
+            let mut block_entities = ChunkBlockEntities::default();
+            for (block_pos, block_id) in block_entity_positions {
+                let Some(block_entity) = block_entity_spawners.spawn(
+                    &mut commands,
+                    parent_chunk,
+                    &mut block_entities,
+                    BlockEntity {
+                        block_id,
+                        chunk_cords: cords,
+                        block_pos,
+                    },
+                ) else {
+                    continue;
+                };
+                // The saved block entity replaces the fresh one.
+                let Some(saved) = saved_block_entities.get(&block_pos) else {
+                    continue;
+                };
+                if !block_entity_spawners.load(&mut commands.entity(block_entity), block_id, saved)
+                {
+                    error!(
+                        "Failed to load the block entity at {} of chunk {}",
+                        block_pos, cords
+                    );
+                }
+            }
+            commands.entity(parent_chunk).insert(block_entities);
+            if structure_blocks > 0 {
+                commands.entity(parent_chunk).insert(ModifiedChunk);
+            }
+
+            let cube_mesh_chunk = commands
+                .spawn((
+                    MeshChunk { parent_chunk },
+                    CubeMeshChunk,
+                    PbrBundle {
+                        mesh: meshes.add(cube_mesh),
+                        material: cube_mesh_material.0.clone(),
+                        ..Default::default()
+                    },
                     cube_mesh_md,
-                    xsprite_mesh,
+                    ChunkMeshType::Cube,
+                ))
+                .id();
+
+            let translucent_mesh_chunk = commands
+                .spawn((
+                    MeshChunk { parent_chunk },
+                    TranslucentMeshChunk,
+                    PbrBundle {
+                        mesh: meshes.add(translucent_mesh),
+                        material: translucent_mesh_material.0.clone(),
+                        ..Default::default()
+                    },
+                    translucent_mesh_md,
+                    ChunkMeshType::TranslucentCube,
+                ))
+                .id();
+
+            let xsprite_mesh_chunk = commands
+                .spawn((
+                    MeshChunk { parent_chunk },
+                    XSpriteMeshChunk,
+                    PbrBundle {
+                        mesh: meshes.add(xsprite_mesh),
+                        material: xsprite_mesh_material.0.clone(),
+                        ..Default::default()
+                    },
                     xsprite_mesh_md,
-                    custom_mesh,
+                    ChunkMeshType::XSprite,
+                ))
+                .id();
+
+            let custom_mesh_chunk = commands
+                .spawn((
+                    MeshChunk { parent_chunk },
+                    CustomMeshChunk,
+                    PbrBundle {
+                        mesh: meshes.add(custom_mesh),
+                        material: custom_mesh_material.0.clone(),
+                        ..Default::default()
+                    },
                     custom_mesh_md,
-                    chunk_grid,
-                } = chunk_generation_results.unwrap();
-                let parent_transform = Transform::from_translation(
-                    Vec3 {
-                        x: cords.x as f32 * chunk_grid.dims.x as f32,
-                        y: 0.0,
-                        z: cords.y as f32 * chunk_grid.dims.z as f32,
-                    } + CHUNK_TRANSLATION_OFFSET,
-                );
-
-                let parent_chunk = commands
-                    .spawn((
-                        Chunk { cords },
-                        chunk_grid,
-                        SpatialBundle::from_transform(parent_transform),
-                        ToIntroduce::new(cords),
-                    ))
-                    .id();
-
-                let cube_mesh_chunk = commands
-                    .spawn((
-                        MeshChunk { parent_chunk },
-                        CubeMeshChunk,
-                        PbrBundle {
-                            mesh: meshes.add(cube_mesh),
-                            material: cube_mesh_material.0.clone(),
-                            ..Default::default()
-                        },
-                        cube_mesh_md,
-                        ChunkMeshType::Cube,
-                    ))
-                    .id();
-
-                let xsprite_mesh_chunk = commands
-                    .spawn((
-                        MeshChunk { parent_chunk },
-                        XSpriteMeshChunk,
-                        PbrBundle {
-                            mesh: meshes.add(xsprite_mesh),
-                            material: xsprite_mesh_material.0.clone(),
-                            ..Default::default()
-                        },
-                        xsprite_mesh_md,
-                        ChunkMeshType::XSprite,
-                    ))
-                    .id();
-
-                let custom_mesh_chunk = commands
-                    .spawn((
-                        MeshChunk { parent_chunk },
-                        CustomMeshChunk,
-                        PbrBundle {
-                            mesh: meshes.add(custom_mesh),
-                            material: custom_mesh_material.0.clone(),
-                            ..Default::default()
-                        },
-                        custom_mesh_md,
-                        ChunkMeshType::Custom,
-                    ))
-                    .id();
-
-                commands.entity(parent_chunk).insert(ChildMeshChunks {
-                    cube_mesh_chunk,
-                    xsprite_mesh_chunk,
-                    custom_mesh_chunk,
-                });
-
-                commands.entity(parent_chunk).push_children(&[
-                    cube_mesh_chunk,
-                    xsprite_mesh_chunk,
-                    custom_mesh_chunk,
-                ]);
-
-                chunk_map.insert_chunk(cords, parent_chunk);
-            }
+                    ChunkMeshType::Custom,
+                ))
+                .id();
+
+            // Fluids are drawn with the translucent cubes, they are usually see-through.
+            let fluid_mesh_chunk = commands
+                .spawn((
+                    MeshChunk { parent_chunk },
+                    FluidMeshChunk,
+                    PbrBundle {
+                        mesh: meshes.add(fluid_mesh),
+                        material: translucent_mesh_material.0.clone(),
+                        ..Default::default()
+                    },
+                    fluid_mesh_md,
+                    ChunkMeshType::Fluid,
+                ))
+                .id();
+
+            let child_mesh_chunks = ChildMeshChunks {
+                cube_mesh_chunk,
+                translucent_mesh_chunk,
+                xsprite_mesh_chunk,
+                custom_mesh_chunk,
+                fluid_mesh_chunk,
+            };
+            commands
+                .entity(parent_chunk)
+                .push_children(&child_mesh_chunks.iter().collect::<Vec<_>>())
+                .insert(child_mesh_chunks);
+
+            chunk_map.insert_chunk(cords, parent_chunk);
+            block_ticks.restore(cords, scheduled_ticks);
+            // Spread the light across the borders with the chunks that are already loaded.
+            light_updates
+                .borders
+                .extend(FACES.map(|face| (cords, face)));
         });
 }
 
 pub fn queue_chunks_to_spawn(
     mut chunk_queue: ResMut<ChunkQueue>,
     chunk_map: Res<ChunkMap>,
-    current_chunk: Res<CurrentChunk>,
+    mut chunk_loaders: ChunkLoaders,
 ) {
-    let current_chunk = current_chunk.get();
-    for x in -RENDER_DISTANCE..=RENDER_DISTANCE {
-        for z in -RENDER_DISTANCE..=RENDER_DISTANCE {
-            let chunk_cords = ChunkCords::from([x, z]) + current_chunk;
-            if !chunk_map.contains_chunk(chunk_cords) {
-                chunk_queue.push(chunk_cords);
-            }
+    if !chunk_loaders.changed() {
+        return;
+    }
+    // Chunks that are no longer in range are cancelled.
+    chunk_queue.reprioritize(|chunk_cords| chunk_loaders.load_priority(chunk_cords));
+    for chunk_cords in chunk_loaders.iter_chunks_to_load() {
+        if chunk_map.contains_chunk(chunk_cords) || chunk_queue.contains(chunk_cords) {
+            continue;
+        }
+        if let Some(priority) = chunk_loaders.load_priority(chunk_cords) {
+            chunk_queue.push(chunk_cords, priority);
         }
     }
 }
 
-pub fn despawn_chunks(
+#[allow(clippy::too_many_arguments)]
+pub fn despawn_chunks<const N: usize>(
     mut commands: Commands,
     mut chunk_map: ResMut<ChunkMap>,
-    current_chunk: Res<CurrentChunk>,
+    mut chunk_loaders: ChunkLoaders,
+    chunks: Query<&Chunk>,
+    chunks_to_save: ChunksToSave<N>,
+    chunks_tasks_query: Query<(Entity, &ComputeChunk<N>)>,
+    chunk_storage: Option<Res<ChunkStorage>>,
+    mut block_ticks: ResMut<BlockTicks>,
 ) {
-    let current_chunk = current_chunk.get();
-    for chunk_entity in chunk_map
-        .extract_if(|cords| chunk_distance(*cords, current_chunk) > RENDER_DISTANCE)
-        .into_iter()
-    {
+    if !chunk_loaders.changed() {
+        return;
+    }
+    // Cancel the chunks that are being built but are no longer in range.
+    for (entity, compute_chunk) in chunks_tasks_query.iter() {
+        if !chunk_loaders.keeps_loaded(compute_chunk.cords) {
+            chunk_map.remove_chunk(compute_chunk.cords);
+            commands.entity(entity).despawn();
+        }
+    }
+    let chunks_to_despawn = chunk_map.extract_if(|cords| !chunk_loaders.keeps_loaded(*cords));
+    let ticks_to_save: Vec<_> = chunks
+        .iter_many(&chunks_to_despawn)
+        .filter_map(|chunk| Some((chunk.cords, block_ticks.unload(chunk.cords)?)))
+        .collect();
+    if let Some(chunk_storage) = chunk_storage {
+        chunks_to_save.queue(&chunk_storage, &chunks_to_despawn);
+        queue_ticks(&chunk_storage, &ticks_to_save);
+        // Rewriting the region files takes a while, so it's done in the background.
+        chunk_storage.flush_in_background();
+    }
+    for chunk_entity in chunks_to_despawn.into_iter() {
         commands.entity(chunk_entity).despawn_recursive();
     }
 }
 
+/// Save all of the modified chunks that are still loaded, and all of the scheduled ticks, when the
+/// app exits.
+pub fn save_modified_chunks_on_exit<const N: usize>(
+    mut app_exit_events: EventReader<AppExit>,
+    chunks_to_save: ChunksToSave<N>,
+    chunk_storage: Option<Res<ChunkStorage>>,
+    block_ticks: Res<BlockTicks>,
+    chunk_map: Res<ChunkMap>,
+) {
+    if app_exit_events.read().last().is_none() {
+        return;
+    }
+    if let Some(chunk_storage) = chunk_storage {
+        chunks_to_save.queue(&chunk_storage, &chunks_to_save.loaded_chunks());
+        let ticks_to_save = block_ticks.chunks_to_save().map(|chunk_cords| {
+            let mut ticks = block_ticks.chunk_ticks(chunk_cords);
+            // The saved ticks of the chunks that aren't loaded weren't restored yet.
+            if chunk_map.get_chunk(chunk_cords).is_none() {
+                ticks.extend(chunk_storage.load_ticks(chunk_cords).unwrap_or_default());
+            }
+            (chunk_cords, ticks)
+        });
+        queue_ticks(&chunk_storage, &ticks_to_save.collect::<Vec<_>>());
+        // The app is about to exit, so the data is written right away.
+        if let Err(err) = chunk_storage.flush() {
+            error!(
+                "Failed to save chunks to {}: {}",
+                chunk_storage.path().display(),
+                err
+            );
+        }
+    }
+}
+
+/// The data of the loaded chunks that is saved with them.
+#[derive(SystemParam)]
+pub struct ChunksToSave<'w, 's, const N: usize> {
+    modified_chunks: Query<
+        'w,
+        's,
+        (
+            &'static Chunk,
+            &'static ChunkGrid<N>,
+            &'static ChunkBlockStates,
+            &'static ChunkFluidLevels,
+        ),
+        With<ModifiedChunk>,
+    >,
+    chunk_block_entities: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Chunk,
+            &'static ChunkBlockEntities,
+            Has<ModifiedChunk>,
+        ),
+    >,
+    block_entities: Query<'w, 's, EntityRef<'static>, With<BlockEntity>>,
+    block_entity_spawners: Res<'w, BlockEntitySpawners>,
+}
+
+impl<'w, 's, const N: usize> ChunksToSave<'w, 's, N> {
+    /// Queue the grids, the block states and the fluid levels of the modified chunks, and the saved
+    /// block entities of the chunks.
+    fn queue(&self, chunk_storage: &ChunkStorage, chunks: &[Entity]) {
+        let modified_chunks: Vec<_> = self.modified_chunks.iter_many(chunks).collect();
+        chunk_storage.queue_chunks(
+            modified_chunks
+                .iter()
+                .map(|(chunk, chunk_grid, ..)| (chunk.cords, &chunk_grid.0)),
+        );
+        chunk_storage.queue_block_states(
+            modified_chunks
+                .iter()
+                .map(|(chunk, _, block_states, _)| (chunk.cords, *block_states)),
+        );
+        chunk_storage.queue_fluid_levels(
+            modified_chunks
+                .iter()
+                .map(|(chunk, .., fluid_levels)| (chunk.cords, *fluid_levels)),
+        );
+        // The block entities change without modifying their chunk, so they are saved whenever
+        // their chunk has any.
+        let saved_block_entities: Vec<_> = self
+            .chunk_block_entities
+            .iter_many(chunks)
+            .filter_map(|(_, chunk, block_entities, modified)| {
+                let saved: StoredBlockEntities = block_entities
+                    .0
+                    .iter()
+                    .filter_map(|(block_pos, entity)| {
+                        let entity = self.block_entities.get(*entity).ok()?;
+                        Some((*block_pos, self.block_entity_spawners.save(&entity)?))
+                    })
+                    .collect();
+                (modified || !saved.is_empty()).then_some((chunk.cords, saved))
+            })
+            .collect();
+        chunk_storage.queue_block_entities(
+            saved_block_entities
+                .iter()
+                .map(|(chunk_cords, saved)| (*chunk_cords, saved)),
+        );
+    }
+
+    /// The entities of all of the loaded chunks.
+    fn loaded_chunks(&self) -> Vec<Entity> {
+        self.chunk_block_entities
+            .iter()
+            .map(|(entity, ..)| entity)
+            .collect()
+    }
+}
+
+fn queue_ticks(chunk_storage: &ChunkStorage, chunks: &[(ChunkCords, Vec<ScheduledTick>)]) {
+    chunk_storage.queue_ticks(
+        chunks
+            .iter()
+            .map(|(chunk_cords, ticks)| (*chunk_cords, ticks.as_slice())),
+    );
+}
+
 pub fn build_chunks<const N: usize>(
     mut chunk_queue: ResMut<ChunkQueue>,
     chunk_builder: Res<BoxedBuilder<N>>,
     mesh_registry: Res<MeshReg>,
+    light_registry: Res<LightReg>,
     mut chunk_map: ResMut<ChunkMap>,
     mut commands: Commands,
+    chunk_storage: Option<Res<ChunkStorage>>,
+    chunk_budget: Res<ChunkBudget>,
+    vertical_range: Res<VerticalChunkRange>,
+    structure_placements: Res<StructurePlacements>,
 ) {
+    if chunk_queue.is_empty() {
+        return;
+    }
     let async_task_pool = AsyncComputeTaskPool::get();
     let mesh_registry = Arc::new(mesh_registry.clone());
-    for chunk_cords in chunk_queue.drain() {
+    let light_registry = Arc::new(light_registry.clone());
+    let vertical_range = *vertical_range;
+    for _ in 0..chunk_budget.max_builds_per_frame {
+        let Some(chunk_cords) = chunk_queue.pop() else {
+            break;
+        };
         chunk_map.insert_chunk(chunk_cords, Entity::PLACEHOLDER);
         let new_mesh_reg = Arc::clone(&mesh_registry);
-        let chunk_grid = chunk_builder.build_chunk(chunk_cords);
+        let light_reg = Arc::clone(&light_registry);
+        let chunk_builder = Arc::clone(&chunk_builder.builder);
+        let chunk_storage = chunk_storage.as_deref().cloned();
+        let structure_placements = structure_placements.clone();
+        let outer_layers = outer_layers(chunk_cords, &vertical_range);
         let task = async_task_pool.spawn(async move {
-            let (cube_chunk_mesh, cube_mesh_md) = meshify_cubic_voxels(
-                &[Face::Bottom],
+            let stored_chunk_grid = chunk_storage.as_ref().and_then(|chunk_storage| {
+                chunk_storage.load_chunk(chunk_cords).unwrap_or_else(|err| {
+                    error!("Failed to load chunk {}: {}", chunk_cords, err);
+                    None
+                })
+            });
+            let chunk_storage = chunk_storage.as_ref();
+            let scheduled_ticks = load_or_default(
+                chunk_storage,
+                chunk_cords,
+                "ticks",
+                ChunkStorage::load_ticks,
+            );
+            let mut block_states = load_or_default(
+                chunk_storage,
+                chunk_cords,
+                "block states",
+                ChunkStorage::load_block_states,
+            );
+            let mut fluid_levels = load_or_default(
+                chunk_storage,
+                chunk_cords,
+                "fluid levels",
+                ChunkStorage::load_fluid_levels,
+            );
+            let mut saved_block_entities = load_or_default(
+                chunk_storage,
+                chunk_cords,
+                "block entities",
+                ChunkStorage::load_block_entities,
+            );
+            let mut chunk_grid = stored_chunk_grid.map_or_else(
+                || chunk_builder.build_chunk(chunk_cords),
+                |stored_chunk_grid| stored_chunk_grid.to_grid(),
+            );
+            // The blocks of the structures placed by the chunk itself, and by its neighbors.
+            let structure_blocks = structure_placements.apply(chunk_cords, &mut chunk_grid);
+            // The saved data of the blocks the structures overwrote doesn't belong to them.
+            for block_pos in &structure_blocks {
+                block_states.clear(*block_pos);
+                fluid_levels.0.remove(block_pos);
+                saved_block_entities.remove(block_pos);
+            }
+            // The chunk is lit on its own, the light from its neighbors spreads into it once it's
+            // spawned.
+            let sky_exposed = chunk_cords.y >= vertical_range.max;
+            let chunk_light = light_chunk(&chunk_grid, light_reg.as_ref(), sky_exposed);
+            let light_at =
+                |pos: IVec3| light_at(chunk_cords, &chunk_light, pos, &vertical_range, |_, _| None);
+            // The neighbors of the chunk aren't known yet, the fluid is meshed again once the chunk
+            // is introduced to them.
+            let ChunkMeshes {
+                mut cube_mesh,
+                cube_mesh_md,
+                mut translucent_mesh,
+                translucent_mesh_md,
+                mut xsprite_mesh,
+                xsprite_mesh_md,
+                mut custom_mesh,
+                custom_mesh_md,
+                mut fluid_mesh,
+                fluid_mesh_md,
+            } = meshify_chunk(
                 &chunk_grid,
                 new_mesh_reg.as_ref(),
-                MeshingAlgorithm::Culling,
-                None,
+                outer_layers,
+                |block_pos| block_states.get(block_pos).unwrap_or_default(),
+                |block_pos| fluid_levels.level(block_pos),
+                |_| None,
             )?;
-            let (xsprite_chunk_mesh, xsprite_mesh_md) =
-                meshify_xsprite_voxels(new_mesh_reg.as_ref(), &chunk_grid);
-            let (custom_chunk_mesh, custom_mesh_md) =
-                meshify_custom_voxels(new_mesh_reg.as_ref(), &chunk_grid);
+            let mesh_reg = new_mesh_reg.as_ref();
+            bake_cube_light(
+                &mut cube_mesh,
+                &cube_mesh_md,
+                mesh_reg,
+                light_reg.as_ref(),
+                &chunk_grid,
+                light_at,
+            );
+            bake_cube_light(
+                &mut translucent_mesh,
+                &translucent_mesh_md,
+                mesh_reg,
+                light_reg.as_ref(),
+                &chunk_grid,
+                light_at,
+            );
+            bake_xsprite_light(
+                &mut xsprite_mesh,
+                &xsprite_mesh_md,
+                mesh_reg,
+                light_reg.as_ref(),
+                &chunk_grid,
+                light_at,
+            );
+            bake_custom_light(
+                &mut custom_mesh,
+                &custom_mesh_md,
+                mesh_reg,
+                light_reg.as_ref(),
+                &chunk_grid,
+                light_at,
+            );
+            bake_fluid_light(
+                &mut fluid_mesh,
+                &fluid_mesh_md,
+                mesh_reg,
+                light_reg.as_ref(),
+                &chunk_grid,
+                light_at,
+            );
 
             Some(ChunkGenResult {
                 cords: chunk_cords,
-                cube_mesh: cube_chunk_mesh,
+                cube_mesh,
                 cube_mesh_md: ChunkMeshMd::Cube(cube_mesh_md),
-                xsprite_mesh: xsprite_chunk_mesh,
+                translucent_mesh,
+                translucent_mesh_md: ChunkMeshMd::Cube(translucent_mesh_md),
+                xsprite_mesh,
                 xsprite_mesh_md: ChunkMeshMd::Xsprite(xsprite_mesh_md),
-                custom_mesh: custom_chunk_mesh,
+                custom_mesh,
                 custom_mesh_md: ChunkMeshMd::Custom(custom_mesh_md),
-                chunk_grid: ChunkGrid(chunk_grid),
+                fluid_mesh,
+                fluid_mesh_md: ChunkMeshMd::Fluid(fluid_mesh_md),
+                chunk_grid: ChunkGrid(PalettedGrid::from_grid(&chunk_grid)),
+                chunk_light: ChunkLight(chunk_light),
+                structure_blocks: structure_blocks.len(),
+                scheduled_ticks,
+                block_states,
+                fluid_levels,
+                saved_block_entities,
             })
         });
-        commands.spawn(ComputeChunk(task));
+        commands.spawn(ComputeChunk {
+            cords: chunk_cords,
+            task,
+        });
+    }
+}
+
+/// Load a part of the chunk from the storage, errors are logged and the default is used instead.
+fn load_or_default<T: Default>(
+    chunk_storage: Option<&ChunkStorage>,
+    chunk_cords: ChunkCords,
+    name: &str,
+    load: impl FnOnce(&ChunkStorage, ChunkCords) -> io::Result<T>,
+) -> T {
+    let Some(chunk_storage) = chunk_storage else {
+        return T::default();
+    };
+    load(chunk_storage, chunk_cords).unwrap_or_else(|err| {
+        error!(
+            "Failed to load the {} of chunk {}: {}",
+            name, chunk_cords, err
+        );
+        T::default()
+    })
+}
+
+/// The outer layers of the chunk whose faces are culled when it's meshed. Only the bottom of the
+/// world is never going to be seen, the rest of the chunk's outer faces are culled when its
+/// neighbors are introduced.
+pub(crate) fn outer_layers(
+    chunk_cords: ChunkCords,
+    vertical_range: &VerticalChunkRange,
+) -> &'static [Face] {
+    if chunk_cords.y == vertical_range.min {
+        &[Face::Bottom]
+    } else {
+        &[]
     }
 }
+
+/// The meshes of a chunk and their meta-data, before they are lit.
+pub(crate) struct ChunkMeshes {
+    pub cube_mesh: Mesh,
+    pub cube_mesh_md: CubeMD<BlockId>,
+    pub translucent_mesh: Mesh,
+    pub translucent_mesh_md: CubeMD<BlockId>,
+    pub xsprite_mesh: Mesh,
+    pub xsprite_mesh_md: XSpriteMD<BlockId>,
+    pub custom_mesh: Mesh,
+    pub custom_mesh_md: CustomMD<BlockId>,
+    pub fluid_mesh: Mesh,
+    pub fluid_mesh_md: FluidMD<BlockId>,
+}
+
+/// Generate all of the meshes of a chunk from its grid. `fluid_outside_at` returns the block and
+/// the fluid level at a position outside of the chunk, see [`meshify_fluid_voxels`].
+pub(crate) fn meshify_chunk<const N: usize>(
+    chunk_grid: &Grid<BlockId, N>,
+    mesh_registry: &MeshReg,
+    outer_layers: &[Face],
+    orientation_at: impl Fn(BlockPos) -> Orientation + Copy,
+    level_at: impl Fn(BlockPos) -> u8,
+    fluid_outside_at: impl Fn(IVec3) -> Option<(BlockId, u8)>,
+) -> Option<ChunkMeshes> {
+    let (cube_mesh, cube_mesh_md) = meshify_cubic_voxels(
+        outer_layers,
+        chunk_grid,
+        mesh_registry,
+        MeshingAlgorithm::Culling,
+        None,
+        orientation_at,
+    )?;
+    let (translucent_mesh, translucent_mesh_md) = meshify_translucent_cubic_voxels(
+        outer_layers,
+        chunk_grid,
+        mesh_registry,
+        MeshingAlgorithm::Culling,
+        None,
+        orientation_at,
+    )?;
+    let (xsprite_mesh, xsprite_mesh_md) = meshify_xsprite_voxels(mesh_registry, chunk_grid);
+    let (custom_mesh, custom_mesh_md) =
+        meshify_custom_voxels(mesh_registry, chunk_grid, orientation_at);
+    let (fluid_mesh, fluid_mesh_md) =
+        meshify_fluid_voxels(mesh_registry, chunk_grid, level_at, fluid_outside_at);
+    Some(ChunkMeshes {
+        cube_mesh,
+        cube_mesh_md,
+        translucent_mesh,
+        translucent_mesh_md,
+        xsprite_mesh,
+        xsprite_mesh_md,
+        custom_mesh,
+        custom_mesh_md,
+        fluid_mesh,
+        fluid_mesh_md,
+    })
+}
@@ -1,49 +1,105 @@
 use bevy_asset::{Assets, Handle};
 use bevy_ecs::prelude::*;
-use bevy_render::mesh::Mesh;
+use bevy_ecs::system::SystemParam;
+use bevy_math::{IVec3, Vec3};
+use bevy_render::{
+    camera::{Camera, Projection},
+    mesh::Mesh,
+};
+use bevy_transform::prelude::{GlobalTransform, Transform};
 use moxi_mesh_utils::prelude::{
-    introduce_adjacent_chunks, update_cube_mesh, update_custom_mesh, update_xsprite_mesh,
-    EMPTY_AABB,
+    introduce_adjacent_chunks, meshify_fluid_voxels, sort_quads_back_to_front, update_cube_mesh,
+    update_custom_mesh, update_xsprite_mesh, EMPTY_AABB,
+};
+use moxi_utils::prelude::{
+    adj_chunk, block_to_global_block_pos, BlockGrid, BlockId, ChunkCords, Orientation,
 };
-use moxi_utils::prelude::adj_chunk;
 
+use super::spawn::{meshify_chunk, outer_layers, ChunkMeshes};
 use crate::{
     blockreg::meshreg::MeshReg,
     chunk::{
         components::{
-            ChildMeshChunks, ChunkGrid, ChunkMeshType, CubeMeshChunk, ToIntroduce, ToUpdate,
+            ChildMeshChunks, Chunk, ChunkBlockStates, ChunkFluidLevels, ChunkGrid, ChunkMeshType,
+            MeshChunk, ToIntroduce, ToRemesh, ToUpdate, TranslucentMeshChunk,
         },
         meshmd::ChunkMeshMd,
-        resources::ChunkMap,
+        resources::{ChunkMap, CurrentChunk, LightUpdates, TranslucentSorting, VerticalChunkRange},
     },
+    prelude::PLACEHOLDER_DIMS,
 };
 
-pub fn handle_chunk_updates(
+pub fn handle_chunk_updates<const N: usize>(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut chunks_to_update: Query<
-        (Entity, &ChunkMeshType, &Handle<Mesh>, &mut ChunkMeshMd),
+        (
+            Entity,
+            &MeshChunk,
+            &ChunkMeshType,
+            &Handle<Mesh>,
+            &mut ChunkMeshMd,
+        ),
         With<ToUpdate>,
     >,
+    parent_chunks: Query<(&Chunk, &ChunkGrid<N>, &ChunkFluidLevels, &ChunkBlockStates)>,
+    chunk_neighbors: ChunkNeighbors<N>,
     mesh_registry: Res<MeshReg>,
+    mut light_updates: ResMut<LightUpdates>,
 ) {
     let mesh_registry = mesh_registry.into_inner();
-    for (chunk_entity, chunk_mesh_type, mesh_handle, mut chunk_mesh_md) in &mut chunks_to_update {
+    for (chunk_entity, mesh_chunk, chunk_mesh_type, mesh_handle, mut chunk_mesh_md) in
+        &mut chunks_to_update
+    {
         let chunk_mesh = meshes.get_mut(mesh_handle).unwrap();
+        let parent_chunk = parent_chunks.get(mesh_chunk.parent_chunk);
+        let orientation_at = |block_pos| {
+            parent_chunk
+                .ok()
+                .and_then(|(_, _, _, block_states)| block_states.get::<Orientation>(block_pos))
+                .unwrap_or_default()
+        };
         match (chunk_mesh_type, chunk_mesh_md.as_mut()) {
-            (ChunkMeshType::Cube, ChunkMeshMd::Cube(ref mut md)) => {
-                update_cube_mesh(chunk_mesh, md, mesh_registry);
+            (
+                ChunkMeshType::Cube | ChunkMeshType::TranslucentCube,
+                ChunkMeshMd::Cube(ref mut md),
+            ) => {
+                update_cube_mesh(chunk_mesh, md, mesh_registry, orientation_at);
             }
             (ChunkMeshType::XSprite, ChunkMeshMd::Xsprite(ref mut md)) => {
                 update_xsprite_mesh(mesh_registry, chunk_mesh, md);
             }
             (ChunkMeshType::Custom, ChunkMeshMd::Custom(ref mut md)) => {
-                update_custom_mesh(mesh_registry, chunk_mesh, md);
+                if let Ok((_, chunk_grid, ..)) = parent_chunk {
+                    update_custom_mesh(
+                        mesh_registry,
+                        chunk_mesh,
+                        md,
+                        &chunk_grid.0,
+                        orientation_at,
+                    );
+                }
+            }
+            (ChunkMeshType::Fluid, ChunkMeshMd::Fluid(ref mut md)) => {
+                // The surface of the fluid depends on the levels of its neighbors, so the whole
+                // mesh is generated again.
+                if let Ok((chunk, chunk_grid, fluid_levels, _)) = parent_chunk {
+                    (*chunk_mesh, *md) = meshify_fluid_voxels(
+                        mesh_registry,
+                        &chunk_grid.0,
+                        |block_pos| fluid_levels.level(block_pos),
+                        |pos| chunk_neighbors.fluid_at(chunk.cords, pos),
+                    );
+                }
             }
             _ => panic!("Chunk mesh type and mesh meta-data type mismatch"),
         }
 
         let aabb = chunk_mesh.compute_aabb().unwrap_or(EMPTY_AABB);
+        // The new parts of the mesh aren't lit yet.
+        if let Ok((parent_chunk, ..)) = parent_chunk {
+            light_updates.relight_chunk(parent_chunk.cords);
+        }
 
         commands
             .entity(chunk_entity)
@@ -52,12 +108,141 @@ pub fn handle_chunk_updates(
     }
 }
 
+/// The loaded chunks around the chunks, and the range of the chunks that can be loaded.
+#[derive(SystemParam)]
+pub struct ChunkNeighbors<'w, 's, const N: usize> {
+    chunk_map: Res<'w, ChunkMap>,
+    vertical_range: Res<'w, VerticalChunkRange>,
+    fluid_chunks: Query<'w, 's, (&'static ChunkGrid<N>, &'static ChunkFluidLevels)>,
+}
+
+impl<'w, 's, const N: usize> ChunkNeighbors<'w, 's, N> {
+    /// The block and the fluid level at a position outside of the chunk, `None` if the chunk of
+    /// the position isn't loaded.
+    pub fn fluid_at(&self, chunk_cords: ChunkCords, pos: IVec3) -> Option<(BlockId, u8)> {
+        let dims = unsafe { PLACEHOLDER_DIMS };
+        let global_block_pos = block_to_global_block_pos(chunk_cords * dims.as_ivec3() + pos, dims);
+        let (chunk_grid, fluid_levels) = self
+            .fluid_chunks
+            .get(self.chunk_map.get_chunk(global_block_pos.cords)?)
+            .ok()?;
+        Some((
+            chunk_grid.get_block(global_block_pos.pos)?,
+            fluid_levels.level(global_block_pos.pos),
+        ))
+    }
+}
+
+/// The parts of a chunk its meshes are generated from.
+type ChunkToRemesh<'a, const N: usize> = (
+    Entity,
+    &'a Chunk,
+    &'a ChunkGrid<N>,
+    &'a ChunkFluidLevels,
+    &'a ChunkBlockStates,
+    &'a ChildMeshChunks,
+);
+
+/// Generate the meshes of the chunks marked with [`ToRemesh`] again, from their grids. The chunks
+/// are introduced to their neighbors again, to cull the faces on their borders.
+pub fn remesh_chunks<const N: usize>(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    chunks_to_remesh: Query<ChunkToRemesh<N>, With<ToRemesh>>,
+    chunk_neighbors: ChunkNeighbors<N>,
+    mut mesh_chunks: Query<(&Handle<Mesh>, &mut ChunkMeshMd)>,
+    mesh_registry: Res<MeshReg>,
+    mut light_updates: ResMut<LightUpdates>,
+) {
+    let mesh_registry = mesh_registry.into_inner();
+    for (chunk_entity, chunk, chunk_grid, fluid_levels, block_states, child_mesh_chunks) in
+        chunks_to_remesh.iter()
+    {
+        commands.entity(chunk_entity).remove::<ToRemesh>();
+        let Some(ChunkMeshes {
+            cube_mesh,
+            cube_mesh_md,
+            translucent_mesh,
+            translucent_mesh_md,
+            xsprite_mesh,
+            xsprite_mesh_md,
+            custom_mesh,
+            custom_mesh_md,
+            fluid_mesh,
+            fluid_mesh_md,
+        }) = meshify_chunk(
+            &chunk_grid.0.to_grid(),
+            mesh_registry,
+            outer_layers(chunk.cords, &chunk_neighbors.vertical_range),
+            |block_pos| {
+                block_states
+                    .get::<Orientation>(block_pos)
+                    .unwrap_or_default()
+            },
+            |block_pos| fluid_levels.level(block_pos),
+            |pos| chunk_neighbors.fluid_at(chunk.cords, pos),
+        )
+        else {
+            continue;
+        };
+        for (mesh_chunk, mesh, md) in [
+            (
+                child_mesh_chunks.cube_mesh_chunk,
+                cube_mesh,
+                ChunkMeshMd::Cube(cube_mesh_md),
+            ),
+            (
+                child_mesh_chunks.translucent_mesh_chunk,
+                translucent_mesh,
+                ChunkMeshMd::Cube(translucent_mesh_md),
+            ),
+            (
+                child_mesh_chunks.xsprite_mesh_chunk,
+                xsprite_mesh,
+                ChunkMeshMd::Xsprite(xsprite_mesh_md),
+            ),
+            (
+                child_mesh_chunks.custom_mesh_chunk,
+                custom_mesh,
+                ChunkMeshMd::Custom(custom_mesh_md),
+            ),
+            (
+                child_mesh_chunks.fluid_mesh_chunk,
+                fluid_mesh,
+                ChunkMeshMd::Fluid(fluid_mesh_md),
+            ),
+        ] {
+            let Ok((mesh_handle, mut chunk_mesh_md)) = mesh_chunks.get_mut(mesh_chunk) else {
+                continue;
+            };
+            let Some(chunk_mesh) = meshes.get_mut(mesh_handle) else {
+                continue;
+            };
+            let aabb = mesh.compute_aabb().unwrap_or(EMPTY_AABB);
+            *chunk_mesh = mesh;
+            *chunk_mesh_md = md;
+            // The changes that weren't applied yet are already in the new mesh.
+            commands
+                .entity(mesh_chunk)
+                .remove::<ToUpdate>()
+                .insert(aabb);
+        }
+        light_updates.relight_chunk(chunk.cords);
+        commands.entity(chunk_entity).insert(ToIntroduce::new(
+            chunk.cords,
+            &chunk_neighbors.vertical_range,
+        ));
+    }
+}
+
+/// Introduce the chunks to their neighbors once they are loaded: the faces of the cubes on their
+/// borders are culled, and their fluids are meshed again to join the fluids across the borders.
 pub fn introduce_adj_chunks<const N: usize>(
     mut commands: Commands,
-    chunk_grids: Query<&ChunkGrid<N>>,
+    chunk_grids: Query<(&ChunkGrid<N>, &ChunkBlockStates)>,
     mut parent_chunks: Query<(Entity, &ChildMeshChunks, &mut ToIntroduce)>,
     chunk_map: Res<ChunkMap>,
-    mut cube_mesh_chunks: Query<&mut ChunkMeshMd, With<CubeMeshChunk>>,
+    mut mesh_chunks: Query<&mut ChunkMeshMd>,
     mesh_registry: Res<MeshReg>,
 ) {
     let mesh_registry = mesh_registry.into_inner();
@@ -67,17 +252,37 @@ pub fn introduce_adj_chunks<const N: usize>(
         for connection_face in to_introduce.adj_chunks_to_introduce.drain(..) {
             let adj_chunk_cords = adj_chunk(chunk_cords, connection_face);
             if let Some(adj_chunk_entity) = chunk_map.get_chunk(adj_chunk_cords) {
-                let cube_mesh_entity = child_mesh_chunks.cube_mesh_chunk;
-                let mut cube_mesh_md = cube_mesh_chunks.get_mut(cube_mesh_entity).unwrap();
-                if let Ok(adj_chunk_grid) = chunk_grids.get(adj_chunk_entity) {
+                let (Ok((chunk_grid, _)), Ok((adj_chunk_grid, adj_block_states))) = (
+                    chunk_grids.get(chunk_entity),
+                    chunk_grids.get(adj_chunk_entity),
+                ) else {
+                    continue;
+                };
+                for cube_mesh_entity in [
+                    child_mesh_chunks.cube_mesh_chunk,
+                    child_mesh_chunks.translucent_mesh_chunk,
+                ] {
+                    let mut cube_mesh_md = mesh_chunks.get_mut(cube_mesh_entity).unwrap();
                     introduce_adjacent_chunks(
                         mesh_registry,
                         cube_mesh_md.get_cube_md_mut().unwrap(),
+                        &chunk_grid.0,
                         connection_face,
                         &adj_chunk_grid.0,
+                        |block_pos| {
+                            adj_block_states
+                                .get::<Orientation>(block_pos)
+                                .unwrap_or_default()
+                        },
                     );
                     commands.entity(cube_mesh_entity).insert(ToUpdate);
                 }
+                let fluid_mesh_entity = child_mesh_chunks.fluid_mesh_chunk;
+                if let Ok(ChunkMeshMd::Fluid(fluid_mesh_md)) = mesh_chunks.get(fluid_mesh_entity) {
+                    if !fluid_mesh_md.is_empty() {
+                        commands.entity(fluid_mesh_entity).insert(ToUpdate);
+                    }
+                }
             } else {
                 couldnt_introduce.push(connection_face);
             }
@@ -89,3 +294,61 @@ pub fn introduce_adj_chunks<const N: usize>(
         }
     }
 }
+
+/// Sort the quads of the translucent meshes back to front, as seen from the active 3d camera (the
+/// center of the [`CurrentChunk`] if there is none). The meshes are sorted again when they
+/// change, and all of them are sorted again when the camera moves further than
+/// [`TranslucentSorting::resort_distance`].
+pub fn sort_translucent_chunks(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut translucent_mesh_chunks: Query<
+        (&MeshChunk, &Handle<Mesh>, &mut ChunkMeshMd),
+        With<TranslucentMeshChunk>,
+    >,
+    parent_chunks: Query<&Transform, With<Chunk>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Projection>>,
+    current_chunk: Res<CurrentChunk>,
+    mut translucent_sorting: ResMut<TranslucentSorting>,
+) {
+    let view_pos = cameras
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .min_by_key(|(camera, _)| camera.order)
+        .map_or_else(
+            || {
+                (current_chunk.get().as_vec3() + Vec3::splat(0.5))
+                    * unsafe { PLACEHOLDER_DIMS }.as_vec3()
+            },
+            |(_, camera_transform)| camera_transform.translation(),
+        );
+    let sort_all = match translucent_sorting.last_view_pos {
+        Some(last_view_pos) => {
+            last_view_pos.distance(view_pos) > translucent_sorting.resort_distance
+        }
+        None => true,
+    };
+    if sort_all {
+        translucent_sorting.last_view_pos = Some(view_pos);
+    }
+    for (mesh_chunk, mesh_handle, mut chunk_mesh_md) in &mut translucent_mesh_chunks {
+        if !sort_all && !chunk_mesh_md.is_changed() {
+            continue;
+        }
+        let (Ok(parent_transform), Some(mesh)) = (
+            parent_chunks.get(mesh_chunk.parent_chunk),
+            meshes.get_mut(mesh_handle),
+        ) else {
+            continue;
+        };
+        let Some(md) = chunk_mesh_md.get_cube_md_mut() else {
+            continue;
+        };
+        // The chunks are spawned with their transforms, so unlike their global transforms, they
+        // are up to date in the frame the chunks are spawned.
+        let local_view_pos = parent_transform
+            .compute_affine()
+            .inverse()
+            .transform_point3(view_pos);
+        sort_quads_back_to_front(mesh, md, local_view_pos);
+    }
+}
@@ -9,16 +9,32 @@ use self::{
     },
 };
 use crate::*;
-use bevy_app::{Plugin, PreUpdate};
-use blockworld::{global_block_breaker, global_block_placer, GlobalBlockBreak, GlobalBlockPlace};
+use bevy_app::{FixedUpdate, Plugin, PreUpdate};
+use blockworld::{
+    global_block_breaker, global_block_placer, remesh_blocks_with_changed_state, GlobalBlockBreak,
+    GlobalBlockPlace,
+};
 use chunk::MoxiChunkPlugin;
+use fluid::{schedule_loaded_fluids, tick_fluids, FluidTicks};
 use prelude::Block;
+use random_ticks::{send_random_ticks, RandomTicks};
+use region::{global_region_editor, BlockRegionUpdateEvent, GlobalRegionEdit};
+use scheduled_ticks::tick_scheduled_blocks;
+use structure::paste_pending_structure_blocks;
 
-pub struct _MoxiBptaPlugin<const N: usize>;
+#[derive(Default)]
+pub struct _MoxiBptaPlugin<const N: usize> {
+    /// The configuration of the chunks (render distance, budget, ...).
+    pub chunk_plugin: MoxiChunkPlugin<N>,
+}
 
-pub struct Air;
+impl<const N: usize> _MoxiBptaPlugin<N> {
+    pub fn with_chunk_plugin(chunk_plugin: MoxiChunkPlugin<N>) -> Self {
+        Self { chunk_plugin }
+    }
+}
 
-pub const RENDER_DISTANCE: i32 = 10;
+pub struct Air;
 
 impl Block for Air {
     fn get_name() -> &'static str {
@@ -28,11 +44,15 @@ impl Block for Air {
 
 impl<const N: usize> Plugin for _MoxiBptaPlugin<N> {
     fn build(&self, app: &mut bevy_app::App) {
-        app.add_plugins(MoxiChunkPlugin::<N>::default());
+        app.add_plugins(self.chunk_plugin);
         app.add_event::<BlockWorldUpdateEvent>()
             .add_event::<GlobalBlockBreak>()
             .add_event::<GlobalBlockPlace>()
-            .add_event::<InBetweenerEvent>();
+            .add_event::<GlobalRegionEdit>()
+            .add_event::<BlockRegionUpdateEvent>()
+            .add_event::<InBetweenerEvent>()
+            .init_resource::<FluidTicks>()
+            .init_resource::<RandomTicks>();
 
         app.init_block::<Air>();
         app.add_systems(
@@ -40,6 +60,9 @@ impl<const N: usize> Plugin for _MoxiBptaPlugin<N> {
             (
                 global_block_breaker::<N>,
                 global_block_placer::<N>,
+                paste_pending_structure_blocks,
+                global_region_editor::<N>,
+                remesh_blocks_with_changed_state::<N>,
                 handle_world_block_update::<N>,
                 send_world_block_updates_to_surrounding_blocks::<N>,
                 apply_deferred_for_all_actions,
@@ -47,5 +70,13 @@ impl<const N: usize> Plugin for _MoxiBptaPlugin<N> {
             )
                 .chain(),
         );
+        app.add_systems(
+            FixedUpdate,
+            (
+                (schedule_loaded_fluids, tick_fluids::<N>).chain(),
+                tick_scheduled_blocks,
+                send_random_ticks::<N>,
+            ),
+        );
     }
 }
@@ -0,0 +1,97 @@
+use crate::chunk::components::ChunkBlockEntities;
+use crate::*;
+use bevy_ecs::system::EntityCommands;
+use bevy_ecs::world::EntityRef;
+use bevy_hierarchy::{BuildChildren, DespawnRecursiveExt};
+use moxi_utils::prelude::{BlockGlobalPos, BlockId, BlockPos, ChunkCords};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The entity of a placed block, for blocks that are registered with
+/// [`BlockWorldMut::with_block_entity`](crate::prelude::BlockWorldMut::with_block_entity). The
+/// entity is a child of the chunk entity: it's spawned when the block is placed (or when its chunk
+/// is loaded), and despawned when the block is broken (or when its chunk is unloaded). Only the
+/// [`SavedBlockEntity`] component of the entity (if it has one) persists with the chunk.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BlockEntity {
+    pub block_id: BlockId,
+    pub chunk_cords: ChunkCords,
+    pub block_pos: BlockPos,
+}
+
+impl BlockEntity {
+    pub fn global_block_pos(&self) -> BlockGlobalPos {
+        BlockGlobalPos::new(self.block_pos, self.chunk_cords)
+    }
+}
+
+/// A component of block entities that is saved with the chunk of their block, like the items of a
+/// chest, see [`BlockWorldMut::with_saved_block_entity`](crate::prelude::BlockWorldMut::with_saved_block_entity).
+pub trait SavedBlockEntity: Component + Clone {
+    fn save(&self) -> Vec<u8>;
+
+    /// Load the component from the data it was saved as, `None` if the data is invalid.
+    fn load(data: &[u8]) -> Option<Self>;
+}
+
+pub(crate) type BlockEntitySpawner = Arc<dyn Fn(&mut EntityCommands) + Send + Sync>;
+pub(crate) type BlockEntitySaver = Arc<dyn Fn(&EntityRef) -> Option<Vec<u8>> + Send + Sync>;
+/// Inserts the saved component into the block entity, returns false if the data is invalid.
+pub(crate) type BlockEntityLoader = Arc<dyn Fn(&mut EntityCommands, &[u8]) -> bool + Send + Sync>;
+
+/// The spawners of the block entities, by the id of the block.
+#[derive(Resource, Default, Clone)]
+pub(crate) struct BlockEntitySpawners {
+    pub spawners: HashMap<BlockId, BlockEntitySpawner>,
+    /// How the [`SavedBlockEntity`] components are saved and loaded, by the id of the block.
+    pub savers: HashMap<BlockId, (BlockEntitySaver, BlockEntityLoader)>,
+}
+
+impl BlockEntitySpawners {
+    pub fn is_empty(&self) -> bool {
+        self.spawners.is_empty()
+    }
+
+    pub fn has_block_entity(&self, block_id: BlockId) -> bool {
+        self.spawners.contains_key(&block_id)
+    }
+
+    /// The data the block entity is saved as, `None` if its block has no [`SavedBlockEntity`].
+    pub fn save(&self, entity: &EntityRef) -> Option<Vec<u8>> {
+        let block_id = entity.get::<BlockEntity>()?.block_id;
+        let (saver, _) = self.savers.get(&block_id)?;
+        saver(entity)
+    }
+
+    /// Load the saved data into the block entity, returns false if the data is invalid.
+    pub fn load(
+        &self,
+        entity_commands: &mut EntityCommands,
+        block_id: BlockId,
+        data: &[u8],
+    ) -> bool {
+        self.savers
+            .get(&block_id)
+            .is_some_and(|(_, loader)| loader(entity_commands, data))
+    }
+
+    /// Spawn the entity of the block (if it has one) as a child of the chunk entity, and track it
+    /// in the chunk's [`ChunkBlockEntities`].
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        chunk_entity: Entity,
+        block_entities: &mut ChunkBlockEntities,
+        block_entity: BlockEntity,
+    ) -> Option<Entity> {
+        let spawner = self.spawners.get(&block_entity.block_id)?;
+        let mut entity_commands = commands.spawn(block_entity);
+        spawner(&mut entity_commands);
+        let entity = entity_commands.id();
+        commands.entity(chunk_entity).add_child(entity);
+        if let Some(old_entity) = block_entities.0.insert(block_entity.block_pos, entity) {
+            commands.entity(old_entity).despawn_recursive();
+        }
+        Some(entity)
+    }
+}
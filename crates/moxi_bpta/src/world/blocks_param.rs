@@ -1,14 +1,18 @@
-use crate::chunk::components::{ChildMeshChunks, ChunkGrid};
+use crate::chunk::components::{
+    ChildMeshChunks, ChunkBlockEntities, ChunkBlockStates, ChunkGrid, ModifiedChunk,
+};
 use crate::chunk::resources::ChunkMap;
 use crate::prelude::{
-    block_id, get_block_name, BlockIdtoEnt, BlockMarker, BlockName, GlobalBlockBreak,
-    GlobalBlockPlace, BLOCKS_GLOBAL, PLACEHOLDER_DIMS,
+    block_id, get_block_name, BlockIdtoEnt, BlockMarker, BlockName, BlockUpdate, BlockUpdateType,
+    BlockWorldUpdateEvent, DynamicProperty, GlobalBlockBreak, GlobalBlockPlace, GlobalRegionEdit,
+    BLOCKS_GLOBAL, BLOCK_STATE_CHANGED, PLACEHOLDER_DIMS,
 };
 use bevy_ecs::prelude::*;
 use bevy_ecs::system::SystemParam;
+use bevy_math::Vec3;
 use moxi_utils::prelude::{
-    global_enumerate_neighboring_blocks, BlockGlobalPos, BlockId, BlockPos, ChunkCords, Face, Grid,
-    SurroundingBlocks,
+    block_to_global_block_pos, global_enumerate_neighboring_blocks, voxel_raycast, BlockGlobalPos,
+    BlockGrid, BlockId, BlockPos, ChunkCords, Face, Orientation, PalettedGrid, SurroundingBlocks,
 };
 
 #[derive(SystemParam)]
@@ -16,14 +20,30 @@ pub struct _Blocks<'w, 's, const N: usize> {
     _blocks_query: Query<'w, 's, (&'static BlockMarker, &'static BlockName)>,
     pub(crate) chunk_map: Res<'w, ChunkMap>,
     pub(crate) chunks_query: Query<'w, 's, (&'static mut ChunkGrid<N>, &'static ChildMeshChunks)>,
+    pub(crate) block_states_query: Query<'w, 's, &'static mut ChunkBlockStates>,
+    pub(crate) block_entities_query: Query<'w, 's, &'static mut ChunkBlockEntities>,
     _block_id_to_ent: Res<'w, BlockIdtoEnt>,
 }
 
+/// The block hit by a ray cast with [`_Blocks::raycast`].
+#[derive(Clone, Copy)]
+pub struct BlockRayHit {
+    pub global_block_pos: BlockGlobalPos,
+    pub block_id: BlockId,
+    /// The face of the block the ray hit.
+    pub face: Face,
+    /// The distance from the origin of the ray to the hit.
+    pub distance: f32,
+}
+
 #[derive(SystemParam)]
 pub struct _BlocksMut<'w, 's, const N: usize> {
     blocks: _Blocks<'w, 's, N>,
     global_block_place_sender: EventWriter<'w, GlobalBlockPlace>,
     global_block_break_sender: EventWriter<'w, GlobalBlockBreak>,
+    pub(crate) global_region_edit_sender: EventWriter<'w, GlobalRegionEdit>,
+    block_world_update_sender: EventWriter<'w, BlockWorldUpdateEvent>,
+    commands: Commands<'w, 's>,
 }
 
 impl<'w, 's, const N: usize> std::ops::Deref for _BlocksMut<'w, 's, N> {
@@ -40,6 +60,19 @@ impl<'w, 's, const N: usize> _BlocksMut<'w, 's, N> {
         chunk_cords: ChunkCords,
         block_pos: BlockPos,
         block_id: BlockId,
+    ) {
+        self.set_oriented_block_at_id(chunk_cords, block_pos, block_id, Orientation::default());
+    }
+
+    /// Like [`set_block_at_id`](Self::set_block_at_id), but the block is placed in the given
+    /// orientation. The orientation is stored as the block's [`Orientation`] dynamic property,
+    /// and can be changed later with [`set_dynamic_property`](Self::set_dynamic_property).
+    pub fn set_oriented_block_at_id(
+        &mut self,
+        chunk_cords: ChunkCords,
+        block_pos: BlockPos,
+        block_id: BlockId,
+        orientation: Orientation,
     ) {
         let current_block = self.get_block_id_at(chunk_cords, block_pos).unwrap_or(0);
 
@@ -55,6 +88,7 @@ impl<'w, 's, const N: usize> _BlocksMut<'w, 's, N> {
                 chunk_cords,
                 block_pos,
                 block_id,
+                orientation,
             });
         }
     }
@@ -68,6 +102,74 @@ impl<'w, 's, const N: usize> _BlocksMut<'w, 's, N> {
         let block_id = block_id!(block_name);
         self.set_block_at_id(chunk_cords, block_pos, block_id);
     }
+
+    /// Set the value of a [`DynamicProperty`] of the block. If the value changed, the block is
+    /// updated with [`BLOCK_STATE_CHANGED`], and the chunk is saved when it's unloaded.
+    pub fn set_dynamic_property<P>(
+        &mut self,
+        chunk_cords: ChunkCords,
+        block_pos: BlockPos,
+        property: P,
+    ) where
+        P: DynamicProperty + From<u8> + Into<u8> + Copy,
+    {
+        let Some(mut block_states) = self.get_block_states_mut(chunk_cords) else {
+            return;
+        };
+        let previous = block_states.set(block_pos, property);
+        if previous.map(|previous| previous.encode()) != Some(property.encode()) {
+            self.block_state_changed(chunk_cords, block_pos);
+        }
+    }
+
+    /// Remove a [`DynamicProperty`] from the block, returns its value. If the block had the
+    /// property, the block is updated with [`BLOCK_STATE_CHANGED`], and the chunk is saved when it's
+    /// unloaded.
+    pub fn remove_dynamic_property<P>(
+        &mut self,
+        chunk_cords: ChunkCords,
+        block_pos: BlockPos,
+    ) -> Option<P>
+    where
+        P: DynamicProperty + From<u8> + Into<u8> + Copy,
+    {
+        let removed = self
+            .get_block_states_mut(chunk_cords)?
+            .remove::<P>(block_pos);
+        if removed.is_some() {
+            self.block_state_changed(chunk_cords, block_pos);
+        }
+        removed
+    }
+
+    pub(crate) fn send_block_update(
+        &mut self,
+        chunk_cords: ChunkCords,
+        block_pos: BlockPos,
+        block_update_type: BlockUpdateType,
+    ) {
+        self.block_world_update_sender
+            .send(BlockWorldUpdateEvent::new(
+                block_pos,
+                chunk_cords,
+                BlockUpdate::Pure(block_update_type),
+            ));
+    }
+
+    fn block_state_changed(&mut self, chunk_cords: ChunkCords, block_pos: BlockPos) {
+        if let Some(chunk) = self.blocks.chunk_map.get_chunk(chunk_cords) {
+            self.commands.entity(chunk).insert(ModifiedChunk);
+        }
+        self.send_block_update(chunk_cords, block_pos, BLOCK_STATE_CHANGED);
+    }
+
+    fn get_block_states_mut(
+        &mut self,
+        chunk_cords: ChunkCords,
+    ) -> Option<Mut<'_, ChunkBlockStates>> {
+        let chunk = self.blocks.chunk_map.get_chunk(chunk_cords)?;
+        self.blocks.block_states_query.get_mut(chunk).ok()
+    }
 }
 
 impl<'w, 's, const N: usize> _Blocks<'w, 's, N> {
@@ -98,7 +200,54 @@ impl<'w, 's, const N: usize> _Blocks<'w, 's, N> {
         self.get_block_id_at(chunk_cords, block_pos).unwrap_or(0)
     }
 
-    pub fn get_chunk_grid(&self, chunk_cords: ChunkCords) -> Option<&Grid<BlockId, N>> {
+    /// The value of a [`DynamicProperty`] of the block, if it has one.
+    pub fn get_dynamic_property<P>(&self, chunk_cords: ChunkCords, block_pos: BlockPos) -> Option<P>
+    where
+        P: DynamicProperty + From<u8> + Into<u8> + Copy,
+    {
+        let chunk = self.chunk_map.get_chunk(chunk_cords)?;
+        let block_states = self.block_states_query.get(chunk).ok()?;
+        block_states.get(block_pos)
+    }
+
+    /// The [`BlockEntity`](crate::prelude::BlockEntity) of the block, if it has one.
+    pub fn get_block_entity(&self, global_block_pos: BlockGlobalPos) -> Option<Entity> {
+        let chunk = self.chunk_map.get_chunk(global_block_pos.cords)?;
+        let block_entities = self.block_entities_query.get(chunk).ok()?;
+        block_entities.0.get(&global_block_pos.pos).copied()
+    }
+
+    /// Cast a ray through the blocks of the world, and return the first (non air) block it hits
+    /// within `max_distance`. Only the loaded chunks are checked, it doesn't depend on colliders.
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<BlockRayHit> {
+        self.raycast_filtered(origin, direction, max_distance, |_, _| true)
+    }
+
+    /// Like [`_Blocks::raycast`], but the ray only stops at the (non air) blocks `can_hit` returns
+    /// true for. For example, to go through blocks that are non collidable, or xsprites.
+    pub fn raycast_filtered(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        can_hit: impl Fn(BlockGlobalPos, BlockId) -> bool,
+    ) -> Option<BlockRayHit> {
+        let dims = unsafe { PLACEHOLDER_DIMS };
+        let mut hit_block_id = 0;
+        let hit = voxel_raycast(origin, direction, max_distance, |block| {
+            let global_block_pos = block_to_global_block_pos(block, dims);
+            hit_block_id = self.block_id_at(global_block_pos.cords, global_block_pos.pos);
+            hit_block_id != 0 && can_hit(global_block_pos, hit_block_id)
+        })?;
+        Some(BlockRayHit {
+            global_block_pos: block_to_global_block_pos(hit.block, dims),
+            block_id: hit_block_id,
+            face: hit.face,
+            distance: hit.distance,
+        })
+    }
+
+    pub fn get_chunk_grid(&self, chunk_cords: ChunkCords) -> Option<&PalettedGrid<BlockId, N>> {
         let chunk = self.chunk_map.get_chunk(chunk_cords)?;
         let chunk = self.chunks_query.get(chunk).ok()?;
         Some(&chunk.0 .0)
@@ -110,11 +259,13 @@ impl<'w, 's, const N: usize> _Blocks<'w, 's, N> {
         block_pos: BlockPos,
     ) -> SurroundingBlocks<(Face, ChunkCords, BlockPos, BlockId)> {
         let default = SurroundingBlocks::default();
-        let chunk_entity = self.chunk_map.get_chunk(chunk_cords);
-        if chunk_entity.is_none() {
+        let Some(Ok((chunk_grid, _))) = self
+            .chunk_map
+            .get_chunk(chunk_cords)
+            .map(|chunk_entity| self.chunks_query.get(chunk_entity))
+        else {
             return default;
-        }
-        let chunk_grid = self.chunks_query.get(chunk_entity.unwrap()).unwrap().0;
+        };
         let global_block_pos = BlockGlobalPos::new(block_pos, chunk_cords);
         global_enumerate_neighboring_blocks(global_block_pos, unsafe { PLACEHOLDER_DIMS })
             .map(|(face, gbp)| {
@@ -1,23 +1,35 @@
-use crate::blockreg::meshreg::MeshReg;
+use crate::blockreg::{lightreg::LightReg, meshreg::MeshReg};
 use crate::prelude::Trigger;
 use crate::*;
 use action::{Action, IntoActionSet};
 use bevy_asset::{Assets, Handle};
 use bevy_ecs::world::unsafe_world_cell::UnsafeWorldCell;
+use bevy_hierarchy::DespawnRecursiveExt;
 use bevy_render::mesh::Mesh;
-use chunk::components::ToUpdate;
+use block_entity::{BlockEntity, BlockEntitySpawners, SavedBlockEntity};
+use chunk::components::{ModifiedChunk, ToUpdate};
 use chunk::meshmd::ChunkMeshMd;
+use chunk::resources::LightUpdates;
+use fluid::{
+    any_update, schedule_fluid_tick, update_fluid_meshes_around, update_surrounding_fluids,
+    FluidTicks,
+};
 use lazy_static::lazy_static;
-use moxi_mesh_utils::prelude::{BlockMeshType, MeshRegistry};
+use moxi_mesh_utils::prelude::{
+    BlockMeshType, CubeLayer, MeshRegistry, MeshRegistryCommon, EMPTY_SHAPE, FULL_SHAPE,
+};
 use moxi_mesh_utils::BlockMeshChange;
 use moxi_utils::prelude::{
-    is_block_pos_on_edge, neighbor_across_chunk, to_cords, BlockId, BlockPos, ChunkCords,
-    Dimensions, NDir, SurroundingBlocks, SurroundingBlocksCommon, FACES,
+    adj_chunk, is_block_pos_on_edge, neighbor_across_chunk, BlockGrid, BlockId, BlockPos,
+    ChunkCords, Dimensions, Face, Orientation, SurroundingBlocks, SurroundingBlocksCommon, FACES,
+    MAX_LIGHT,
+};
+use prelude::{
+    Block, BlockRegistry, CommonActionSet, Fluid, IntoTrigger, LightEmission, LightOpacity,
 };
-use prelude::{Block, BlockRegistry, CommonActionSet, IntoTrigger};
 use std::any::TypeId;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 lazy_static! {
     pub(crate) static ref NAME_2_ID: Mutex<HashMap<&'static str, BlockId>> =
@@ -74,6 +86,8 @@ pub struct GlobalBlockPlace {
     pub block_id: BlockId,
     pub block_pos: BlockPos,
     pub chunk_cords: ChunkCords,
+    /// The orientation the block is placed in, stored as its [`Orientation`] dynamic property.
+    pub orientation: Orientation,
 }
 
 #[derive(Event)]
@@ -162,9 +176,76 @@ impl<'w> BlockWorldMut<'w> {
         static_properties: B,
     ) -> &mut BlockWorldMut<'w> {
         self.block_world_mut.insert(static_properties);
+        let block_id = self.block_world_mut.get::<BlockMarker>().unwrap().0;
+        let light_emission = self.block_world_mut.get::<LightEmission>().copied();
+        let light_opacity = self.block_world_mut.get::<LightOpacity>().copied();
+        self.block_world_mut.world_scope(|world| {
+            let mut light_reg = world.resource_mut::<LightReg>();
+            if let Some(LightEmission(light_emission)) = light_emission {
+                light_reg.set_light_emission(block_id, light_emission);
+            }
+            if let Some(LightOpacity(light_opacity)) = light_opacity {
+                light_reg.set_light_opacity(block_id, light_opacity);
+            }
+        });
         self
     }
 
+    /// Make the block a [`Fluid`]. The block is scheduled to flow (see [`FluidTicks`]) whenever it,
+    /// or one of its neighbors, is updated.
+    pub fn with_fluid(&'w mut self, fluid: Fluid) -> &'w mut BlockWorldMut<'w> {
+        self.block_world_mut
+            .world_scope(|world| world.init_resource::<FluidTicks>());
+        self.with_static_properties(fluid)
+            .with_block_actions(any_update, (), schedule_fluid_tick)
+    }
+
+    /// Every placed block of this type gets its own entity (a [`BlockEntity`]), with a clone of the
+    /// bundle. The block entities of a chunk are spawned again (with a fresh clone of the bundle)
+    /// whenever the chunk is loaded, use [`with_saved_block_entity`](Self::with_saved_block_entity)
+    /// for block entities whose state persists with the chunk.
+    pub fn with_block_entity<B: Bundle + Clone>(
+        &'w mut self,
+        bundle: B,
+    ) -> &'w mut BlockWorldMut<'w> {
+        let block_id = self.block_world_mut.get::<BlockMarker>().unwrap().0;
+        self.block_world_mut.world_scope(|world| {
+            world.resource_mut::<BlockEntitySpawners>().spawners.insert(
+                block_id,
+                Arc::new(move |entity_commands| {
+                    entity_commands.insert(bundle.clone());
+                }),
+            );
+        });
+        self
+    }
+
+    /// Like [`with_block_entity`](Self::with_block_entity), but the component is saved with the
+    /// chunk when it's unloaded (if the [`ChunkStorage`](crate::prelude::ChunkStorage) resource
+    /// exists), and the saved component replaces the fresh clone when the chunk is loaded again.
+    pub fn with_saved_block_entity<C: SavedBlockEntity>(
+        &'w mut self,
+        component: C,
+    ) -> &'w mut BlockWorldMut<'w> {
+        let block_id = self.block_world_mut.get::<BlockMarker>().unwrap().0;
+        self.block_world_mut.world_scope(|world| {
+            world.resource_mut::<BlockEntitySpawners>().savers.insert(
+                block_id,
+                (
+                    Arc::new(|entity| entity.get::<C>().map(C::save)),
+                    Arc::new(|entity_commands, data| {
+                        let Some(component) = C::load(data) else {
+                            return false;
+                        };
+                        entity_commands.insert(component);
+                        true
+                    }),
+                ),
+            );
+        });
+        self.with_block_entity(component)
+    }
+
     pub fn with_block_actions<I, M1, M2, M3>(
         &'w mut self,
         into_trigger: impl IntoTrigger<I, M1>,
@@ -231,9 +312,11 @@ impl BlockInitiallizerTrait for World {
         if block_id == 0 {
             self.init_resource::<BlockRegistry>();
             self.init_resource::<MeshReg>();
+            self.init_resource::<LightReg>();
             self.init_resource::<BlockIdtoEnt>();
             self.init_resource::<ActionsMap>();
             self.init_resource::<TriggersMap>();
+            self.init_resource::<BlockEntitySpawners>();
         }
 
         assert!(
@@ -247,15 +330,27 @@ impl BlockInitiallizerTrait for World {
         ID_2_NAME.lock().unwrap().insert(block_id, block_name);
 
         let block_mesh = B::get_mesh();
+        let light_opacity = match block_mesh.get_type() {
+            BlockMeshType::Cube => MAX_LIGHT,
+            _ => 0,
+        };
         let handle = block_mesh
             .clone()
             .as_option()
             .map_or(Handle::default(), |mesh| {
                 self.resource_mut::<Assets<Mesh>>().add(mesh)
             });
+        let shape = B::get_shape().unwrap_or_else(|| match block_mesh.get_type() {
+            BlockMeshType::Cube | BlockMeshType::TranslucentCube => FULL_SHAPE.clone(),
+            _ => EMPTY_SHAPE.clone(),
+        });
         let mut mesh_reg = self.resource_mut::<MeshReg>();
         mesh_reg.meshes.push(block_mesh);
         mesh_reg.handles.push(handle);
+        mesh_reg.shapes.push(shape);
+        let mut light_reg = self.resource_mut::<LightReg>();
+        light_reg.emission.push(0);
+        light_reg.opacity.push(light_opacity);
 
         unsafe {
             let tmp_mut_ptr = self as *mut World;
@@ -287,18 +382,43 @@ pub(crate) fn global_block_placer<const N: usize>(
     mut chunk_meshes_query: Query<&mut ChunkMeshMd>,
     mesh_registry: Res<MeshReg>,
     mut block_world_update_sender: EventWriter<BlockWorldUpdateEvent>,
+    mut light_updates: ResMut<LightUpdates>,
+    block_entity_spawners: Res<BlockEntitySpawners>,
 ) {
     for event in block_place_events.read() {
         let GlobalBlockPlace {
             block_id,
             block_pos,
             chunk_cords,
+            orientation,
         } = *event;
 
         let surrounding_blocks = blocks.get_global_surrounding_blocks(chunk_cords, block_pos);
-        let chunk_entity = blocks.chunk_map.get_chunk(chunk_cords).unwrap();
-        let mut chunk_grid = blocks.chunks_query.get_mut(chunk_entity).unwrap();
+        let Some(chunk_entity) = blocks.chunk_map.get_chunk(chunk_cords) else {
+            continue;
+        };
+        let Ok(mut chunk_grid) = blocks.chunks_query.get_mut(chunk_entity) else {
+            continue;
+        };
         let _ = chunk_grid.0.set_block(block_id, block_pos);
+        commands.entity(chunk_entity).insert(ModifiedChunk);
+        if !orientation.is_default() {
+            if let Ok(mut block_states) = blocks.block_states_query.get_mut(chunk_entity) {
+                block_states.set(block_pos, orientation);
+            }
+        }
+        if let Ok(mut block_entities) = blocks.block_entities_query.get_mut(chunk_entity) {
+            block_entity_spawners.spawn(
+                &mut commands,
+                chunk_entity,
+                &mut block_entities,
+                BlockEntity {
+                    block_id,
+                    chunk_cords,
+                    block_pos,
+                },
+            );
+        }
         let mesh_type = mesh_registry.get_block_mesh_type(&block_id);
         let chunk_mesh_entity = chunk_grid.1.get_from_type(mesh_type.into());
         let mut chunk_mesh_md = chunk_meshes_query.get_mut(chunk_mesh_entity).unwrap();
@@ -309,7 +429,38 @@ pub(crate) fn global_block_placer<const N: usize>(
             block_id,
             surrounding_blocks.map(|x| x.map(|(_, _, _, id)| id)),
         );
+        update_surrounding_fluids(
+            &blocks,
+            surrounding_blocks
+                .into_iter()
+                .flatten()
+                .map(|(_, cc, bp, id)| (cc, bp, id)),
+            mesh_registry.as_ref(),
+            &mut chunk_meshes_query,
+            &mut commands,
+        );
+        if mesh_registry.is_fluid(&block_id) {
+            update_fluid_meshes_around(
+                &blocks,
+                chunk_cords,
+                block_pos,
+                block_id,
+                &mut chunk_meshes_query,
+                &mut commands,
+            );
+        }
+        update_faces_around_block(
+            &blocks,
+            block_id,
+            orientation,
+            surrounding_blocks,
+            BlockMeshChange::CullFaces,
+            mesh_registry.as_ref(),
+            &mut chunk_meshes_query,
+            &mut commands,
+        );
 
+        light_updates.push_block(chunk_cords, block_pos);
         block_world_update_sender.send(BlockWorldUpdateEvent {
             block_pos,
             chunk_cords,
@@ -325,6 +476,7 @@ pub(crate) fn global_block_breaker<const N: usize>(
     mut block_world_update_sender: EventWriter<BlockWorldUpdateEvent>,
     mut commands: Commands,
     mut chunk_meshes_query: Query<&mut ChunkMeshMd>,
+    mut light_updates: ResMut<LightUpdates>,
 ) {
     for event in block_break_events.read() {
         let GlobalBlockBreak {
@@ -334,10 +486,28 @@ pub(crate) fn global_block_breaker<const N: usize>(
         } = *event;
 
         let surrounding_blocks = blocks.get_global_surrounding_blocks(chunk_cords, block_pos);
-        let chunk_entity = blocks.chunk_map.get_chunk(chunk_cords).unwrap();
-        let mut chunk_grid = blocks.chunks_query.get_mut(chunk_entity).unwrap();
+        let Some(chunk_entity) = blocks.chunk_map.get_chunk(chunk_cords) else {
+            continue;
+        };
+        let Ok(mut chunk_grid) = blocks.chunks_query.get_mut(chunk_entity) else {
+            continue;
+        };
         let dims = chunk_grid.0.dims;
         let _ = chunk_grid.0.set_block(0, block_pos);
+        let mut orientation = Orientation::default();
+        if let Ok(mut block_states) = blocks.block_states_query.get_mut(chunk_entity) {
+            orientation = block_states.get(block_pos).unwrap_or_default();
+            block_states.clear(block_pos);
+        }
+        if let Some(block_entity) = blocks
+            .block_entities_query
+            .get_mut(chunk_entity)
+            .ok()
+            .and_then(|mut block_entities| block_entities.0.remove(&block_pos))
+        {
+            commands.entity(block_entity).despawn_recursive();
+        }
+        commands.entity(chunk_entity).insert(ModifiedChunk);
         let mesh_type = mesh_registry.get_block_mesh_type(&block_id);
         let chunk_mesh_entity = chunk_grid.1.get_from_type(mesh_type.into());
         let mut chunk_mesh_md = chunk_meshes_query.get_mut(chunk_mesh_entity).unwrap();
@@ -350,17 +520,60 @@ pub(crate) fn global_block_breaker<const N: usize>(
             block_id,
             surrounding_blocks.map(|x| x.map(|(_, _, _, id)| id)),
         );
+        update_surrounding_fluids(
+            &blocks,
+            surrounding_blocks
+                .into_iter()
+                .flatten()
+                .map(|(_, cc, bp, id)| (cc, bp, id)),
+            mesh_registry.as_ref(),
+            &mut chunk_meshes_query,
+            &mut commands,
+        );
+        if mesh_registry.is_fluid(&block_id) {
+            update_fluid_meshes_around(
+                &blocks,
+                chunk_cords,
+                block_pos,
+                block_id,
+                &mut chunk_meshes_query,
+                &mut commands,
+            );
+        }
+        update_faces_around_block(
+            &blocks,
+            block_id,
+            orientation,
+            surrounding_blocks,
+            BlockMeshChange::AddFaces,
+            mesh_registry.as_ref(),
+            &mut chunk_meshes_query,
+            &mut commands,
+        );
 
-        if matches!(mesh_type, BlockMeshType::Cube) {
-            for face in FACES.into_iter().filter(|face| !face.is_vertical()) {
+        if let Some(layer) = cube_layer(mesh_type) {
+            for face in FACES {
                 if is_block_pos_on_edge(block_pos, face, dims) {
-                    let adj_chunk_cords = chunk_cords + to_cords(Some(NDir::from(face)));
+                    let adj_chunk_cords = adj_chunk(chunk_cords, face);
                     let neighbor_block_pos = neighbor_across_chunk(block_pos, face, dims).unwrap();
-                    let adj_chunk_entity = blocks.chunk_map.get_chunk(adj_chunk_cords).unwrap();
-                    let adj_chunk_grid = blocks.chunks_query.get(adj_chunk_entity).unwrap();
+                    let Some(adj_chunk_entity) = blocks.chunk_map.get_chunk(adj_chunk_cords) else {
+                        continue;
+                    };
+                    let Ok(adj_chunk_grid) = blocks.chunks_query.get(adj_chunk_entity) else {
+                        continue;
+                    };
                     let neighbor_block = adj_chunk_grid.0.get_block_or(neighbor_block_pos, 0);
                     let adj_mesh_type = mesh_registry.get_block_mesh_type(&neighbor_block);
-                    if matches!(adj_mesh_type, BlockMeshType::Cube) {
+                    // The face of the neighbor was hidden by the block.
+                    if layer.contains(mesh_registry.as_ref(), &neighbor_block)
+                        && layer.is_face_culled(
+                            mesh_registry.as_ref(),
+                            &neighbor_block,
+                            face.opposite(),
+                            &block_id,
+                            orientation,
+                        )
+                    {
                         let adj_chunk_mesh_entity =
                             adj_chunk_grid.1.get_from_type(adj_mesh_type.into());
                         let mut adj_chunk_mesh_md =
@@ -380,6 +593,7 @@ pub(crate) fn global_block_breaker<const N: usize>(
 
         //
 
+        light_updates.push_block(chunk_cords, block_pos);
         block_world_update_sender.send(BlockWorldUpdateEvent {
             block_pos,
             chunk_cords,
@@ -387,3 +601,112 @@ pub(crate) fn global_block_breaker<const N: usize>(
         });
     }
 }
+
+/// The mesh of a block depends on its dynamic properties (like its [`Orientation`]), so the block
+/// is meshed again when they change.
+pub(crate) fn remesh_blocks_with_changed_state<const N: usize>(
+    mut block_world_updates: EventReader<BlockWorldUpdateEvent>,
+    blocks: _Blocks<N>,
+    mesh_registry: Res<MeshReg>,
+    mut commands: Commands,
+    mut chunk_meshes_query: Query<&mut ChunkMeshMd>,
+) {
+    for event in block_world_updates.read().filter(|event| {
+        event
+            .block_update
+            .is_pure_and(|update_type| update_type == BLOCK_STATE_CHANGED)
+    }) {
+        let BlockWorldUpdateEvent {
+            block_pos,
+            chunk_cords,
+            ..
+        } = *event;
+        let block_id = blocks.block_id_at(chunk_cords, block_pos);
+        let mesh_type = mesh_registry.get_block_mesh_type(&block_id);
+        if cube_layer(mesh_type).is_none() && mesh_type != BlockMeshType::Custom {
+            continue;
+        }
+        let Some(Ok((_, child_mesh_chunks))) = blocks
+            .chunk_map
+            .get_chunk(chunk_cords)
+            .map(|chunk_entity| blocks.chunks_query.get(chunk_entity))
+        else {
+            continue;
+        };
+        let chunk_mesh_entity = child_mesh_chunks.get_from_type(mesh_type.into());
+        let mut chunk_mesh_md = chunk_meshes_query.get_mut(chunk_mesh_entity).unwrap();
+        let surrounding_blocks = blocks
+            .get_global_surrounding_blocks(chunk_cords, block_pos)
+            .map(|x| x.map(|(_, _, _, id)| id));
+        chunk_mesh_md.log_block_break(block_pos, block_id, surrounding_blocks);
+        chunk_mesh_md.log_block_add(block_pos, block_id, surrounding_blocks);
+        commands.entity(chunk_mesh_entity).insert(ToUpdate);
+    }
+}
+
+/// Update the faces of the neighbors of a block that was placed ([`BlockMeshChange::CullFaces`])
+/// or broken ([`BlockMeshChange::AddFaces`]). The faces of the custom neighbors are hidden by
+/// their neighbors, so they are meshed again. The cube layers cull (or add back) the faces of
+/// their blocks by themselves, unless the block is a partial block (a block outside of the cube
+/// layers, with a [`BlockShape`](moxi_mesh_utils::prelude::BlockShape)), then the faces of the
+/// cube neighbors it hides are updated here.
+#[allow(clippy::too_many_arguments)]
+fn update_faces_around_block<const N: usize>(
+    blocks: &_Blocks<N>,
+    block_id: BlockId,
+    orientation: Orientation,
+    surrounding_blocks: SurroundingBlocks<(Face, ChunkCords, BlockPos, BlockId)>,
+    change: BlockMeshChange,
+    mesh_registry: &MeshReg,
+    chunk_meshes_query: &mut Query<&mut ChunkMeshMd>,
+    commands: &mut Commands,
+) {
+    let is_partial_block = cube_layer(mesh_registry.get_block_mesh_type(&block_id)).is_none();
+    for (face, neighbor_chunk_cords, neighbor_block_pos, neighbor_block) in
+        surrounding_blocks.into_iter().flatten()
+    {
+        let neighbor_mesh_type = mesh_registry.get_block_mesh_type(&neighbor_block);
+        let is_cube_to_update = is_partial_block
+            && cube_layer(neighbor_mesh_type).is_some()
+            && mesh_registry.occludes_face(&block_id, orientation, face);
+        if neighbor_mesh_type != BlockMeshType::Custom && !is_cube_to_update {
+            continue;
+        }
+        let Some(Ok((_, child_mesh_chunks))) = blocks
+            .chunk_map
+            .get_chunk(neighbor_chunk_cords)
+            .map(|chunk_entity| blocks.chunks_query.get(chunk_entity))
+        else {
+            continue;
+        };
+        let chunk_mesh_entity = child_mesh_chunks.get_from_type(neighbor_mesh_type.into());
+        let Ok(mut chunk_mesh_md) = chunk_meshes_query.get_mut(chunk_mesh_entity) else {
+            continue;
+        };
+        if neighbor_mesh_type == BlockMeshType::Custom {
+            chunk_mesh_md.log_block_add(neighbor_block_pos, neighbor_block, [None; 6]);
+        } else {
+            let surrounding_blocks = match change {
+                BlockMeshChange::AddFaces => SurroundingBlocks::<BlockId>::uniform(neighbor_block)
+                    .with_face(face.opposite(), 0),
+                _ => [None; 6].with_face(face.opposite(), block_id),
+            };
+            chunk_mesh_md.update_block(
+                change,
+                neighbor_block_pos,
+                neighbor_block,
+                surrounding_blocks,
+            );
+        }
+        commands.entity(chunk_mesh_entity).insert(ToUpdate);
+    }
+}
+
+/// The [`CubeLayer`] the blocks with the mesh type are meshed in, if they are cubes.
+fn cube_layer(mesh_type: BlockMeshType) -> Option<CubeLayer> {
+    match mesh_type {
+        BlockMeshType::Cube => Some(CubeLayer::Opaque),
+        BlockMeshType::TranslucentCube => Some(CubeLayer::Translucent),
+        _ => None,
+    }
+}
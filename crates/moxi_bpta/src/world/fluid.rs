@@ -0,0 +1,297 @@
+use crate::chunk::components::{Chunk, ChunkFluidLevels, ModifiedChunk, ToUpdate};
+use crate::chunk::meshmd::ChunkMeshMd;
+use crate::chunk::resources::ChunkMap;
+use crate::prelude::{Fluid, StaticBlockQuery, PLACEHOLDER_DIMS};
+use crate::*;
+use bevy_math::IVec3;
+use moxi_mesh_utils::prelude::{MeshRegistry, MeshRegistryCommon, MAX_FLUID_LEVEL};
+use moxi_utils::prelude::{block_to_global_block_pos, BlockId, BlockPos, ChunkCords, Face};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+/// The level of a fluid block changed.
+pub const FLUID_LEVEL_CHANGED: BlockUpdateType = BlockUpdateType::from_u128(48124891481412313);
+
+const HORIZONTAL_FACES: [Face; 4] = [Face::Right, Face::Left, Face::Back, Face::Front];
+
+type GlobalPos = (ChunkCords, BlockPos);
+
+/// The fluid blocks that are waiting to flow. The fluids advance by one tick every
+/// [`FixedUpdate`](bevy_app::FixedUpdate).
+#[derive(Resource, Default)]
+pub struct FluidTicks {
+    tick: u64,
+    /// The tick each fluid block is going to flow at.
+    scheduled: HashMap<GlobalPos, u64>,
+    /// The fluid blocks that were updated since the last tick.
+    pending: Vec<GlobalPos>,
+    /// The levels of the fluid blocks the fluids flowed into, until they are placed.
+    flowing: HashMap<GlobalPos, u8>,
+}
+
+impl FluidTicks {
+    /// The number of ticks since the app started.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+}
+
+/// Fluid blocks react to all of the updates, to them and to their neighbors.
+pub(crate) fn any_update(_: In<BlockWorldUpdateEvent>) -> bool {
+    true
+}
+
+/// The action of the fluid blocks: schedule the block to flow. Fluid blocks that the fluid flowed
+/// into get their level, the rest of the placed fluid blocks are sources.
+pub(crate) fn schedule_fluid_tick(
+    In(event): In<BlockWorldUpdateEvent>,
+    mut fluid_ticks: ResMut<FluidTicks>,
+    chunk_map: Res<ChunkMap>,
+    mut fluid_levels: Query<&mut ChunkFluidLevels>,
+) {
+    let global_pos = (event.chunk_cords(), event.block_pos());
+    if event
+        .block_update()
+        .is_pure_and(|block_update| block_update == BLOCK_PLACED)
+    {
+        let level = fluid_ticks
+            .flowing
+            .get(&global_pos)
+            .copied()
+            .unwrap_or(MAX_FLUID_LEVEL);
+        if let Some(mut chunk_fluid_levels) = chunk_map
+            .get_chunk(event.chunk_cords())
+            .and_then(|chunk_entity| fluid_levels.get_mut(chunk_entity).ok())
+        {
+            chunk_fluid_levels.set_level(event.block_pos(), level);
+        }
+    }
+    fluid_ticks.pending.push(global_pos);
+}
+
+/// Advance the fluids by one tick. Each fluid block that is due flows into the air below it, or
+/// if it's standing on a block, into the air around it (one level lower). Flowing fluid blocks
+/// take their level from the fluid above them, or from their highest neighbor, so they recede
+/// once their source is removed.
+pub(crate) fn tick_fluids<const N: usize>(
+    mut fluid_ticks: ResMut<FluidTicks>,
+    mut blocks: _BlocksMut<N>,
+    fluids: StaticBlockQuery<&Fluid>,
+    mut fluid_levels: Query<&mut ChunkFluidLevels>,
+    mut chunk_meshes: Query<&mut ChunkMeshMd>,
+    mut commands: Commands,
+) {
+    let FluidTicks {
+        tick,
+        scheduled,
+        pending,
+        flowing,
+    } = fluid_ticks.as_mut();
+    *tick += 1;
+    // The blocks the fluid flowed into were either placed or replaced.
+    flowing.retain(|(chunk_cords, block_pos), _| {
+        blocks.get_block_id_at(*chunk_cords, *block_pos) == Some(0)
+    });
+    for global_pos in pending.drain(..) {
+        let block = blocks.block_id_at(global_pos.0, global_pos.1);
+        if let Some(fluid) = fluids.get_static_property(block) {
+            scheduled
+                .entry(global_pos)
+                .or_insert(*tick + fluid.tick_delay as u64);
+        }
+    }
+    let due: Vec<GlobalPos> = scheduled
+        .iter()
+        .filter(|(_, due_tick)| **due_tick <= *tick)
+        .map(|(global_pos, _)| *global_pos)
+        .collect();
+    scheduled.retain(|_, due_tick| *due_tick > *tick);
+
+    let level_at = |fluid_levels: &Query<&mut ChunkFluidLevels>,
+                    blocks: &_BlocksMut<N>,
+                    (chunk_cords, block_pos): GlobalPos| {
+        blocks
+            .chunk_map
+            .get_chunk(chunk_cords)
+            .and_then(|chunk_entity| fluid_levels.get(chunk_entity).ok())
+            .map_or(MAX_FLUID_LEVEL, |chunk_fluid_levels| {
+                chunk_fluid_levels.level(block_pos)
+            })
+    };
+
+    for (chunk_cords, block_pos) in due {
+        let block = blocks.block_id_at(chunk_cords, block_pos);
+        let Some(fluid) = fluids.get_static_property(block).copied() else {
+            continue;
+        };
+        let min_level = MAX_FLUID_LEVEL.saturating_sub(fluid.flow_distance).max(1);
+        let neighbors = blocks.get_global_surrounding_blocks(chunk_cords, block_pos);
+        let same_fluid = |face: Face| neighbors[face].filter(|(_, _, _, id)| *id == block);
+
+        let mut level = level_at(&fluid_levels, &blocks, (chunk_cords, block_pos));
+        if level < MAX_FLUID_LEVEL {
+            let fed_level = if same_fluid(Face::Top).is_some() {
+                MAX_FLUID_LEVEL - 1
+            } else {
+                HORIZONTAL_FACES
+                    .into_iter()
+                    .filter_map(same_fluid)
+                    .map(|(_, cc, bp, _)| level_at(&fluid_levels, &blocks, (cc, bp)))
+                    .max()
+                    .unwrap_or(0)
+                    .saturating_sub(1)
+            };
+            let fed_level = if fed_level < min_level { 0 } else { fed_level };
+            if fed_level != level {
+                level = fed_level;
+                let Some(chunk_entity) = blocks.chunk_map.get_chunk(chunk_cords) else {
+                    continue;
+                };
+                if let Ok(mut chunk_fluid_levels) = fluid_levels.get_mut(chunk_entity) {
+                    chunk_fluid_levels.set_level(block_pos, level);
+                }
+                if level == 0 {
+                    blocks.set_block_at_id(chunk_cords, block_pos, 0);
+                    continue;
+                }
+                // The levels are saved with the chunk.
+                commands.entity(chunk_entity).insert(ModifiedChunk);
+                update_fluid_meshes_around(
+                    &blocks,
+                    chunk_cords,
+                    block_pos,
+                    block,
+                    &mut chunk_meshes,
+                    &mut commands,
+                );
+                blocks.send_block_update(chunk_cords, block_pos, FLUID_LEVEL_CHANGED);
+            }
+        }
+
+        let mut flow_into =
+            |blocks: &mut _BlocksMut<N>, global_pos: GlobalPos, level: u8| match flowing
+                .entry(global_pos)
+            {
+                Entry::Occupied(mut entry) => {
+                    let flowing_level = entry.get_mut();
+                    *flowing_level = (*flowing_level).max(level);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(level);
+                    blocks.set_block_at_id(global_pos.0, global_pos.1, block);
+                }
+            };
+        match neighbors[Face::Bottom] {
+            Some((_, cc, bp, 0)) => {
+                flow_into(&mut blocks, (cc, bp), MAX_FLUID_LEVEL - 1);
+                continue;
+            }
+            Some((_, _, _, id)) if id == block => continue,
+            _ => {}
+        }
+        if level <= min_level {
+            continue;
+        }
+        for (_, cc, bp, _) in HORIZONTAL_FACES
+            .into_iter()
+            .filter_map(|face| neighbors[face])
+            .filter(|(_, _, _, id)| *id == 0)
+        {
+            flow_into(&mut blocks, (cc, bp), level - 1);
+        }
+    }
+}
+
+/// Schedule the flowing fluid blocks of the chunks that were loaded to flow, the ticks they were
+/// scheduled at aren't saved with their chunk.
+pub(crate) fn schedule_loaded_fluids(
+    mut fluid_ticks: ResMut<FluidTicks>,
+    loaded_chunks: Query<(&Chunk, &ChunkFluidLevels), Added<ChunkFluidLevels>>,
+) {
+    for (chunk, chunk_fluid_levels) in loaded_chunks.iter() {
+        fluid_ticks.pending.extend(
+            chunk_fluid_levels
+                .0
+                .keys()
+                .map(|block_pos| (chunk.cords, *block_pos)),
+        );
+    }
+}
+
+/// Mark the fluid meshes of the chunks around the fluid block to be meshed again, the surfaces of
+/// the fluid blocks around it depend on it, also across the borders of its chunk.
+pub(crate) fn update_fluid_meshes_around<const N: usize>(
+    blocks: &_Blocks<N>,
+    chunk_cords: ChunkCords,
+    block_pos: BlockPos,
+    block_id: BlockId,
+    chunk_meshes: &mut Query<&mut ChunkMeshMd>,
+    commands: &mut Commands,
+) {
+    let dims = unsafe { PLACEHOLDER_DIMS };
+    let global_block = chunk_cords * dims.as_ivec3() + block_pos.as_ivec3();
+    let mut chunks = HashSet::new();
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let neighbor = block_to_global_block_pos(global_block + IVec3::new(x, y, z), dims);
+                if chunks.insert(neighbor.cords) {
+                    update_fluid_mesh(
+                        blocks,
+                        neighbor.cords,
+                        neighbor.pos,
+                        block_id,
+                        chunk_meshes,
+                        commands,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Mark the fluid blocks around the block to be meshed again, their faces depend on it.
+pub(crate) fn update_surrounding_fluids<const N: usize>(
+    blocks: &_Blocks<N>,
+    surrounding_blocks: impl Iterator<Item = (ChunkCords, BlockPos, BlockId)>,
+    mesh_registry: &impl MeshRegistry<BlockId>,
+    chunk_meshes: &mut Query<&mut ChunkMeshMd>,
+    commands: &mut Commands,
+) {
+    for (chunk_cords, block_pos, block_id) in surrounding_blocks {
+        if mesh_registry.is_fluid(&block_id) {
+            update_fluid_mesh(
+                blocks,
+                chunk_cords,
+                block_pos,
+                block_id,
+                chunk_meshes,
+                commands,
+            );
+        }
+    }
+}
+
+/// Mark the fluid mesh of the chunk to be meshed again.
+fn update_fluid_mesh<const N: usize>(
+    blocks: &_Blocks<N>,
+    chunk_cords: ChunkCords,
+    block_pos: BlockPos,
+    block_id: BlockId,
+    chunk_meshes: &mut Query<&mut ChunkMeshMd>,
+    commands: &mut Commands,
+) {
+    let Some(Ok((_, child_mesh_chunks))) = blocks
+        .chunk_map
+        .get_chunk(chunk_cords)
+        .map(|chunk_entity| blocks.chunks_query.get(chunk_entity))
+    else {
+        return;
+    };
+    let fluid_mesh_chunk = child_mesh_chunks.fluid_mesh_chunk;
+    if let Ok(mut chunk_mesh_md) = chunk_meshes.get_mut(fluid_mesh_chunk) {
+        if let ChunkMeshMd::Fluid(md) = chunk_mesh_md.as_mut() {
+            md.log_update(block_id, block_pos);
+        }
+        commands.entity(fluid_mesh_chunk).insert(ToUpdate);
+    }
+}
@@ -1,21 +1,43 @@
 pub(crate) mod block_commands;
+pub(crate) mod block_entity;
 pub(crate) mod blocks_param;
 pub(crate) mod blockworld;
+pub(crate) mod fluid;
+pub(crate) mod random_ticks;
+pub(crate) mod region;
+pub(crate) mod scheduled_ticks;
+pub(crate) mod structure;
 pub(crate) mod update_event;
 
+pub use block_entity::{BlockEntity, SavedBlockEntity};
 pub use blocks_param::*;
+pub use fluid::{FluidTicks, FLUID_LEVEL_CHANGED};
+pub use random_ticks::RandomTicks;
+pub use region::*;
+pub use scheduled_ticks::{BlockTicks, ScheduledTick};
+pub use structure::{
+    Structure, StructurePlacements, DEFAULT_MAX_PENDING_STRUCTURE_CHUNKS, STRUCTURE_FORMAT_VERSION,
+};
 pub use update_event::*;
 
 #[cfg(test)]
 mod tests {
+    use super::block_entity::BlockEntitySpawners;
     use super::blockworld::*;
     use crate::blockreg::meshreg::MeshReg;
+    use crate::chunk::components::{
+        ChildMeshChunks, ChunkBlockEntities, ChunkBlockStates, ChunkGrid, ModifiedChunk,
+    };
+    use crate::chunk::resources::ChunkMap;
     use crate::prelude::*;
     use bevy_asset::Assets;
-    use bevy_ecs::prelude::*;
+    use bevy_ecs::{prelude::*, system::SystemState};
     use bevy_render::mesh::Mesh;
     use defs::*;
     use moxi_mesh_utils::prelude::{BlockMeshType, MeshRegistry};
+    use moxi_utils::prelude::{
+        BlockGlobalPos, BlockGrid, BlockId, Dimensions, LightRegistry, PalettedGrid,
+    };
 
     // different module so I can fold it neetly in the editor
     mod defs {
@@ -71,6 +93,44 @@ mod tests {
         pub fn action_print_hello_world(_world: &mut World) {
             println!("Hello world!");
         }
+
+        #[derive(Component, Clone)]
+        pub struct Inventory(pub Vec<u32>);
+
+        impl SavedBlockEntity for Inventory {
+            fn save(&self) -> Vec<u8> {
+                self.0.iter().flat_map(|item| item.to_le_bytes()).collect()
+            }
+
+            fn load(data: &[u8]) -> Option<Self> {
+                let items = data.chunks_exact(4);
+                if !items.remainder().is_empty() {
+                    return None;
+                }
+                Some(Self(
+                    items
+                        .map(|item| u32::from_le_bytes(item.try_into().unwrap()))
+                        .collect(),
+                ))
+            }
+        }
+
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub struct Open(pub bool);
+
+        impl DynamicProperty for Open {}
+
+        impl From<u8> for Open {
+            fn from(value: u8) -> Self {
+                Self(value != 0)
+            }
+        }
+
+        impl From<Open> for u8 {
+            fn from(open: Open) -> Self {
+                open.0 as u8
+            }
+        }
     }
 
     /// Test simply adding a block
@@ -128,6 +188,270 @@ mod tests {
         assert_eq!(mesh_ty, BlockMeshType::Cube);
     }
 
+    /// Test the light static properties, and that they are registered in the light registry
+    #[test]
+    fn test_light_properties() {
+        let mut app = bevy_app::App::new();
+        let world = &mut app.world;
+
+        world.init_resource::<Assets<Mesh>>();
+        world
+            .init_block::<Block1>()
+            .with_static_properties(LightEmission(14))
+            .init_block::<Block2>()
+            .with_static_properties(LightOpacity(2));
+
+        let light_reg = world.resource::<LightReg>();
+        assert_eq!(light_reg.light_emission(&0), 14);
+        assert_eq!(light_reg.light_opacity(&0), 0);
+        assert_eq!(light_reg.light_emission(&1), 0);
+        assert_eq!(light_reg.light_opacity(&1), 2);
+
+        let mut system_state: SystemState<StaticBlockQuery<&LightEmission>> =
+            SystemState::new(world);
+        let static_block_query = system_state.get(world);
+        assert_eq!(
+            static_block_query.get_static_property(0).map(|e| e.0),
+            Some(14)
+        );
+        assert!(static_block_query.get_static_property(1).is_none());
+    }
+
+    /// Test reading and writing the dynamic properties of a block
+    #[test]
+    fn test_dynamic_properties() {
+        let mut app = bevy_app::App::new();
+        app.add_event::<GlobalBlockPlace>()
+            .add_event::<GlobalBlockBreak>()
+            .add_event::<GlobalRegionEdit>()
+            .add_event::<BlockWorldUpdateEvent>();
+        let world = &mut app.world;
+        world.init_resource::<Assets<Mesh>>();
+        world.init_block::<Block1>();
+
+        let mut chunk_map = ChunkMap::default();
+        let chunk_entity = world
+            .spawn((
+                ChunkGrid::<8>(PalettedGrid::new(0, Dimensions::new(2, 2, 2))),
+                ChildMeshChunks {
+                    cube_mesh_chunk: Entity::PLACEHOLDER,
+                    translucent_mesh_chunk: Entity::PLACEHOLDER,
+                    xsprite_mesh_chunk: Entity::PLACEHOLDER,
+                    custom_mesh_chunk: Entity::PLACEHOLDER,
+                    fluid_mesh_chunk: Entity::PLACEHOLDER,
+                },
+                ChunkBlockStates::default(),
+            ))
+            .id();
+        chunk_map.insert_chunk([0, 0, 0].into(), chunk_entity);
+        world.insert_resource(chunk_map);
+
+        let (chunk_cords, block_pos) = ([0, 0, 0].into(), [1, 0, 1].into());
+        let mut system_state: SystemState<_BlocksMut<8>> = SystemState::new(world);
+        let mut blocks = system_state.get_mut(world);
+        assert_eq!(
+            blocks.get_dynamic_property::<Open>(chunk_cords, block_pos),
+            None
+        );
+        blocks.set_dynamic_property(chunk_cords, block_pos, Open(true));
+        // Setting the same value again isn't a change
+        blocks.set_dynamic_property(chunk_cords, block_pos, Open(true));
+        assert_eq!(
+            blocks.get_dynamic_property::<Open>(chunk_cords, block_pos),
+            Some(Open(true))
+        );
+        assert_eq!(
+            blocks.remove_dynamic_property::<Open>(chunk_cords, block_pos),
+            Some(Open(true))
+        );
+        assert_eq!(
+            blocks.get_dynamic_property::<Open>(chunk_cords, block_pos),
+            None
+        );
+        system_state.apply(world);
+
+        let events = world.resource::<Events<BlockWorldUpdateEvent>>();
+        let updates: Vec<_> = events.get_reader().read(events).copied().collect();
+        assert_eq!(updates.len(), 2);
+        assert!(updates.iter().all(|update| update
+            .block_update()
+            .is_pure_and(|update_type| update_type == BLOCK_STATE_CHANGED)));
+        // The chunk is saved with the changed block states when it's unloaded.
+        assert!(world.get::<ModifiedChunk>(chunk_entity).is_some());
+    }
+
+    /// Test spawning the block entity of a placed block, and looking it up
+    #[test]
+    fn test_block_entities() {
+        let mut app = bevy_app::App::new();
+        let world = &mut app.world;
+        world.init_resource::<Assets<Mesh>>();
+        world.init_block::<Block1>();
+        world
+            .init_block::<Block2>()
+            .with_block_entity(Inventory(vec![1, 2, 3]));
+
+        let chunk_entity = world.spawn(ChunkBlockEntities::default()).id();
+        let mut chunk_map = ChunkMap::default();
+        chunk_map.insert_chunk([0, 0, 0].into(), chunk_entity);
+        world.insert_resource(chunk_map);
+
+        let mut system_state: SystemState<(
+            Commands,
+            Res<BlockEntitySpawners>,
+            Query<&mut ChunkBlockEntities>,
+        )> = SystemState::new(world);
+        let (mut commands, spawners, mut block_entities) = system_state.get_mut(world);
+        let mut block_entities = block_entities.get_mut(chunk_entity).unwrap();
+        for block_id in [0, 1] {
+            spawners.spawn(
+                &mut commands,
+                chunk_entity,
+                &mut block_entities,
+                BlockEntity {
+                    block_id,
+                    chunk_cords: [0, 0, 0].into(),
+                    block_pos: [block_id as u32, 0, 0].into(),
+                },
+            );
+        }
+        system_state.apply(world);
+
+        let mut system_state: SystemState<_Blocks<8>> = SystemState::new(world);
+        let blocks = system_state.get_mut(world);
+        // Only Block2 has a block entity
+        let global_pos = |x| BlockGlobalPos::new([x, 0, 0].into(), [0, 0, 0].into());
+        assert!(blocks.get_block_entity(global_pos(0)).is_none());
+        let block_entity = blocks.get_block_entity(global_pos(1)).unwrap();
+        assert_eq!(
+            world.get::<Inventory>(block_entity).unwrap().0,
+            vec![1, 2, 3]
+        );
+        assert_eq!(world.get::<BlockEntity>(block_entity).unwrap().block_id, 1);
+    }
+
+    /// Test saving a block entity, and loading it into the entity of the block when its chunk is
+    /// loaded again
+    #[test]
+    fn test_saved_block_entities() {
+        let mut app = bevy_app::App::new();
+        let world = &mut app.world;
+        world.init_resource::<Assets<Mesh>>();
+        world.init_block::<Block1>();
+        world
+            .init_block::<Block2>()
+            .with_saved_block_entity(Inventory(vec![1, 2, 3]));
+        let chunk_entity = world.spawn(ChunkBlockEntities::default()).id();
+        let block_entity = |block_pos: [u32; 3]| BlockEntity {
+            block_id: 1,
+            chunk_cords: [0, 0, 0].into(),
+            block_pos: block_pos.into(),
+        };
+
+        let mut system_state: SystemState<(
+            Commands,
+            Res<BlockEntitySpawners>,
+            Query<&mut ChunkBlockEntities>,
+        )> = SystemState::new(world);
+        let (mut commands, spawners, mut block_entities) = system_state.get_mut(world);
+        let mut block_entities = block_entities.get_mut(chunk_entity).unwrap();
+        let entity = spawners
+            .spawn(
+                &mut commands,
+                chunk_entity,
+                &mut block_entities,
+                block_entity([0, 0, 0]),
+            )
+            .unwrap();
+        system_state.apply(world);
+        world.get_mut::<Inventory>(entity).unwrap().0 = vec![4, 5];
+
+        let spawners = world.resource::<BlockEntitySpawners>().clone();
+        let saved = spawners.save(&world.entity(entity)).unwrap();
+        let (mut commands, spawners, mut block_entities) = system_state.get_mut(world);
+        let mut block_entities = block_entities.get_mut(chunk_entity).unwrap();
+        let mut spawn = |block_pos, data: &[u8]| {
+            let entity = spawners
+                .spawn(
+                    &mut commands,
+                    chunk_entity,
+                    &mut block_entities,
+                    block_entity(block_pos),
+                )
+                .unwrap();
+            let loaded = spawners.load(&mut commands.entity(entity), 1, data);
+            (entity, loaded)
+        };
+        let (loaded_entity, loaded) = spawn([1, 0, 0], &saved);
+        assert!(loaded);
+        // Invalid data leaves the fresh block entity
+        let (invalid_entity, loaded) = spawn([2, 0, 0], &[1, 2, 3]);
+        assert!(!loaded);
+        system_state.apply(world);
+
+        assert_eq!(world.get::<Inventory>(loaded_entity).unwrap().0, vec![4, 5]);
+        assert_eq!(
+            world.get::<Inventory>(invalid_entity).unwrap().0,
+            vec![1, 2, 3]
+        );
+    }
+
+    /// Test that the random ticks only reach the blocks that opted in, the same way every run
+    #[test]
+    fn test_random_ticks() {
+        use super::random_ticks::send_random_ticks;
+        use bevy_ecs::system::RunSystemOnce;
+
+        let random_tick_updates = |seed: u64| {
+            let mut app = bevy_app::App::new();
+            app.add_event::<BlockWorldUpdateEvent>();
+            let world = &mut app.world;
+            world.init_resource::<Assets<Mesh>>();
+            world.init_block::<Block1>();
+            world
+                .init_block::<Block2>()
+                .with_static_properties(RandomlyTicked);
+            world.insert_resource(RandomTicks::new(seed, 16));
+
+            // The bottom half of the chunks is Block2, the top half is Block1.
+            let dims = Dimensions::new(4, 4, 4);
+            let mut grid = PalettedGrid::<BlockId, 64>::new(0, dims);
+            for (block_pos, _) in grid.clone().enumerate_blocks() {
+                if block_pos.y < 2 {
+                    grid.set_block(1, block_pos).unwrap();
+                }
+            }
+            let mut chunk_map = ChunkMap::default();
+            for chunk_cords in [[0, 0, 0], [1, 0, 0], [0, 0, -1]] {
+                let chunk_entity = world.spawn(ChunkGrid::<64>(grid.clone())).id();
+                chunk_map.insert_chunk(chunk_cords.into(), chunk_entity);
+            }
+            world.insert_resource(chunk_map);
+
+            let mut updates = Vec::new();
+            for _ in 0..4 {
+                world.run_system_once(send_random_ticks::<64>);
+                let events = world.resource::<Events<BlockWorldUpdateEvent>>();
+                updates.extend(events.get_reader().read(events).map(|update| {
+                    assert!(update
+                        .block_update()
+                        .is_pure_and(|update_type| update_type == RANDOM_TICK));
+                    (update.chunk_cords(), update.block_pos())
+                }));
+                world
+                    .resource_mut::<Events<BlockWorldUpdateEvent>>()
+                    .clear();
+            }
+            updates
+        };
+
+        let updates = random_tick_updates(7);
+        assert!(!updates.is_empty());
+        assert!(updates.iter().all(|(_, block_pos)| block_pos.y < 2));
+        assert_eq!(updates, random_tick_updates(7));
+        assert_ne!(updates, random_tick_updates(8));
+    }
+
     /// Test the execution of block actions
     #[test]
     fn test_block_actions1() {
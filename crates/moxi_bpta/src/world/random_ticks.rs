@@ -0,0 +1,108 @@
+//! Random ticks: every fixed tick, random blocks of each loaded chunk receive an update (grass
+//! spreading, leaves decaying, crops growing, ...).
+
+use crate::chunk::components::ChunkGrid;
+use crate::chunk::resources::ChunkMap;
+use crate::prelude::{RandomlyTicked, StaticBlockQuery};
+use crate::*;
+use moxi_utils::prelude::{BlockGrid, BlockPos, ChunkCords, Dimensions};
+
+/// Resource that configures the random ticks. Every [`FixedUpdate`](bevy_app::FixedUpdate),
+/// `blocks_per_chunk` random positions of each loaded chunk are picked, and the blocks there that
+/// are [`RandomlyTicked`] receive a pure [`RANDOM_TICK`] update. The positions only depend on the
+/// seed, the tick and the chunk, so the same seed picks the same blocks.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct RandomTicks {
+    pub seed: u64,
+    /// How many positions are picked in each chunk every tick, 0 turns the random ticks off.
+    pub blocks_per_chunk: u32,
+    tick: u64,
+}
+
+impl Default for RandomTicks {
+    fn default() -> Self {
+        Self::new(0, 3)
+    }
+}
+
+impl RandomTicks {
+    pub fn new(seed: u64, blocks_per_chunk: u32) -> Self {
+        Self {
+            seed,
+            blocks_per_chunk,
+            tick: 0,
+        }
+    }
+
+    /// The number of random ticks since the app started.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The positions picked in the chunk at the current tick.
+    pub fn positions(
+        &self,
+        chunk_cords: ChunkCords,
+        dims: Dimensions,
+    ) -> impl Iterator<Item = BlockPos> {
+        let mut state = [
+            self.tick,
+            chunk_cords.x as u64,
+            chunk_cords.y as u64,
+            chunk_cords.z as u64,
+        ]
+        .into_iter()
+        .fold(self.seed, |state, value| splitmix64(state ^ value));
+        let len = (dims.x * dims.y * dims.z) as u64;
+        (0..self.blocks_per_chunk).map(move |_| {
+            state = splitmix64(state);
+            let i = (state % len) as u32;
+            BlockPos::new(i % dims.x, i / (dims.x * dims.z), i / dims.x % dims.z)
+        })
+    }
+}
+
+/// A step of the SplitMix64 generator.
+fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Send the random ticks of the current tick, the chunks are visited in order so the updates are
+/// always sent in the same order.
+pub(crate) fn send_random_ticks<const N: usize>(
+    mut random_ticks: ResMut<RandomTicks>,
+    chunk_map: Res<ChunkMap>,
+    chunk_grids: Query<&ChunkGrid<N>>,
+    randomly_ticked: StaticBlockQuery<&RandomlyTicked>,
+    mut block_world_update_sender: EventWriter<BlockWorldUpdateEvent>,
+) {
+    random_ticks.tick += 1;
+    if random_ticks.blocks_per_chunk == 0 {
+        return;
+    }
+    let mut chunks: Vec<_> = chunk_map
+        .iter()
+        .filter(|(_, chunk_entity)| *chunk_entity != Entity::PLACEHOLDER)
+        .collect();
+    chunks.sort_by_key(|(chunk_cords, _)| chunk_cords.to_array());
+    for (chunk_cords, chunk_entity) in chunks {
+        let Ok(chunk_grid) = chunk_grids.get(chunk_entity) else {
+            continue;
+        };
+        for block_pos in random_ticks.positions(chunk_cords, chunk_grid.0.dims()) {
+            let Some(block_id) = chunk_grid.0.get_block(block_pos) else {
+                continue;
+            };
+            if randomly_ticked.get_static_property(block_id).is_some() {
+                block_world_update_sender.send(BlockWorldUpdateEvent::new(
+                    block_pos,
+                    chunk_cords,
+                    BlockUpdate::Pure(RANDOM_TICK),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,642 @@
+//! Editing whole regions of the world at once. The blocks of a region edit are written to the
+//! grids of the chunks in bulk, and each chunk that changed is meshed again once, instead of once
+//! per block.
+
+use crate::chunk::components::{ChunkFluidLevels, ModifiedChunk, ToRemesh};
+use crate::chunk::resources::LightUpdates;
+use crate::*;
+use bevy_hierarchy::DespawnRecursiveExt;
+use bevy_math::{I64Vec3, IVec3};
+use block_entity::{BlockEntity, BlockEntitySpawners};
+use blockworld::PLACEHOLDER_DIMS;
+use moxi_utils::prelude::{
+    adj_chunk, block_to_global_block_pos, is_block_pos_on_edge, BlockGrid, BlockId, BlockPos,
+    ChunkCords, FACES,
+};
+use std::collections::{HashMap, HashSet};
+
+/// The largest radius of a [`Region::Sphere`], larger radii are clamped to it so a huge sphere
+/// doesn't enumerate billions of blocks.
+pub const MAX_SPHERE_RADIUS: f32 = 64.0;
+
+/// The most blocks a [`Region::Cuboid`] or a [`Region::Line`] can have, larger ones are empty so
+/// they don't enumerate billions of blocks either. It's more than the blocks of the largest sphere.
+pub const MAX_REGION_BLOCKS: u64 = 1 << 22;
+
+/// A region of the world. The positions are in blocks from the origin of the world, see
+/// [`block_to_global_block_pos`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Region {
+    /// All of the blocks between the two corners (inclusive). The cuboid is empty if it has more
+    /// than [`MAX_REGION_BLOCKS`] blocks.
+    Cuboid { min: IVec3, max: IVec3 },
+    /// The blocks whose centers are within `radius` of the center. The radius is clamped to
+    /// [`MAX_SPHERE_RADIUS`], the sphere is empty if the radius isn't finite.
+    Sphere { center: IVec3, radius: f32 },
+    /// The blocks on the line between the two blocks (inclusive). The line is empty if it has
+    /// more than [`MAX_REGION_BLOCKS`] blocks.
+    Line { from: IVec3, to: IVec3 },
+}
+
+impl Region {
+    /// The cuboid between the two corners, in any order.
+    pub fn cuboid(corner1: IVec3, corner2: IVec3) -> Self {
+        Self::Cuboid {
+            min: corner1.min(corner2),
+            max: corner1.max(corner2),
+        }
+    }
+
+    pub fn sphere(center: IVec3, radius: f32) -> Self {
+        Self::Sphere { center, radius }
+    }
+
+    pub fn line(from: IVec3, to: IVec3) -> Self {
+        Self::Line { from, to }
+    }
+
+    /// The blocks of the region.
+    pub fn blocks(&self) -> Vec<IVec3> {
+        match *self {
+            Region::Cuboid { min, max } => {
+                let size = (max.as_i64vec3() - min.as_i64vec3() + 1).max(I64Vec3::ZERO);
+                let volume = (size.x as u64)
+                    .checked_mul(size.y as u64)
+                    .and_then(|area| area.checked_mul(size.z as u64));
+                if volume.filter(|volume| *volume <= MAX_REGION_BLOCKS).is_none() {
+                    return vec![];
+                }
+                (min.y..=max.y)
+                    .flat_map(|y| {
+                        (min.z..=max.z)
+                            .flat_map(move |z| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+                    })
+                    .collect()
+            }
+            Region::Sphere { center, radius } => {
+                if !radius.is_finite() {
+                    return vec![];
+                }
+                let radius = radius.min(MAX_SPHERE_RADIUS);
+                let extent = IVec3::splat(radius.max(0.0) as i32);
+                Region::cuboid(center - extent, center + extent)
+                    .blocks()
+                    .into_iter()
+                    .filter(|block| (*block - center).as_vec3().length() <= radius)
+                    .collect()
+            }
+            Region::Line { from, to } => {
+                let delta = to.as_i64vec3() - from.as_i64vec3();
+                let steps = delta.abs().max_element();
+                if steps == 0 {
+                    return vec![from];
+                }
+                if steps as u64 >= MAX_REGION_BLOCKS {
+                    return vec![];
+                }
+                (0..=steps)
+                    .map(|step| {
+                        let t = step as f32 / steps as f32;
+                        from + (delta.as_vec3() * t).round().as_ivec3()
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Blocks copied from the world with [`_Blocks::copy_region`], to be pasted with
+/// [`_BlocksMut::paste`].
+#[derive(Clone, Debug, Default)]
+pub struct BlockClipboard {
+    /// The blocks (including air), relative to the origin they were copied from.
+    pub blocks: Vec<(IVec3, BlockId)>,
+}
+
+impl BlockClipboard {
+    /// Leave out the air, so pasting doesn't clear the blocks around the pasted blocks.
+    pub fn without_air(mut self) -> Self {
+        self.blocks.retain(|(_, block_id)| *block_id != 0);
+        self
+    }
+}
+
+/// Which events are sent for the blocks changed by a region edit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RegionUpdates {
+    /// A [`BlockWorldUpdateEvent`] for every block that was removed or placed, as if each block
+    /// was set on its own. The triggers of the blocks run as usual.
+    #[default]
+    PerBlock,
+    /// A single [`BlockRegionUpdateEvent`] for the whole edit.
+    Coalesced,
+    /// No events at all, nothing reacts to the changes.
+    Suppressed,
+}
+
+/// Sent once for each region edit made with [`RegionUpdates::Coalesced`].
+#[derive(Event, Clone, Debug)]
+pub struct BlockRegionUpdateEvent {
+    /// The chunks whose blocks changed.
+    pub chunks: Vec<ChunkCords>,
+    /// The number of blocks that changed.
+    pub changed_blocks: usize,
+}
+
+/// An edit of a region of the world, sent by [`_BlocksMut`]'s region methods.
+#[derive(Clone, Debug)]
+pub enum RegionEdit {
+    /// Set all of the blocks of the region.
+    Fill { region: Region, block_id: BlockId },
+    /// Set the blocks of the region that are `from` to `to`.
+    Replace {
+        region: Region,
+        from: BlockId,
+        to: BlockId,
+    },
+    /// Set the blocks of the clipboard, relative to `at`.
+    Paste {
+        clipboard: BlockClipboard,
+        at: IVec3,
+    },
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct GlobalRegionEdit {
+    pub edit: RegionEdit,
+    pub updates: RegionUpdates,
+}
+
+impl<'w, 's, const N: usize> _Blocks<'w, 's, N> {
+    /// Copy the blocks of the region, relative to `origin`. The blocks in chunks that aren't
+    /// loaded are left out.
+    pub fn copy_region(&self, region: Region, origin: IVec3) -> BlockClipboard {
+        let dims = unsafe { PLACEHOLDER_DIMS };
+        let blocks = region
+            .blocks()
+            .into_iter()
+            .filter_map(|block| {
+                let global_pos = block_to_global_block_pos(block, dims);
+                self.get_block_id_at(global_pos.cords, global_pos.pos)
+                    .map(|block_id| (block - origin, block_id))
+            })
+            .collect();
+        BlockClipboard { blocks }
+    }
+}
+
+impl<'w, 's, const N: usize> _BlocksMut<'w, 's, N> {
+    /// Set all of the blocks of the region (in chunks that are loaded) to the block.
+    pub fn fill_region(&mut self, region: Region, block_id: BlockId, updates: RegionUpdates) {
+        self.edit_region(RegionEdit::Fill { region, block_id }, updates);
+    }
+
+    /// Set the blocks of the region that are `from` to `to`.
+    pub fn replace_in_region(
+        &mut self,
+        region: Region,
+        from: BlockId,
+        to: BlockId,
+        updates: RegionUpdates,
+    ) {
+        self.edit_region(RegionEdit::Replace { region, from, to }, updates);
+    }
+
+    /// Paste the blocks of the clipboard, relative to `at`.
+    pub fn paste(&mut self, clipboard: BlockClipboard, at: IVec3, updates: RegionUpdates) {
+        self.edit_region(RegionEdit::Paste { clipboard, at }, updates);
+    }
+
+    pub fn edit_region(&mut self, edit: RegionEdit, updates: RegionUpdates) {
+        self.global_region_edit_sender
+            .send(GlobalRegionEdit { edit, updates });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn global_region_editor<const N: usize>(
+    mut region_edits: EventReader<GlobalRegionEdit>,
+    mut blocks: _Blocks<N>,
+    mut fluid_levels: Query<&mut ChunkFluidLevels>,
+    mut commands: Commands,
+    mut light_updates: ResMut<LightUpdates>,
+    block_entity_spawners: Res<BlockEntitySpawners>,
+    mut block_world_update_sender: EventWriter<BlockWorldUpdateEvent>,
+    mut block_region_update_sender: EventWriter<BlockRegionUpdateEvent>,
+) {
+    let dims = unsafe { PLACEHOLDER_DIMS };
+    for GlobalRegionEdit { edit, updates } in region_edits.read() {
+        let (blocks_to_set, replaces): (Vec<(IVec3, BlockId)>, _) = match edit {
+            RegionEdit::Fill { region, block_id } => (
+                region
+                    .blocks()
+                    .into_iter()
+                    .map(|block| (block, *block_id))
+                    .collect(),
+                None,
+            ),
+            RegionEdit::Replace { region, from, to } => (
+                region
+                    .blocks()
+                    .into_iter()
+                    .map(|block| (block, *to))
+                    .collect(),
+                Some(*from),
+            ),
+            RegionEdit::Paste { clipboard, at } => (
+                clipboard
+                    .blocks
+                    .iter()
+                    .map(|(offset, block_id)| (*at + *offset, *block_id))
+                    .collect(),
+                None,
+            ),
+        };
+        let mut blocks_by_chunk: HashMap<ChunkCords, Vec<(BlockPos, BlockId)>> = HashMap::new();
+        for (block, block_id) in blocks_to_set {
+            let global_pos = block_to_global_block_pos(block, dims);
+            blocks_by_chunk
+                .entry(global_pos.cords)
+                .or_default()
+                .push((global_pos.pos, block_id));
+        }
+
+        let mut changed_chunks = Vec::new();
+        let mut changed_blocks = 0;
+        let mut chunks_to_remesh = HashSet::new();
+        for (chunk_cords, blocks_to_set) in blocks_by_chunk {
+            let Some(chunk_entity) = blocks.chunk_map.get_chunk(chunk_cords) else {
+                continue;
+            };
+            let Ok((mut chunk_grid, _)) = blocks.chunks_query.get_mut(chunk_entity) else {
+                continue;
+            };
+            // (position, old block, new block)
+            let mut changes = Vec::new();
+            for (block_pos, block_id) in blocks_to_set {
+                let Some(old_block_id) = chunk_grid.0.get_block(block_pos) else {
+                    continue;
+                };
+                if old_block_id == block_id || replaces.is_some_and(|from| from != old_block_id) {
+                    continue;
+                }
+                let _ = chunk_grid.0.set_block(block_id, block_pos);
+                changes.push((block_pos, old_block_id, block_id));
+            }
+            if changes.is_empty() {
+                continue;
+            }
+
+            let mut block_states = blocks.block_states_query.get_mut(chunk_entity).ok();
+            let mut block_entities = blocks.block_entities_query.get_mut(chunk_entity).ok();
+            let mut chunk_fluid_levels = fluid_levels.get_mut(chunk_entity).ok();
+            for &(block_pos, old_block_id, block_id) in &changes {
+                if let Some(block_states) = block_states.as_mut() {
+                    block_states.clear(block_pos);
+                }
+                if let Some(chunk_fluid_levels) = chunk_fluid_levels.as_mut() {
+                    chunk_fluid_levels.0.remove(&block_pos);
+                }
+                if let Some(block_entities) = block_entities.as_mut() {
+                    if let Some(block_entity) = block_entities.0.remove(&block_pos) {
+                        commands.entity(block_entity).despawn_recursive();
+                    }
+                    block_entity_spawners.spawn(
+                        &mut commands,
+                        chunk_entity,
+                        block_entities,
+                        BlockEntity {
+                            block_id,
+                            chunk_cords,
+                            block_pos,
+                        },
+                    );
+                }
+                light_updates.push_block(chunk_cords, block_pos);
+                // The faces of the neighboring chunk that touch the block might have changed too.
+                for face in FACES {
+                    if is_block_pos_on_edge(block_pos, face, dims) {
+                        chunks_to_remesh.insert(adj_chunk(chunk_cords, face));
+                    }
+                }
+
+                if *updates == RegionUpdates::PerBlock {
+                    let block_updates = [
+                        (old_block_id != 0).then_some(BLOCK_REMOVED),
+                        (block_id != 0).then_some(BLOCK_PLACED),
+                    ];
+                    for block_update in block_updates.into_iter().flatten() {
+                        block_world_update_sender.send(BlockWorldUpdateEvent {
+                            block_pos,
+                            chunk_cords,
+                            block_update: BlockUpdate::Pure(block_update),
+                        });
+                    }
+                }
+            }
+            commands
+                .entity(chunk_entity)
+                .insert((ModifiedChunk, ToRemesh));
+            changed_chunks.push(chunk_cords);
+            changed_blocks += changes.len();
+        }
+
+        for chunk_cords in chunks_to_remesh {
+            if changed_chunks.contains(&chunk_cords) {
+                continue;
+            }
+            if let Some(chunk_entity) = blocks.chunk_map.get_chunk(chunk_cords) {
+                commands.entity(chunk_entity).insert(ToRemesh);
+            }
+        }
+        if *updates == RegionUpdates::Coalesced && !changed_chunks.is_empty() {
+            block_region_update_sender.send(BlockRegionUpdateEvent {
+                chunks: changed_chunks,
+                changed_blocks,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::components::{
+        ChildMeshChunks, ChunkBlockEntities, ChunkBlockStates, ChunkGrid,
+    };
+    use crate::chunk::resources::ChunkMap;
+    use bevy_ecs::system::RunSystemOnce;
+    use blockworld::BlockIdtoEnt;
+    use moxi_utils::prelude::PalettedGrid;
+
+    const N: usize = 4096;
+
+    /// A world with two empty chunks next to each other along x, and the chunk above the first
+    /// one.
+    fn region_world() -> (bevy_app::App, [Entity; 3]) {
+        let mut app = bevy_app::App::new();
+        app.add_event::<GlobalRegionEdit>()
+            .add_event::<BlockWorldUpdateEvent>()
+            .add_event::<BlockRegionUpdateEvent>();
+        let world = &mut app.world;
+        world.init_resource::<BlockIdtoEnt>();
+        world.init_resource::<BlockEntitySpawners>();
+        world.init_resource::<LightUpdates>();
+
+        let dims = unsafe { PLACEHOLDER_DIMS };
+        let mut chunk_map = ChunkMap::default();
+        let chunks = [[0, 0, 0], [1, 0, 0], [0, 1, 0]].map(|chunk_cords| {
+            let chunk_entity = world
+                .spawn((
+                    ChunkGrid::<N>(PalettedGrid::new(0, dims)),
+                    ChildMeshChunks {
+                        cube_mesh_chunk: Entity::PLACEHOLDER,
+                        translucent_mesh_chunk: Entity::PLACEHOLDER,
+                        xsprite_mesh_chunk: Entity::PLACEHOLDER,
+                        custom_mesh_chunk: Entity::PLACEHOLDER,
+                        fluid_mesh_chunk: Entity::PLACEHOLDER,
+                    },
+                    ChunkBlockStates::default(),
+                    ChunkBlockEntities::default(),
+                    ChunkFluidLevels::default(),
+                ))
+                .id();
+            chunk_map.insert_chunk(chunk_cords.into(), chunk_entity);
+            chunk_entity
+        });
+        world.insert_resource(chunk_map);
+        (app, chunks)
+    }
+
+    /// Run the region editor on the edit, returns the block and region updates it sent.
+    fn edit(
+        world: &mut World,
+        edit: RegionEdit,
+        updates: RegionUpdates,
+    ) -> (Vec<BlockWorldUpdateEvent>, Vec<BlockRegionUpdateEvent>) {
+        world.send_event(GlobalRegionEdit { edit, updates });
+        world.run_system_once(global_region_editor::<N>);
+        let block_updates = world
+            .resource_mut::<Events<BlockWorldUpdateEvent>>()
+            .drain()
+            .collect();
+        let region_updates = world
+            .resource_mut::<Events<BlockRegionUpdateEvent>>()
+            .drain()
+            .collect();
+        world.resource_mut::<Events<GlobalRegionEdit>>().clear();
+        (block_updates, region_updates)
+    }
+
+    fn block_at(world: &World, chunk_entity: Entity, block_pos: [u32; 3]) -> Option<BlockId> {
+        world
+            .get::<ChunkGrid<N>>(chunk_entity)?
+            .0
+            .get_block(block_pos.into())
+    }
+
+    /// Clear the markers the edits leave on the chunks.
+    fn clear_markers(world: &mut World, chunks: [Entity; 3]) {
+        for chunk_entity in chunks {
+            world
+                .entity_mut(chunk_entity)
+                .remove::<(ModifiedChunk, ToRemesh)>();
+        }
+    }
+
+    #[test]
+    fn test_region_editor_batching() {
+        let (mut app, chunks) = region_world();
+        let world = &mut app.world;
+
+        // A row of blocks across the border of the first two chunks, sent as a single event.
+        let row = Region::cuboid(IVec3::new(14, 0, 0), IVec3::new(17, 0, 0));
+        let (block_updates, region_updates) = edit(
+            world,
+            RegionEdit::Fill {
+                region: row,
+                block_id: 1,
+            },
+            RegionUpdates::Coalesced,
+        );
+        assert!(block_updates.is_empty());
+        assert_eq!(region_updates.len(), 1);
+        assert_eq!(region_updates[0].changed_blocks, 4);
+        let mut changed_chunks = region_updates[0].chunks.clone();
+        changed_chunks.sort_by_key(|chunk_cords| chunk_cords.x);
+        assert_eq!(changed_chunks, vec![[0, 0, 0].into(), [1, 0, 0].into()]);
+        for x in 14..16 {
+            assert_eq!(block_at(world, chunks[0], [x, 0, 0]), Some(1));
+        }
+        for x in 0..2 {
+            assert_eq!(block_at(world, chunks[1], [x, 0, 0]), Some(1));
+        }
+        assert_eq!(block_at(world, chunks[0], [13, 0, 0]), Some(0));
+        for chunk_entity in &chunks[..2] {
+            assert!(world.get::<ModifiedChunk>(*chunk_entity).is_some());
+            assert!(world.get::<ToRemesh>(*chunk_entity).is_some());
+        }
+        // The row isn't on the top edge of its chunks, so the chunk above isn't meshed again.
+        assert!(world.get::<ModifiedChunk>(chunks[2]).is_none());
+        assert!(world.get::<ToRemesh>(chunks[2]).is_none());
+        clear_markers(world, chunks);
+
+        // The top of the row touches the chunk above.
+        let (_, region_updates) = edit(
+            world,
+            RegionEdit::Fill {
+                region: Region::cuboid(IVec3::new(1, 15, 0), IVec3::new(1, 15, 0)),
+                block_id: 1,
+            },
+            RegionUpdates::Coalesced,
+        );
+        assert_eq!(region_updates[0].chunks, vec![[0, 0, 0].into()]);
+        assert!(world.get::<ModifiedChunk>(chunks[2]).is_none());
+        assert!(world.get::<ToRemesh>(chunks[2]).is_some());
+        clear_markers(world, chunks);
+
+        // Setting the blocks to what they already are changes nothing.
+        let (block_updates, region_updates) = edit(
+            world,
+            RegionEdit::Fill {
+                region: row,
+                block_id: 1,
+            },
+            RegionUpdates::Coalesced,
+        );
+        assert!(block_updates.is_empty());
+        assert!(region_updates.is_empty());
+        assert!(chunks
+            .iter()
+            .all(|chunk_entity| world.get::<ToRemesh>(*chunk_entity).is_none()));
+    }
+
+    #[test]
+    fn test_region_editor_replace() {
+        let (mut app, chunks) = region_world();
+        let world = &mut app.world;
+        edit(
+            world,
+            RegionEdit::Fill {
+                region: Region::cuboid(IVec3::new(0, 0, 0), IVec3::new(1, 0, 0)),
+                block_id: 1,
+            },
+            RegionUpdates::Suppressed,
+        );
+        edit(
+            world,
+            RegionEdit::Fill {
+                region: Region::cuboid(IVec3::new(2, 0, 0), IVec3::new(3, 0, 0)),
+                block_id: 2,
+            },
+            RegionUpdates::Suppressed,
+        );
+        // The state of a replaced block doesn't carry over to the new block.
+        world
+            .get_mut::<ChunkFluidLevels>(chunks[0])
+            .unwrap()
+            .set_level([0, 0, 0].into(), 3);
+
+        let (_, region_updates) = edit(
+            world,
+            RegionEdit::Replace {
+                region: Region::cuboid(IVec3::new(0, 0, 0), IVec3::new(4, 0, 0)),
+                from: 1,
+                to: 3,
+            },
+            RegionUpdates::Coalesced,
+        );
+        assert_eq!(region_updates[0].changed_blocks, 2);
+        let row: Vec<_> = (0..5)
+            .map(|x| block_at(world, chunks[0], [x, 0, 0]).unwrap())
+            .collect();
+        assert_eq!(row, vec![3, 3, 2, 2, 0]);
+        assert!(world
+            .get::<ChunkFluidLevels>(chunks[0])
+            .unwrap()
+            .0
+            .is_empty());
+    }
+
+    #[test]
+    fn test_region_editor_updates() {
+        let (mut app, chunks) = region_world();
+        let world = &mut app.world;
+        let region = Region::cuboid(IVec3::new(0, 0, 0), IVec3::new(2, 0, 0));
+
+        // Nothing is sent for suppressed edits, but the blocks are still set.
+        let (block_updates, region_updates) = edit(
+            world,
+            RegionEdit::Fill {
+                region,
+                block_id: 1,
+            },
+            RegionUpdates::Suppressed,
+        );
+        assert!(block_updates.is_empty());
+        assert!(region_updates.is_empty());
+        assert_eq!(block_at(world, chunks[0], [2, 0, 0]), Some(1));
+        assert!(world.get::<ModifiedChunk>(chunks[0]).is_some());
+
+        // Replacing a block sends its removal and its placement, clearing a block only its removal.
+        let (block_updates, region_updates) = edit(
+            world,
+            RegionEdit::Paste {
+                clipboard: BlockClipboard {
+                    blocks: vec![(IVec3::ZERO, 2), (IVec3::X, 0)],
+                },
+                at: IVec3::ZERO,
+            },
+            RegionUpdates::PerBlock,
+        );
+        assert!(region_updates.is_empty());
+        let mut updates: Vec<_> = block_updates
+            .iter()
+            .map(|update| {
+                let BlockUpdate::Pure(update_type) = update.block_update() else {
+                    panic!("Region edits only send pure updates");
+                };
+                (update.block_pos().x, update_type == BLOCK_PLACED)
+            })
+            .collect();
+        updates.sort();
+        assert_eq!(updates, vec![(0, false), (0, true), (1, false)]);
+    }
+
+    #[test]
+    fn test_region_blocks() {
+        let cuboid = Region::cuboid(IVec3::new(1, 0, -1), IVec3::new(-1, 1, 1));
+        assert_eq!(cuboid.blocks().len(), 3 * 2 * 3);
+        let huge_cuboid = Region::cuboid(IVec3::splat(i32::MIN), IVec3::splat(i32::MAX));
+        assert!(huge_cuboid.blocks().is_empty());
+        let largest_cuboid = Region::cuboid(IVec3::ZERO, IVec3::new(1023, 1023, 3));
+        assert_eq!(largest_cuboid.blocks().len() as u64, MAX_REGION_BLOCKS);
+        assert!(Region::cuboid(IVec3::ZERO, IVec3::new(1023, 1023, 4))
+            .blocks()
+            .is_empty());
+
+        let sphere = Region::sphere(IVec3::ZERO, 1.0).blocks();
+        assert_eq!(sphere.len(), 7);
+        assert!(!sphere.contains(&IVec3::ONE));
+        assert!(Region::sphere(IVec3::ZERO, f32::NAN).blocks().is_empty());
+        assert!(Region::sphere(IVec3::ZERO, f32::INFINITY)
+            .blocks()
+            .is_empty());
+        assert!(Region::sphere(IVec3::ZERO, -1.0).blocks().is_empty());
+        let huge_sphere = Region::sphere(IVec3::new(5, 0, 0), 1e12).blocks();
+        assert!(huge_sphere.contains(&IVec3::new(5 + MAX_SPHERE_RADIUS as i32, 0, 0)));
+        assert!(!huge_sphere.contains(&IVec3::new(6 + MAX_SPHERE_RADIUS as i32, 0, 0)));
+
+        let line = Region::line(IVec3::ZERO, IVec3::new(4, 2, 0)).blocks();
+        assert_eq!(line.len(), 5);
+        assert_eq!(line.first(), Some(&IVec3::ZERO));
+        assert_eq!(line.last(), Some(&IVec3::new(4, 2, 0)));
+        assert_eq!(
+            Region::line(IVec3::ONE, IVec3::ONE).blocks(),
+            vec![IVec3::ONE]
+        );
+        assert!(Region::line(IVec3::splat(i32::MIN), IVec3::splat(i32::MAX))
+            .blocks()
+            .is_empty());
+    }
+}
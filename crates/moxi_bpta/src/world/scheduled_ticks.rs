@@ -0,0 +1,211 @@
+//! Updates scheduled for a block at a later tick (crops growing, timers, falling blocks, ...).
+
+use crate::chunk::resources::ChunkMap;
+use crate::*;
+use moxi_utils::prelude::{BlockGlobalPos, BlockPos, ChunkCords};
+use std::collections::{HashMap, HashSet};
+
+/// An update scheduled for a block, `delay` ticks from now.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScheduledTick {
+    pub block_pos: BlockPos,
+    pub update: BlockUpdateType,
+    pub delay: u64,
+}
+
+/// Resource that holds the updates scheduled for blocks. The ticks advance every
+/// [`FixedUpdate`](bevy_app::FixedUpdate), and each update that is due is sent as a pure
+/// [`BlockWorldUpdateEvent`], to whichever block is at the position by then. Updates scheduled in
+/// a chunk that isn't loaded wait for it to be loaded. When a chunk is unloaded its updates are
+/// saved with it if there is a [`ChunkStorage`](`crate::prelude::ChunkStorage`), and dropped
+/// otherwise.
+#[derive(Resource, Default)]
+pub struct BlockTicks {
+    tick: u64,
+    /// The updates scheduled in each chunk, and the tick they are due at.
+    scheduled: HashMap<ChunkCords, Vec<(BlockPos, BlockUpdateType, u64)>>,
+    /// The loaded chunks that had ticks in the storage. Their ticks are saved when they are
+    /// unloaded even if none are left, so the stored ticks aren't loaded again.
+    stored: HashSet<ChunkCords>,
+}
+
+impl BlockTicks {
+    /// The number of ticks since the app started.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Schedule the update for the block, `delay` ticks from now (at least one). Scheduling an
+    /// update the block already has scheduled does nothing.
+    pub fn schedule(&mut self, global_pos: BlockGlobalPos, update: BlockUpdateType, delay: u64) {
+        let due_tick = self.tick + delay.max(1);
+        let chunk_ticks = self.scheduled.entry(global_pos.cords).or_default();
+        if !chunk_ticks
+            .iter()
+            .any(|(block_pos, u, _)| *block_pos == global_pos.pos && *u == update)
+        {
+            chunk_ticks.push((global_pos.pos, update, due_tick));
+        }
+    }
+
+    pub fn is_scheduled(&self, global_pos: BlockGlobalPos, update: BlockUpdateType) -> bool {
+        self.scheduled.get(&global_pos.cords).is_some_and(|ticks| {
+            ticks
+                .iter()
+                .any(|(block_pos, u, _)| *block_pos == global_pos.pos && *u == update)
+        })
+    }
+
+    /// Cancel the update scheduled for the block, returns whether it was scheduled.
+    pub fn cancel(&mut self, global_pos: BlockGlobalPos, update: BlockUpdateType) -> bool {
+        let Some(chunk_ticks) = self.scheduled.get_mut(&global_pos.cords) else {
+            return false;
+        };
+        let len = chunk_ticks.len();
+        chunk_ticks.retain(|(block_pos, u, _)| !(*block_pos == global_pos.pos && *u == update));
+        len != chunk_ticks.len()
+    }
+
+    /// Advance by one tick, and take the updates that are due in the chunks that are loaded.
+    pub(crate) fn advance(
+        &mut self,
+        is_loaded: impl Fn(ChunkCords) -> bool,
+    ) -> Vec<BlockWorldUpdateEvent> {
+        self.tick += 1;
+        let tick = self.tick;
+        let mut due = Vec::new();
+        for (chunk_cords, chunk_ticks) in self.scheduled.iter_mut() {
+            if !is_loaded(*chunk_cords) {
+                continue;
+            }
+            chunk_ticks.retain(|(block_pos, update, due_tick)| {
+                if *due_tick > tick {
+                    return true;
+                }
+                due.push((*due_tick, *chunk_cords, *block_pos, *update));
+                false
+            });
+        }
+        self.scheduled
+            .retain(|_, chunk_ticks| !chunk_ticks.is_empty());
+        // The updates that were due first are sent first.
+        due.sort_by_key(|(due_tick, ..)| *due_tick);
+        due.into_iter()
+            .map(|(_, chunk_cords, block_pos, update)| {
+                BlockWorldUpdateEvent::new(block_pos, chunk_cords, BlockUpdate::Pure(update))
+            })
+            .collect()
+    }
+
+    /// The ticks of the chunk, with their delays from now.
+    pub(crate) fn chunk_ticks(&self, chunk_cords: ChunkCords) -> Vec<ScheduledTick> {
+        self.scheduled
+            .get(&chunk_cords)
+            .into_iter()
+            .flatten()
+            .map(|(block_pos, update, due_tick)| ScheduledTick {
+                block_pos: *block_pos,
+                update: *update,
+                delay: due_tick.saturating_sub(self.tick),
+            })
+            .collect()
+    }
+
+    /// The chunks whose ticks have to be saved: the chunks with ticks, and the loaded chunks
+    /// that had ticks in the storage.
+    pub(crate) fn chunks_to_save(&self) -> impl Iterator<Item = ChunkCords> + '_ {
+        self.scheduled
+            .keys()
+            .chain(
+                self.stored
+                    .iter()
+                    .filter(|c| !self.scheduled.contains_key(c)),
+            )
+            .copied()
+    }
+
+    /// Remove the ticks of the chunk. Returns the ticks to save, `None` if nothing has to be
+    /// saved.
+    pub(crate) fn unload(&mut self, chunk_cords: ChunkCords) -> Option<Vec<ScheduledTick>> {
+        let ticks = self.chunk_ticks(chunk_cords);
+        self.scheduled.remove(&chunk_cords);
+        let was_stored = self.stored.remove(&chunk_cords);
+        (was_stored || !ticks.is_empty()).then_some(ticks)
+    }
+
+    /// Schedule the ticks loaded with the chunk.
+    pub(crate) fn restore(&mut self, chunk_cords: ChunkCords, ticks: Vec<ScheduledTick>) {
+        if ticks.is_empty() {
+            return;
+        }
+        self.stored.insert(chunk_cords);
+        for tick in ticks {
+            self.schedule(
+                BlockGlobalPos::new(tick.block_pos, chunk_cords),
+                tick.update,
+                tick.delay,
+            );
+        }
+    }
+}
+
+/// Advance the scheduled ticks, and send the updates that are due.
+pub(crate) fn tick_scheduled_blocks(
+    mut block_ticks: ResMut<BlockTicks>,
+    chunk_map: Res<ChunkMap>,
+    mut block_world_update_sender: EventWriter<BlockWorldUpdateEvent>,
+) {
+    let due = block_ticks.advance(|chunk_cords| chunk_map.get_chunk(chunk_cords).is_some());
+    block_world_update_sender.send_batch(due);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_ticks() {
+        const GROW: BlockUpdateType = BlockUpdateType::from_u128(1);
+        const DECAY: BlockUpdateType = BlockUpdateType::from_u128(2);
+        let global_pos = |x, cords: [i32; 3]| BlockGlobalPos::new([x, 0, 0].into(), cords.into());
+        let loaded = |chunk_cords: ChunkCords| chunk_cords.x == 0;
+
+        let mut block_ticks = BlockTicks::default();
+        block_ticks.schedule(global_pos(0, [0, 0, 0]), GROW, 3);
+        block_ticks.schedule(global_pos(1, [0, 0, 0]), DECAY, 1);
+        // Already scheduled.
+        block_ticks.schedule(global_pos(0, [0, 0, 0]), GROW, 1);
+        block_ticks.schedule(global_pos(2, [0, 0, 0]), DECAY, 2);
+        assert!(block_ticks.cancel(global_pos(2, [0, 0, 0]), DECAY));
+        assert!(!block_ticks.cancel(global_pos(2, [0, 0, 0]), DECAY));
+        // The chunk isn't loaded, the tick waits for it.
+        block_ticks.schedule(global_pos(0, [1, 0, 0]), GROW, 1);
+
+        let updates = block_ticks.advance(loaded);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].block_pos(), [1, 0, 0].into());
+        assert!(updates[0]
+            .block_update()
+            .is_pure_and(|update| update == DECAY));
+        assert!(block_ticks.advance(loaded).is_empty());
+        assert_eq!(block_ticks.advance(loaded).len(), 1);
+        assert!(!block_ticks.is_scheduled(global_pos(0, [0, 0, 0]), GROW));
+        assert!(block_ticks.is_scheduled(global_pos(0, [1, 0, 0]), GROW));
+
+        // Unloading and restoring a chunk keeps the delays of its ticks.
+        block_ticks.schedule(global_pos(3, [0, 0, 0]), GROW, 5);
+        let ticks = block_ticks.unload([0, 0, 0].into()).unwrap();
+        assert_eq!(ticks.len(), 1);
+        assert_eq!(ticks[0].delay, 5);
+        assert!(block_ticks.unload([0, 0, 0].into()).is_none());
+        block_ticks.advance(loaded);
+        block_ticks.restore([0, 0, 0].into(), ticks);
+        assert_eq!(block_ticks.chunk_ticks([0, 0, 0].into())[0].delay, 5);
+        // The restored ticks are saved again once they are done, to remove them from the storage.
+        for _ in 0..5 {
+            block_ticks.advance(loaded);
+        }
+        assert_eq!(block_ticks.unload([0, 0, 0].into()), Some(vec![]));
+        assert!(block_ticks.unload([0, 0, 0].into()).is_none());
+    }
+}
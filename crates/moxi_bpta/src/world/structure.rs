@@ -0,0 +1,419 @@
+//! Structures are volumes of blocks placed into the world as a whole (trees, houses, ...), that
+//! can span multiple chunks. They can be placed while the world is generated, through
+//! [`StructurePlacements`], or pasted into the loaded chunks with [`_BlocksMut::place_structure`].
+//!
+//! Structure file layout:
+//! - The version of the format (u8), [`STRUCTURE_FORMAT_VERSION`].
+//! - The amount of blocks (u32), little-endian.
+//! - The blocks: their offsets (3 x i32) and [`BlockId`]s, little-endian.
+
+use crate::chunk::resources::{ChunkMap, VerticalChunkRange};
+use crate::*;
+use bevy_math::IVec3;
+use blockworld::PLACEHOLDER_DIMS;
+use moxi_utils::prelude::{block_to_global_block_pos, BlockGrid, BlockId, BlockPos, ChunkCords};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// The version of the format structures are saved with.
+pub const STRUCTURE_FORMAT_VERSION: u8 = 0;
+const BLOCK_ID_SIZE: usize = std::mem::size_of::<BlockId>();
+const BLOCK_ENTRY_SIZE: usize = 3 * 4 + BLOCK_ID_SIZE;
+
+/// A sparse volume of blocks. Only the blocks of the structure are set when it's placed, so air
+/// has to be part of the structure to clear the blocks it's placed over.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Structure {
+    /// The blocks, relative to the origin of the structure.
+    pub blocks: Vec<(IVec3, BlockId)>,
+}
+
+impl Structure {
+    pub fn new(blocks: Vec<(IVec3, BlockId)>) -> Self {
+        Self { blocks }
+    }
+
+    /// The smallest and largest offsets of the blocks (inclusive), `None` if the structure is
+    /// empty.
+    pub fn bounds(&self) -> Option<(IVec3, IVec3)> {
+        let mut offsets = self.blocks.iter().map(|(offset, _)| *offset);
+        let first = offsets.next()?;
+        Some(offsets.fold((first, first), |(min, max), offset| {
+            (min.min(offset), max.max(offset))
+        }))
+    }
+
+    /// Load a structure saved with [`Structure::save`].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::decode(&std::fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.encode())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(1 + 4 + self.blocks.len() * BLOCK_ENTRY_SIZE);
+        data.push(STRUCTURE_FORMAT_VERSION);
+        data.extend_from_slice(&(self.blocks.len() as u32).to_le_bytes());
+        for (offset, block_id) in &self.blocks {
+            for c in offset.to_array() {
+                data.extend_from_slice(&c.to_le_bytes());
+            }
+            data.extend_from_slice(&block_id.to_le_bytes());
+        }
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 1 + 4 {
+            return Err(invalid_data("Structure is too short"));
+        }
+        if data[0] != STRUCTURE_FORMAT_VERSION {
+            return Err(invalid_data("Unknown structure format version"));
+        }
+        let len = u32::from_le_bytes(data[1..5].try_into().unwrap()) as usize;
+        let entries = &data[5..];
+        if entries.len() != len * BLOCK_ENTRY_SIZE {
+            return Err(invalid_data("Structure has the wrong amount of blocks"));
+        }
+        let i32_at = |entry: &[u8], i: usize| {
+            i32::from_le_bytes(entry[i * 4..(i + 1) * 4].try_into().unwrap())
+        };
+        let blocks = entries
+            .chunks(BLOCK_ENTRY_SIZE)
+            .map(|entry| {
+                let offset = IVec3::new(i32_at(entry, 0), i32_at(entry, 1), i32_at(entry, 2));
+                let block_id = BlockId::from_le_bytes(entry[12..].try_into().unwrap());
+                (offset, block_id)
+            })
+            .collect();
+        Ok(Self { blocks })
+    }
+
+    /// The blocks of the structure placed at `at`, split by the chunks they are in.
+    fn blocks_by_chunk(&self, at: IVec3) -> HashMap<ChunkCords, Vec<(BlockPos, BlockId)>> {
+        let dims = unsafe { PLACEHOLDER_DIMS };
+        let mut blocks_by_chunk: HashMap<ChunkCords, Vec<(BlockPos, BlockId)>> = HashMap::new();
+        for (offset, block_id) in &self.blocks {
+            let global_pos = block_to_global_block_pos(at + *offset, dims);
+            blocks_by_chunk
+                .entry(global_pos.cords)
+                .or_default()
+                .push((global_pos.pos, *block_id));
+        }
+        blocks_by_chunk
+    }
+}
+
+impl From<BlockClipboard> for Structure {
+    fn from(clipboard: BlockClipboard) -> Self {
+        Self::new(clipboard.blocks)
+    }
+}
+
+impl From<Structure> for BlockClipboard {
+    fn from(structure: Structure) -> Self {
+        Self {
+            blocks: structure.blocks,
+        }
+    }
+}
+
+/// Resource that holds the blocks of the structures placed during generation, until the chunks
+/// they are in are built. The blocks of a chunk are set right after its grid is built (or loaded),
+/// and the blocks of chunks that are already loaded are pasted into them. The pending blocks
+/// aren't saved, they are lost if the app exits before their chunks are built. The blocks of the
+/// chunks that are outside of the [`VerticalChunkRange`] are dropped, and so are the oldest ones
+/// once more than [`StructurePlacements::max_pending_chunks`] chunks have pending blocks.
+///
+/// The resource is a shared handle: clone it (from the world, once the plugin is added) into the
+/// [`ChunkBuilder`](`crate::prelude::ChunkBuilder`), so the builder can place structures that
+/// spill out of the chunk it builds.
+#[derive(Resource, Clone)]
+pub struct StructurePlacements {
+    pending: Arc<Mutex<PendingBlocks>>,
+    /// The maximum amount of chunks with pending blocks.
+    pub max_pending_chunks: usize,
+}
+
+/// The default maximum amount of chunks with pending structure blocks.
+pub const DEFAULT_MAX_PENDING_STRUCTURE_CHUNKS: usize = 4096;
+
+#[derive(Default)]
+struct PendingBlocks {
+    /// The amount of structures placed so far, orders the chunks by their first placement.
+    placements: u64,
+    chunks: HashMap<ChunkCords, PendingChunk>,
+}
+
+struct PendingChunk {
+    /// The placement the chunk got its first pending block from.
+    first_placement: u64,
+    blocks: Vec<(BlockPos, BlockId)>,
+}
+
+impl Default for StructurePlacements {
+    fn default() -> Self {
+        Self {
+            pending: Default::default(),
+            max_pending_chunks: DEFAULT_MAX_PENDING_STRUCTURE_CHUNKS,
+        }
+    }
+}
+
+impl StructurePlacements {
+    pub fn with_max_pending_chunks(mut self, max_pending_chunks: usize) -> Self {
+        self.max_pending_chunks = max_pending_chunks;
+        self
+    }
+
+    /// Place the structure at `at` (in blocks from the origin of the world). Later placements
+    /// overwrite the blocks of earlier ones.
+    pub fn place(&self, structure: &Structure, at: IVec3) {
+        let mut pending = self.pending.lock().unwrap();
+        let placement = pending.placements;
+        pending.placements += 1;
+        for (chunk_cords, blocks) in structure.blocks_by_chunk(at) {
+            pending
+                .chunks
+                .entry(chunk_cords)
+                .or_insert_with(|| PendingChunk {
+                    first_placement: placement,
+                    blocks: vec![],
+                })
+                .blocks
+                .extend(blocks);
+        }
+    }
+
+    /// Whether there are blocks waiting to be set in the chunk.
+    pub fn is_pending(&self, chunk_cords: ChunkCords) -> bool {
+        self.pending
+            .lock()
+            .unwrap()
+            .chunks
+            .contains_key(&chunk_cords)
+    }
+
+    /// Take the blocks waiting to be set in the chunk.
+    pub fn take(&self, chunk_cords: ChunkCords) -> Vec<(BlockPos, BlockId)> {
+        self.pending
+            .lock()
+            .unwrap()
+            .chunks
+            .remove(&chunk_cords)
+            .map(|pending_chunk| pending_chunk.blocks)
+            .unwrap_or_default()
+    }
+
+    /// Set the blocks waiting to be set in the chunk, returns the positions of the blocks that
+    /// were set. The blocks are kept, the chunk might be cancelled before it's spawned, remove
+    /// them with [`StructurePlacements::remove_applied`] once it is.
+    pub fn apply(
+        &self,
+        chunk_cords: ChunkCords,
+        grid: &mut impl BlockGrid<BlockId>,
+    ) -> Vec<BlockPos> {
+        let pending = self.pending.lock().unwrap();
+        let Some(pending_chunk) = pending.chunks.get(&chunk_cords) else {
+            return vec![];
+        };
+        pending_chunk
+            .blocks
+            .iter()
+            .map(|(block_pos, block_id)| {
+                let _ = grid.set_block(*block_id, *block_pos);
+                *block_pos
+            })
+            .collect()
+    }
+
+    /// Remove the first `applied` blocks waiting to be set in the chunk, the ones that were set
+    /// with [`StructurePlacements::apply`]. The blocks placed since then are kept.
+    pub fn remove_applied(&self, chunk_cords: ChunkCords, applied: usize) {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(pending_chunk) = pending.chunks.get_mut(&chunk_cords) else {
+            return;
+        };
+        pending_chunk
+            .blocks
+            .drain(..applied.min(pending_chunk.blocks.len()));
+        if pending_chunk.blocks.is_empty() {
+            pending.chunks.remove(&chunk_cords);
+        }
+    }
+
+    /// Drop the blocks of the chunks that are never going to be built: the chunks outside of the
+    /// vertical range, and the chunks that got their first block the longest ago while there are
+    /// too many chunks with pending blocks. The chunks `is_building` returns true for keep their
+    /// blocks.
+    fn evict(
+        pending: &mut PendingBlocks,
+        max_pending_chunks: usize,
+        vertical_range: &VerticalChunkRange,
+        is_building: impl Fn(ChunkCords) -> bool,
+    ) {
+        pending
+            .chunks
+            .retain(|chunk_cords, _| vertical_range.contains(chunk_cords.y));
+        if pending.chunks.len() <= max_pending_chunks {
+            return;
+        }
+        let mut evictable: Vec<(u64, ChunkCords)> = pending
+            .chunks
+            .iter()
+            .filter(|(chunk_cords, _)| !is_building(**chunk_cords))
+            .map(|(chunk_cords, pending_chunk)| (pending_chunk.first_placement, *chunk_cords))
+            .collect();
+        evictable.sort_unstable_by_key(|(first_placement, _)| *first_placement);
+        let excess = pending.chunks.len() - max_pending_chunks;
+        for (_, chunk_cords) in evictable.into_iter().take(excess) {
+            pending.chunks.remove(&chunk_cords);
+        }
+    }
+}
+
+impl<'w, 's, const N: usize> _BlocksMut<'w, 's, N> {
+    /// Paste the structure at `at` (in blocks from the origin of the world), into the chunks that
+    /// are loaded.
+    pub fn place_structure(&mut self, structure: &Structure, at: IVec3, updates: RegionUpdates) {
+        self.paste(structure.clone().into(), at, updates);
+    }
+}
+
+/// Paste the pending blocks of the chunks that are already loaded, the chunks that are still
+/// being built take their blocks when they are spawned. The blocks of the chunks that are never
+/// going to be built are dropped.
+pub(crate) fn paste_pending_structure_blocks(
+    structure_placements: Res<StructurePlacements>,
+    chunk_map: Res<ChunkMap>,
+    vertical_range: Res<VerticalChunkRange>,
+    mut global_region_edit_sender: EventWriter<GlobalRegionEdit>,
+) {
+    let dims = unsafe { PLACEHOLDER_DIMS }.as_ivec3();
+    let mut pending = structure_placements.pending.lock().unwrap();
+    if pending.chunks.is_empty() {
+        return;
+    }
+    StructurePlacements::evict(
+        &mut pending,
+        structure_placements.max_pending_chunks,
+        &vertical_range,
+        |chunk_cords| chunk_map.contains_chunk(chunk_cords),
+    );
+    let loaded: Vec<ChunkCords> = pending
+        .chunks
+        .keys()
+        .filter(|chunk_cords| chunk_map.get_chunk(**chunk_cords).is_some())
+        .copied()
+        .collect();
+    for chunk_cords in loaded {
+        let blocks = pending.chunks.remove(&chunk_cords).unwrap().blocks;
+        let clipboard = BlockClipboard {
+            blocks: blocks
+                .into_iter()
+                .map(|(block_pos, block_id)| (block_pos.as_ivec3(), block_id))
+                .collect(),
+        };
+        global_region_edit_sender.send(GlobalRegionEdit {
+            edit: RegionEdit::Paste {
+                clipboard,
+                at: chunk_cords * dims,
+            },
+            updates: RegionUpdates::Coalesced,
+        });
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use moxi_utils::prelude::{Dimensions, Grid};
+
+    #[test]
+    fn test_structure_encoding() {
+        let structure = Structure::new(vec![
+            (IVec3::new(0, 0, 0), 3),
+            (IVec3::new(-1, 2, 5), 1),
+            (IVec3::new(4, -7, 0), 0),
+        ]);
+        assert_eq!(
+            structure.bounds(),
+            Some((IVec3::new(-1, -7, 0), IVec3::new(4, 2, 5)))
+        );
+        assert_eq!(Structure::default().bounds(), None);
+
+        let data = structure.encode();
+        assert_eq!(Structure::decode(&data).unwrap(), structure);
+        assert!(Structure::decode(&data[..data.len() - 1]).is_err());
+        assert!(Structure::decode(&[]).is_err());
+    }
+
+    #[test]
+    fn test_structure_placements() {
+        let placements = StructurePlacements::default();
+        // A pillar that crosses from the chunk below into the chunk above it (16 blocks high).
+        let pillar = Structure::new((0..4).map(|y| (IVec3::new(0, y, 0), 1)).collect());
+        placements.place(&pillar, IVec3::new(3, 14, 15));
+        assert!(placements.is_pending([0, 0, 0].into()));
+        assert!(placements.is_pending([0, 1, 0].into()));
+        assert!(!placements.is_pending([0, 0, 1].into()));
+
+        let dims = Dimensions::new(16, 16, 16);
+        let mut grid = Grid::<BlockId, 4096>::new([0; 4096], dims);
+        assert_eq!(
+            placements.apply([0, 1, 0].into(), &mut grid),
+            vec![[3, 0, 15].into(), [3, 1, 15].into()]
+        );
+        assert_eq!(grid.get_block([3, 0, 15].into()), Some(1));
+        assert_eq!(grid.get_block([3, 1, 15].into()), Some(1));
+        assert_eq!(grid.get_block([3, 2, 15].into()), Some(0));
+        // The blocks stay pending until the chunk is spawned, and the blocks placed in the
+        // meantime are kept.
+        assert!(placements.is_pending([0, 1, 0].into()));
+        placements.place(
+            &Structure::new(vec![(IVec3::ZERO, 2)]),
+            IVec3::new(0, 16, 0),
+        );
+        placements.remove_applied([0, 1, 0].into(), 2);
+        assert_eq!(
+            placements.take([0, 1, 0].into()),
+            vec![([0, 0, 0].into(), 2)]
+        );
+        assert!(placements.apply([0, 1, 0].into(), &mut grid).is_empty());
+        assert_eq!(placements.take([0, 0, 0].into()).len(), 2);
+    }
+
+    #[test]
+    fn test_structure_placements_eviction() {
+        let placements = StructurePlacements::default().with_max_pending_chunks(2);
+        let block = Structure::new(vec![(IVec3::ZERO, 1)]);
+        for x in 0..4 {
+            placements.place(&block, IVec3::new(x * 16, 0, 0));
+        }
+        // Never loaded, the world is a single layer of chunks.
+        placements.place(&block, IVec3::new(0, 16, 0));
+
+        let mut pending = placements.pending.lock().unwrap();
+        // The oldest chunk is being built, so the next oldest one is dropped instead.
+        StructurePlacements::evict(
+            &mut pending,
+            placements.max_pending_chunks,
+            &VerticalChunkRange::default(),
+            |chunk_cords| chunk_cords == [0, 0, 0].into(),
+        );
+        drop(pending);
+        assert!(placements.is_pending([0, 0, 0].into()));
+        assert!(!placements.is_pending([1, 0, 0].into()));
+        assert!(!placements.is_pending([2, 0, 0].into()));
+        assert!(placements.is_pending([3, 0, 0].into()));
+        assert!(!placements.is_pending([0, 1, 0].into()));
+    }
+}
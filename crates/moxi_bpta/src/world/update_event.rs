@@ -79,6 +79,10 @@ impl BlockUpdateType {
         Self { id }
     }
 
+    pub const fn to_u128(self) -> u128 {
+        self.id
+    }
+
     pub const fn is(&self, id: u128) -> bool {
         self.id == id
     }
@@ -86,3 +90,7 @@ impl BlockUpdateType {
 
 pub const BLOCK_REMOVED: BlockUpdateType = BlockUpdateType::from_u128(48124891481412311);
 pub const BLOCK_PLACED: BlockUpdateType = BlockUpdateType::from_u128(48124891481412312);
+/// One of the [`dynamic properties`](`crate::prelude::DynamicProperty`) of the block changed.
+pub const BLOCK_STATE_CHANGED: BlockUpdateType = BlockUpdateType::from_u128(48124891481412314);
+/// The block was picked by the random ticks, see [`RandomTicks`](crate::prelude::RandomTicks).
+pub const RANDOM_TICK: BlockUpdateType = BlockUpdateType::from_u128(48124891481412315);
@@ -18,7 +18,17 @@ macro_rules! config_from_dimensions {
         }
         impl Default for MoxiBptaPlugin {
             fn default() -> Self {
-                Self(moxi::prelude::_MoxiBptaPlugin::<BLOCKS_IN_CHUNK>)
+                Self(moxi::prelude::_MoxiBptaPlugin::<BLOCKS_IN_CHUNK>::default())
+            }
+        }
+        impl MoxiBptaPlugin {
+            /// Configure the chunks (render distance, budget, ...).
+            pub fn with_chunk_plugin(
+                chunk_plugin: moxi::prelude::MoxiChunkPlugin<BLOCKS_IN_CHUNK>,
+            ) -> Self {
+                Self(moxi::prelude::_MoxiBptaPlugin::with_chunk_plugin(
+                    chunk_plugin,
+                ))
             }
         }
         impl std::ops::Deref for MoxiBptaPlugin {
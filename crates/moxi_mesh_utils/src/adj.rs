@@ -6,13 +6,17 @@ use cube::*;
 /// reg: the [`MeshRegistry`]
 /// main_mesh: the [`Mesh`] to change
 /// main_md: the [`metadata`](`CubeMD`) of the mesh to change (must be cube mesh)
+/// main_chunk_grid: the grid of the chunk of the mesh to change
 /// connection_side: from the POV of the main mesh, where is the adjacent mesh?
 /// adjacent_chunk_grid: the grid of the chunk to introduce
-pub fn introduce_adjacent_chunks<B: BlockInGrid, const N: usize>(
+/// adjacent_orientation_at: the orientation of the blocks of the chunk to introduce
+pub fn introduce_adjacent_chunks<B: BlockInGrid>(
     reg: &impl MeshRegistry<B>,
     main_md: &mut CubeMD<B>,
+    main_chunk_grid: &impl BlockGrid<B>,
     connection_side: Face,
-    adjacent_chunk_grid: &Grid<B, N>,
+    adjacent_chunk_grid: &impl BlockGrid<B>,
+    adjacent_orientation_at: impl Fn(BlockPos) -> Orientation,
 ) {
     assert_eq!(
         adjacent_chunk_grid.len(),
@@ -27,10 +31,17 @@ pub fn introduce_adjacent_chunks<B: BlockInGrid, const N: usize>(
         let adj_block_pos = neighbor_across_chunk(block_pos, connection_side, dims).unwrap();
 
         let adj_block = adjacent_chunk_grid.get_block(adj_block_pos).unwrap();
-        if reg.is_cube(&adj_block) {
+        let block = main_chunk_grid.get_block(block_pos).unwrap();
+        if main_md.layer.is_face_culled(
+            reg,
+            &block,
+            connection_side,
+            &adj_block,
+            adjacent_orientation_at(adj_block_pos),
+        ) {
             let mut tmp = [None; 6];
             tmp[connection_side as usize] = Some(adj_block);
-            main_md.log(BlockMeshChange::CullFaces, block_pos, adj_block, tmp)
+            main_md.log(BlockMeshChange::CullFaces, block_pos, block, tmp)
         }
     }
 }
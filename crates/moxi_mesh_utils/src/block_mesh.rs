@@ -1,6 +1,7 @@
 pub use bevy_math::Vec3A;
 
 use crate::*;
+use std::borrow::Cow;
 
 pub const EMPTY_AABB: Aabb = Aabb {
     center: Vec3A::ZERO,
@@ -14,6 +15,10 @@ pub const EMPTY_AABB: Aabb = Aabb {
 pub enum BlockMesh {
     /// [`BlockMeshType::Cube`]
     Cube(Mesh),
+    /// [`BlockMeshType::TranslucentCube`]
+    TranslucentCube(Mesh),
+    /// [`BlockMeshType::Fluid`]
+    Fluid(Mesh),
     /// [`BlockMeshType::Custom`]
     Custom(Mesh),
     /// [`BlockMeshType::XSprite`]
@@ -35,9 +40,29 @@ impl BlockMesh {
         unimplemented!()
     }
 
+    /// Turn a [`cube`](`BlockMeshType::Cube`) mesh into a
+    /// [`translucent cube`](`BlockMeshType::TranslucentCube`) mesh, other meshes are unchanged.
+    pub fn into_translucent(self) -> Self {
+        match self {
+            BlockMesh::Cube(mesh) => BlockMesh::TranslucentCube(mesh),
+            other => other,
+        }
+    }
+
+    /// Turn a [`cube`](`BlockMeshType::Cube`) mesh into a [`fluid`](`BlockMeshType::Fluid`) mesh,
+    /// other meshes are unchanged.
+    pub fn into_fluid(self) -> Self {
+        match self {
+            BlockMesh::Cube(mesh) | BlockMesh::TranslucentCube(mesh) => BlockMesh::Fluid(mesh),
+            other => other,
+        }
+    }
+
     pub fn as_ref<'a>(&'a self) -> BlockMeshRef<'a> {
         match self {
             BlockMesh::Cube(mesh) => BlockMeshRef::Cube(mesh),
+            BlockMesh::TranslucentCube(mesh) => BlockMeshRef::TranslucentCube(mesh),
+            BlockMesh::Fluid(mesh) => BlockMeshRef::Fluid(mesh),
             BlockMesh::Custom(mesh) => BlockMeshRef::Custom(mesh),
             BlockMesh::XSprite(mesh) => BlockMeshRef::XSprite(mesh),
             BlockMesh::Air => BlockMeshRef::Air,
@@ -47,6 +72,8 @@ impl BlockMesh {
     pub fn get_type(&self) -> BlockMeshType {
         match self {
             BlockMesh::Cube(_) => BlockMeshType::Cube,
+            BlockMesh::TranslucentCube(_) => BlockMeshType::TranslucentCube,
+            BlockMesh::Fluid(_) => BlockMeshType::Fluid,
             BlockMesh::Custom(_) => BlockMeshType::Custom,
             BlockMesh::XSprite(_) => BlockMeshType::XSprite,
             BlockMesh::Air => BlockMeshType::Air,
@@ -56,6 +83,8 @@ impl BlockMesh {
     pub fn as_option(self) -> Option<Mesh> {
         match self {
             BlockMesh::Cube(mesh) => Some(mesh),
+            BlockMesh::TranslucentCube(mesh) => Some(mesh),
+            BlockMesh::Fluid(mesh) => Some(mesh),
             BlockMesh::Custom(mesh) => Some(mesh),
             BlockMesh::XSprite(mesh) => Some(mesh),
             BlockMesh::Air => None,
@@ -67,6 +96,10 @@ impl BlockMesh {
 pub enum BlockMeshRef<'a> {
     /// [`BlockMeshType::Cube`]
     Cube(&'a Mesh),
+    /// [`BlockMeshType::TranslucentCube`]
+    TranslucentCube(&'a Mesh),
+    /// [`BlockMeshType::Fluid`]
+    Fluid(&'a Mesh),
     /// [`BlockMeshType::Custom`]
     Custom(&'a Mesh),
     /// [`BlockMeshType::XSprite`]
@@ -80,6 +113,13 @@ pub enum BlockMeshRef<'a> {
 pub enum BlockMeshType {
     /// A cube mesh. The most normal type of block mesh. Most blocks will use this.
     Cube,
+    /// A cube mesh that can be seen through, like glass or ice. Translucent cubes are meshed
+    /// separately from the rest of the cubes, so they can be alpha blended. They don't hide the
+    /// faces of the blocks behind them, and only hide the faces of identical translucent cubes.
+    TranslucentCube,
+    /// A fluid, like water or lava. The mesh is a cube whose top surface is lowered according to
+    /// the level of the fluid, and slopes towards the neighboring fluid blocks.
+    Fluid,
     /// A custom mesh. This is a mesh that the user can express in code, meaning not
     /// an imported 3d model. For example, a custom mesh could be a sphere or a cylinder
     /// that the user can easily define using [`Bevy's shapes`](https://docs.rs/bevy/latest/bevy/prelude/shape/index.html).
@@ -96,6 +136,8 @@ impl<'a> BlockMeshRef<'a> {
     pub fn get_type(&self) -> BlockMeshType {
         match self {
             BlockMeshRef::Cube(_) => BlockMeshType::Cube,
+            BlockMeshRef::TranslucentCube(_) => BlockMeshType::TranslucentCube,
+            BlockMeshRef::Fluid(_) => BlockMeshType::Fluid,
             BlockMeshRef::Custom(_) => BlockMeshType::Custom,
             BlockMeshRef::XSprite(_) => BlockMeshType::XSprite,
             BlockMeshRef::Air => BlockMeshType::Air,
@@ -118,6 +160,16 @@ impl<'a> BlockMeshRef<'a> {
         }
     }
 
+    /// Returns true if the mesh is a translucent cube.
+    pub fn is_translucent_cube(&self) -> bool {
+        matches!(self, BlockMeshRef::TranslucentCube(_))
+    }
+
+    /// Returns true if the mesh is a fluid.
+    pub fn is_fluid(&self) -> bool {
+        matches!(self, BlockMeshRef::Fluid(_))
+    }
+
     /// Returns true if the mesh is a custom mesh.
     pub fn is_custom(&self) -> bool {
         match self {
@@ -137,6 +189,10 @@ impl<'a> BlockMeshRef<'a> {
     pub fn get_if(&self, mesh_type: BlockMeshType) -> Option<&'a Mesh> {
         match self {
             BlockMeshRef::Cube(mesh) if mesh_type == BlockMeshType::Cube => Some(mesh),
+            BlockMeshRef::TranslucentCube(mesh) if mesh_type == BlockMeshType::TranslucentCube => {
+                Some(mesh)
+            }
+            BlockMeshRef::Fluid(mesh) if mesh_type == BlockMeshType::Fluid => Some(mesh),
             BlockMeshRef::Custom(mesh) if mesh_type == BlockMeshType::Custom => Some(mesh),
             BlockMeshRef::XSprite(mesh) if mesh_type == BlockMeshType::XSprite => Some(mesh),
             _ => None,
@@ -146,7 +202,9 @@ impl<'a> BlockMeshRef<'a> {
     /// Returns the AABB of the mesh. If air, returns an empty AABB.
     pub fn get_aabb(&self) -> Aabb {
         match self {
-            BlockMeshRef::Cube(mesh) => mesh
+            BlockMeshRef::Cube(mesh)
+            | BlockMeshRef::TranslucentCube(mesh)
+            | BlockMeshRef::Fluid(mesh) => mesh
                 .compute_aabb()
                 .expect("Failed to compute AABB for cube"),
             BlockMeshRef::Custom(mesh) => mesh
@@ -162,7 +220,9 @@ impl<'a> BlockMeshRef<'a> {
     /// Get vertices count
     pub fn get_vertex_count(&self) -> usize {
         match self {
-            BlockMeshRef::Cube(mesh) => mesh.count_vertices(),
+            BlockMeshRef::Cube(mesh)
+            | BlockMeshRef::TranslucentCube(mesh)
+            | BlockMeshRef::Fluid(mesh) => mesh.count_vertices(),
             BlockMeshRef::Custom(mesh) => mesh.count_vertices(),
             BlockMeshRef::XSprite(mesh) => mesh.count_vertices(),
             BlockMeshRef::Air => 0,
@@ -172,7 +232,9 @@ impl<'a> BlockMeshRef<'a> {
     /// Get indices count (not number of traingles, number of indices)
     pub fn get_indices_count(&self) -> usize {
         match self {
-            BlockMeshRef::Cube(mesh) => mesh.indices().map_or(0, |i| i.len()),
+            BlockMeshRef::Cube(mesh)
+            | BlockMeshRef::TranslucentCube(mesh)
+            | BlockMeshRef::Fluid(mesh) => mesh.indices().map_or(0, |i| i.len()),
             BlockMeshRef::Custom(mesh) => mesh.indices().map_or(0, |i| i.len()),
             BlockMeshRef::XSprite(mesh) => mesh.indices().map_or(0, |i| i.len()),
             BlockMeshRef::Air => 0,
@@ -183,6 +245,8 @@ impl<'a> BlockMeshRef<'a> {
     pub fn as_option(self) -> Option<&'a Mesh> {
         match self {
             BlockMeshRef::Cube(mesh) => Some(mesh),
+            BlockMeshRef::TranslucentCube(mesh) => Some(mesh),
+            BlockMeshRef::Fluid(mesh) => Some(mesh),
             BlockMeshRef::Custom(mesh) => Some(mesh),
             BlockMeshRef::XSprite(mesh) => Some(mesh),
             BlockMeshRef::Air => None,
@@ -193,6 +257,8 @@ impl<'a> BlockMeshRef<'a> {
     pub fn unwrap(self) -> &'a Mesh {
         match self {
             BlockMeshRef::Cube(mesh) => mesh,
+            BlockMeshRef::TranslucentCube(mesh) => mesh,
+            BlockMeshRef::Fluid(mesh) => mesh,
             BlockMeshRef::Custom(mesh) => mesh,
             BlockMeshRef::XSprite(mesh) => mesh,
             BlockMeshRef::Air => panic!("Called unwrap on air mesh"),
@@ -203,9 +269,62 @@ impl<'a> BlockMeshRef<'a> {
     pub fn expect(self, msg: &str) -> &'a Mesh {
         match self {
             BlockMeshRef::Cube(mesh) => mesh,
+            BlockMeshRef::TranslucentCube(mesh) => mesh,
+            BlockMeshRef::Fluid(mesh) => mesh,
             BlockMeshRef::Custom(mesh) => mesh,
             BlockMeshRef::XSprite(mesh) => mesh,
             BlockMeshRef::Air => panic!("{}", msg),
         }
     }
 }
+
+/// The mesh of a block in the given orientation. The positions and normals of the mesh are rotated
+/// around the center of the block, the UVs are carried by the vertices. Since the rotation is by
+/// multiples of 90 degrees, the faces of a cube still lie on the planes of the cube, so the
+/// meshers can tell which face of the block a triangle belongs to as usual.
+pub fn oriented_mesh(mesh: &Mesh, orientation: Orientation, center: [f32; 3]) -> Cow<'_, Mesh> {
+    if orientation.is_default() {
+        return Cow::Borrowed(mesh);
+    }
+    let mut mesh = mesh.clone();
+    if let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    {
+        for pos in positions.iter_mut() {
+            let rotated =
+                orientation.rotate([pos[0] - center[0], pos[1] - center[1], pos[2] - center[2]]);
+            *pos = [
+                rotated[0] + center[0],
+                rotated[1] + center[1],
+                rotated[2] + center[2],
+            ];
+        }
+    }
+    if let Some(VertexAttributeValues::Float32x3(normals)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+    {
+        for normal in normals.iter_mut() {
+            *normal = orientation.rotate(*normal);
+        }
+    }
+    Cow::Owned(mesh)
+}
+
+/// The positions and triangles of a mesh, for building colliders out of block meshes. Meshes
+/// without indices are read as a triangle list.
+pub fn mesh_triangles(mesh: &Mesh) -> (Vec<[f32; 3]>, Vec<[u32; 3]>) {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return (Vec::new(), Vec::new());
+    };
+    let indices: Vec<u32> = match mesh.indices() {
+        Some(indices) => indices.iter().map(|i| i as u32).collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    let triangles = indices
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .collect();
+    (positions.clone(), triangles)
+}
@@ -13,6 +13,9 @@ pub(crate) struct CubeVIVI {
     pub(crate) vivi: Vec<Vec<u32>>,
     /// A map that maps a vertex to the voxel it belongs to.
     pub(crate) map: HashMap<u32, u32>,
+    /// Quads that were merged by [`MeshingAlgorithm::Greedy`] cover more than one voxel. This maps
+    /// the first vertex of a merged quad to the voxels it covers, other than the voxel it belongs to.
+    pub(crate) merged: HashMap<u32, Vec<u32>>,
 }
 
 impl CubeVIVI {
@@ -21,6 +24,7 @@ impl CubeVIVI {
         CubeVIVI {
             vivi: vec![vec![]; voxel_count],
             map: HashMap::new(),
+            merged: HashMap::new(),
         }
     }
 
@@ -31,6 +35,35 @@ impl CubeVIVI {
             .insert(vertex, voxel_index as u32 | face_to_u32(face));
     }
 
+    /// Insert a voxel that is covered by a quad that belongs to another voxel (a merged quad).
+    pub(crate) fn insert_merged_quad(&mut self, face: Face, voxel_index: usize, vertex: u32) {
+        self.vivi[voxel_index].push((vertex) | face_to_u32(face));
+        self.merged
+            .entry(vertex)
+            .or_default()
+            .push(voxel_index as u32);
+    }
+
+    /// If the quad of a voxel is a merged quad, get the voxel it belongs to, and the rest of the
+    /// voxels it covers.
+    pub(crate) fn get_merged_quad(
+        &self,
+        face: Face,
+        voxel_index: usize,
+    ) -> Option<(u32, &Vec<u32>)> {
+        let quad = self.get_quad_index(face, voxel_index)?;
+        let members = self.merged.get(&quad)?;
+        let owner = self.map.get(&quad)? & OFFSET_CONST;
+        Some((owner, members))
+    }
+
+    /// Whether the quad of the voxel belongs to it, and not to another voxel it was merged with.
+    pub(crate) fn owns_quad(&self, face: Face, voxel_index: usize) -> bool {
+        self.get_quad_index(face, voxel_index)
+            .and_then(|quad| self.map.get(&quad))
+            .is_some_and(|voxel| voxel & OFFSET_CONST == voxel_index as u32)
+    }
+
     /// Get the quad index of a voxel.
     pub(crate) fn get_quad_index(&self, face: Face, voxel_index: usize) -> Option<u32> {
         for quad in self.vivi[voxel_index].iter() {
@@ -55,12 +88,46 @@ impl CubeVIVI {
             if *v == old_vertex {
                 *v = new_vertex as u32 | q;
                 self.map.insert(new_vertex as u32, voxel);
+                if let Some(members) = self.merged.remove(&(old_vertex & OFFSET_CONST)) {
+                    for m in members.iter() {
+                        for v in self.vivi[*m as usize].iter_mut() {
+                            if *v == old_vertex {
+                                *v = new_vertex as u32 | q;
+                            }
+                        }
+                    }
+                    self.merged.insert(new_vertex as u32, members);
+                }
                 return;
             }
         }
         panic!("Couldn't find vertex index in VIVI");
     }
 
+    /// Move the quads to their new first vertex, `new_vertices` maps the old first vertex of each
+    /// quad to the new one.
+    pub(crate) fn move_quads(&mut self, new_vertices: &HashMap<u32, u32>) {
+        let move_quad = |quad: u32| {
+            let vertex = quad & OFFSET_CONST;
+            new_vertices.get(&vertex).copied().unwrap_or(vertex) | (quad & !OFFSET_CONST)
+        };
+        for quads in self.vivi.iter_mut() {
+            for quad in quads.iter_mut() {
+                *quad = move_quad(*quad);
+            }
+        }
+        self.map = self
+            .map
+            .drain()
+            .map(|(vertex, voxel)| (move_quad(vertex), voxel))
+            .collect();
+        self.merged = self
+            .merged
+            .drain()
+            .map(|(vertex, members)| (move_quad(vertex), members))
+            .collect();
+    }
+
     /// Remove a quad from the CubeVIVI.
     pub(crate) fn remove_quad(&mut self, old_vertex: usize) {
         let voxel = self
@@ -78,6 +145,11 @@ impl CubeVIVI {
         }
         if r.0 {
             self.vivi[v as usize].swap_remove(r.1);
+            if let Some(members) = self.merged.remove(&(old_vertex & OFFSET_CONST)) {
+                for m in members {
+                    self.vivi[m as usize].retain(|j| *j != old_vertex);
+                }
+            }
         } else {
             panic!("Couldn't find quad from vertex");
         }
@@ -87,6 +159,8 @@ impl CubeVIVI {
 /// Mesh meta-data struct for cubic meshes (made up of [`BlockMeshType::Cube`]).
 /// Holds all the information needed to update the mesh at run-time.
 pub struct CubeMD<B: BlockInGrid> {
+    /// The blocks that are meshed in the mesh.
+    pub(crate) layer: CubeLayer,
     pub(crate) vivi: CubeVIVI,
     pub(crate) smooth_lighting_params: Option<SmoothLightingParameters>,
     /// The dimensions of the 3d grid.
@@ -97,6 +171,9 @@ pub struct CubeMD<B: BlockInGrid> {
     /// [`SurroundingBlocks<B>`]: The blocks surrounding the block that changed
     /// in the `Neighbors` data-type, if the voxel is "empty"- None.
     pub(crate) changed_voxels: Vec<(B, BlockPos, BlockMeshChange, SurroundingBlocks<B>)>,
+    /// The blocks that own merged quads (see [`MeshingAlgorithm::Greedy`]), by voxel index.
+    /// Needed to split the merged quads back into a quad per block when they are updated.
+    pub(crate) merged_blocks: HashMap<u32, B>,
 }
 
 impl<B: BlockInGrid> CubeMD<B> {
@@ -117,6 +194,11 @@ impl<B: BlockInGrid> CubeMD<B> {
         self.changed_voxels
             .push((block, block_pos, block_mesh_change, surrounding_blocks));
     }
+    /// The [`CubeLayer`] of the mesh.
+    pub fn layer(&self) -> CubeLayer {
+        self.layer
+    }
+
     /// Get read only of the `SmoothLightingParameters`
     pub fn get_sl_params(&self) -> Option<SmoothLightingParameters> {
         self.smooth_lighting_params
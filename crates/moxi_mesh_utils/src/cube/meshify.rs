@@ -5,18 +5,26 @@ use crate::*;
 pub enum MeshingAlgorithm {
     Naive,
     Culling,
+    /// Culling, and then merging coplanar visible faces of the same block into bigger quads.
+    /// The UVs of a merged quad span one tile per block, so the mesh has to be drawn with a
+    /// material that repeats the texture inside its atlas tile, like the [`TiledAtlasMaterial`]
+    /// (the bounds of the tiles are in [`ATTRIBUTE_TILE_BOUNDS`]).
+    Greedy,
 }
 
 /// Arguments:
 /// - [`outer_layer`](&[Face]): The faces of the blocks that are on the outer layer of the grid.
 /// - [`grid`](`ChunkGrid`): The grid of the blocks, this is the data structure that all of the
-///     information about the blocks is stored in. It is a wrapper around a  3D array of blocks.
+///   information about the blocks is stored in. It is a wrapper around a  3D array of blocks.
 /// - [`reg`](`MeshRegistry`): The mesh registry that contains all the meshes of the blocks.
-/// - ['ma'](MeshingAlgorithm): The meshing algorithm to use - currently supports Culling and
-///     Naive. (Culling is always better than Naive)
+/// - ['ma'](MeshingAlgorithm): The meshing algorithm to use - currently supports Culling,
+///   Naive and Greedy. (Culling is always better than Naive, Greedy produces the least vertices)
 /// - ['sl'](`SmoothLightingParameters`): Enable Smooth Lighting (Some ..) or not (None). Smooth Lighting is a technique often used in
-///     voxel based games that resembles Ambient Occlusion, but it is static- which means the
-///     shadows are computed only once, when the mesh is generated (or updated).
+///   voxel based games that resembles Ambient Occlusion, but it is static- which means the
+///   shadows are computed only once, when the mesh is generated (or updated).
+/// - ['orientation_at'](`Orientation`): The orientation of the block at a position, the mesh of
+///   the block is rotated accordingly (see [`oriented_mesh`]). Blocks with different
+///   orientations aren't merged by greedy meshing.
 ///
 /// Returns the mesh and the mesh metadata.
 pub fn meshify_cubic_voxels<B: BlockInGrid, const N: usize>(
@@ -25,6 +33,50 @@ pub fn meshify_cubic_voxels<B: BlockInGrid, const N: usize>(
     reg: &impl MeshRegistry<B>,
     meshing_algorithm: MeshingAlgorithm,
     smooth_lighting_params: Option<SmoothLightingParameters>,
+    orientation_at: impl Fn(BlockPos) -> Orientation,
+) -> Option<(Mesh, CubeMD<B>)> {
+    meshify_cube_layer(
+        CubeLayer::Opaque,
+        outer_layer,
+        grid,
+        reg,
+        meshing_algorithm,
+        smooth_lighting_params,
+        orientation_at,
+    )
+}
+
+/// Like [`meshify_cubic_voxels`], but meshes the
+/// [`translucent cubes`](`BlockMeshType::TranslucentCube`) (see [`CubeLayer::is_face_culled`] for
+/// which faces are culled). The quads should be sorted back to front before they are drawn, see
+/// [`sort_quads_back_to_front`].
+pub fn meshify_translucent_cubic_voxels<B: BlockInGrid, const N: usize>(
+    outer_layer: &[Face],
+    grid: &Grid<B, N>,
+    reg: &impl MeshRegistry<B>,
+    meshing_algorithm: MeshingAlgorithm,
+    smooth_lighting_params: Option<SmoothLightingParameters>,
+    orientation_at: impl Fn(BlockPos) -> Orientation,
+) -> Option<(Mesh, CubeMD<B>)> {
+    meshify_cube_layer(
+        CubeLayer::Translucent,
+        outer_layer,
+        grid,
+        reg,
+        meshing_algorithm,
+        smooth_lighting_params,
+        orientation_at,
+    )
+}
+
+fn meshify_cube_layer<B: BlockInGrid, const N: usize>(
+    layer: CubeLayer,
+    outer_layer: &[Face],
+    grid: &Grid<B, N>,
+    reg: &impl MeshRegistry<B>,
+    meshing_algorithm: MeshingAlgorithm,
+    smooth_lighting_params: Option<SmoothLightingParameters>,
+    orientation_at: impl Fn(BlockPos) -> Orientation,
 ) -> Option<(Mesh, CubeMD<B>)> {
     let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
     let total_voxels = grid.len();
@@ -45,34 +97,64 @@ pub fn meshify_cubic_voxels<B: BlockInGrid, const N: usize>(
     let voxel_dims = reg.get_block_dims();
     let center = reg.get_block_center();
 
-    for (block_pos, block) in grid.enumerate_blocks().filter(|(_, v)| reg.is_cube(v)) {
-        let position_offset = Vec3::from(voxel_dims) * block_pos.as_vec3();
-
-        let quads_to_keep = match meshing_algorithm {
-            MeshingAlgorithm::Culling => grid.enumerate_neighbors(block_pos).map(|(f, block)| {
-                block.map_or_else(
-                    || outer_layers_to_keep[f as usize],
-                    |block| !reg.is_cube(&block),
-                )
-            }),
-            MeshingAlgorithm::Naive => [true; 6],
-        };
-
-        if quads_to_keep == [false; 6] {
-            continue;
-        }
-
-        add_vertices_normal_cube(
-            quads_to_keep,
+    let mut merged_blocks = HashMap::new();
+    if let MeshingAlgorithm::Greedy = meshing_algorithm {
+        vertices.push((
+            ATTRIBUTE_TILE_BOUNDS,
+            VertexAttributeValues::new(ATTRIBUTE_TILE_BOUNDS.format),
+        ));
+        add_greedy_quads(
+            layer,
+            outer_layers_to_keep,
+            grid,
+            reg,
             &mut indices,
             &mut vertices,
-            reg.get_block_mesh_ref(&block).unwrap(),
             &mut vivi,
-            block_pos,
-            center,
-            position_offset.into(),
-            grid.dims,
+            &mut merged_blocks,
+            &orientation_at,
         );
+    } else {
+        for (block_pos, block) in grid
+            .enumerate_blocks()
+            .filter(|(_, v)| layer.contains(reg, v))
+        {
+            let position_offset = Vec3::from(voxel_dims) * block_pos.as_vec3();
+
+            let quads_to_keep = match meshing_algorithm {
+                MeshingAlgorithm::Naive => [true; 6],
+                _ => grid.enumerate_neighbors(block_pos).map(|(f, neighbor)| {
+                    neighbor.map_or_else(
+                        || outer_layers_to_keep[f as usize],
+                        |neighbor| {
+                            let neighbor_orientation = neighbor_pos(block_pos, f, grid.dims)
+                                .map_or(Orientation::default(), &orientation_at);
+                            !layer.is_face_culled(reg, &block, f, &neighbor, neighbor_orientation)
+                        },
+                    )
+                }),
+            };
+
+            if quads_to_keep == [false; 6] {
+                continue;
+            }
+
+            add_vertices_normal_cube(
+                quads_to_keep,
+                &mut indices,
+                &mut vertices,
+                &oriented_mesh(
+                    reg.get_block_mesh_ref(&block).unwrap(),
+                    orientation_at(block_pos),
+                    center,
+                ),
+                &mut vivi,
+                block_pos,
+                center,
+                position_offset.into(),
+                grid.dims,
+            );
+        }
     }
 
     for (att, vals) in vertices {
@@ -81,10 +163,12 @@ pub fn meshify_cubic_voxels<B: BlockInGrid, const N: usize>(
     mesh.set_indices(Some(Indices::U32(indices)));
 
     let d_mesh = CubeMD {
+        layer,
         dims: grid.dims,
         smooth_lighting_params,
         vivi,
         changed_voxels: vec![],
+        merged_blocks,
     };
 
     if let Some(t) = smooth_lighting_params {
@@ -95,6 +179,207 @@ pub fn meshify_cubic_voxels<B: BlockInGrid, const N: usize>(
     Some((mesh, d_mesh))
 }
 
+/// The axes of the plane a face lies on, (the axis of the face's normal, the first axis of the
+/// plane, the second axis of the plane).
+fn face_axes(face: Face) -> (usize, usize, usize) {
+    match face {
+        Face::Top | Face::Bottom => (1, 0, 2),
+        Face::Right | Face::Left => (0, 2, 1),
+        Face::Back | Face::Front => (2, 0, 1),
+    }
+}
+
+/// Greedy meshing: For each face direction, go over the grid slice by slice, and merge the visible
+/// faces of identical blocks into rectangles. Each rectangle is added to the mesh as the quad of
+/// the first block (the owner), stretched over the rest of the blocks. The rest of the blocks are
+/// registered in the [`CubeVIVI`] as covered by the owner's quad.
+#[allow(clippy::too_many_arguments)]
+fn add_greedy_quads<B: BlockInGrid, const N: usize>(
+    layer: CubeLayer,
+    outer_layers_to_keep: [bool; 6],
+    grid: &Grid<B, N>,
+    reg: &impl MeshRegistry<B>,
+    indices: &mut Vec<u32>,
+    vertices: &mut Vec<(MeshVertexAttribute, VertexAttributeValues)>,
+    vivi: &mut CubeVIVI,
+    merged_blocks: &mut HashMap<u32, B>,
+    orientation_at: &impl Fn(BlockPos) -> Orientation,
+) {
+    let voxel_dims = reg.get_block_dims();
+    let center = reg.get_block_center();
+    let dims = grid.dims.to_array();
+    let is_face_visible = |block_pos: BlockPos, block: B, face: Face| {
+        grid.get_neighbor_of(block_pos, face).map_or(
+            outer_layers_to_keep[face as usize],
+            |neighbor| {
+                let neighbor_orientation = neighbor_pos(block_pos, face, grid.dims)
+                    .map_or(Orientation::default(), orientation_at);
+                !layer.is_face_culled(reg, &block, face, &neighbor, neighbor_orientation)
+            },
+        )
+    };
+
+    for face in FACES {
+        let (n_axis, a_axis, b_axis) = face_axes(face);
+        let (len_a, len_b) = (dims[a_axis] as usize, dims[b_axis] as usize);
+        let pos_in_slice = |n: u32, a: usize, b: usize| {
+            let mut pos = [0; 3];
+            pos[n_axis] = n;
+            pos[a_axis] = a as u32;
+            pos[b_axis] = b as u32;
+            BlockPos::from(pos)
+        };
+
+        for n in 0..dims[n_axis] {
+            let mut merged = vec![false; len_a * len_b];
+            for b in 0..len_b {
+                for a in 0..len_a {
+                    if merged[b * len_a + a] {
+                        continue;
+                    }
+                    let block_pos = pos_in_slice(n, a, b);
+                    let block = grid.get_block(block_pos).unwrap();
+                    if !layer.contains(reg, &block) || !is_face_visible(block_pos, block, face) {
+                        continue;
+                    }
+                    let orientation = orientation_at(block_pos);
+                    let can_merge = |a: usize, b: usize| {
+                        let pos = pos_in_slice(n, a, b);
+                        !merged[b * len_a + a]
+                            && grid.get_block(pos) == Some(block)
+                            && orientation_at(pos) == orientation
+                            && is_face_visible(pos, block, face)
+                    };
+
+                    let mut width = 1;
+                    while a + width < len_a && can_merge(a + width, b) {
+                        width += 1;
+                    }
+                    let mut height = 1;
+                    while b + height < len_b && (a..a + width).all(|a| can_merge(a, b + height)) {
+                        height += 1;
+                    }
+                    for j in b..b + height {
+                        for i in a..a + width {
+                            merged[j * len_a + i] = true;
+                        }
+                    }
+
+                    let quad = vertices[0].1.len() as u32;
+                    let mut sides = [false; 6];
+                    sides[face as usize] = true;
+                    let position_offset = Vec3::from(voxel_dims) * block_pos.as_vec3();
+                    add_vertices_normal_cube(
+                        sides,
+                        indices,
+                        vertices,
+                        &oriented_mesh(
+                            reg.get_block_mesh_ref(&block).unwrap(),
+                            orientation,
+                            center,
+                        ),
+                        vivi,
+                        block_pos,
+                        center,
+                        position_offset.into(),
+                        grid.dims,
+                    );
+                    if width == 1 && height == 1 {
+                        continue;
+                    }
+
+                    stretch_quad(
+                        vertices,
+                        quad as usize,
+                        a_axis,
+                        b_axis,
+                        (width - 1) as f32,
+                        (height - 1) as f32,
+                    );
+                    for j in b..b + height {
+                        for i in a..a + width {
+                            if (i, j) != (a, b) {
+                                let pos = pos_in_slice(n, i, j);
+                                let voxel_index = pos_to_index(pos, grid.dims).unwrap();
+                                vivi.insert_merged_quad(face, voxel_index, quad);
+                            }
+                        }
+                    }
+                    merged_blocks.insert(pos_to_index(block_pos, grid.dims).unwrap() as u32, block);
+                }
+            }
+        }
+    }
+}
+
+/// Stretch the quad that starts at vertex `quad` by `stretch_a` blocks along `a_axis` and
+/// `stretch_b` blocks along `b_axis`. The vertices on the far side of each axis are pushed away
+/// from their partner on the near side, both in position and in UV, so the texture repeats.
+fn stretch_quad(
+    vertices: &mut [(MeshVertexAttribute, VertexAttributeValues)],
+    quad: usize,
+    a_axis: usize,
+    b_axis: usize,
+    stretch_a: f32,
+    stretch_b: f32,
+) {
+    let positions: [[f32; 3]; 4] = {
+        let (_, VertexAttributeValues::Float32x3(positions)) = vertices
+            .iter()
+            .find(|(att, _)| att.id == Mesh::ATTRIBUTE_POSITION.id)
+            .expect("Cube meshes need a position attribute")
+        else {
+            panic!("Unexpected vertex format for position attribute, expected Float32x3.");
+        };
+        positions[quad..quad + 4].try_into().unwrap()
+    };
+    let partner = |v: usize, axis: usize, other_axis: usize| {
+        (0..4)
+            .find(|&u| {
+                positions[u][other_axis] == positions[v][other_axis]
+                    && positions[u][axis] != positions[v][axis]
+            })
+            .map(|u| (u, positions[v][axis] > positions[u][axis]))
+    };
+    let partners: Vec<_> = (0..4)
+        .map(|v| (partner(v, a_axis, b_axis), partner(v, b_axis, a_axis)))
+        .collect();
+
+    for (att, vals) in vertices.iter_mut() {
+        match vals {
+            VertexAttributeValues::Float32x3(vals) if att.id == Mesh::ATTRIBUTE_POSITION.id => {
+                stretch_values(&mut vals[quad..quad + 4], &partners, stretch_a, stretch_b)
+            }
+            VertexAttributeValues::Float32x2(vals) if att.id == Mesh::ATTRIBUTE_UV_0.id => {
+                stretch_values(&mut vals[quad..quad + 4], &partners, stretch_a, stretch_b)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// For each vertex of a quad, the vertex across the quad along each axis, and whether the vertex is
+/// on the far side of that axis.
+type QuadPartners = [(Option<(usize, bool)>, Option<(usize, bool)>)];
+
+fn stretch_values<const D: usize>(
+    vals: &mut [[f32; D]],
+    partners: &QuadPartners,
+    stretch_a: f32,
+    stretch_b: f32,
+) {
+    let original: Vec<[f32; D]> = vals.to_vec();
+    for (v, (partner_a, partner_b)) in partners.iter().enumerate() {
+        for (partner, stretch) in [(partner_a, stretch_a), (partner_b, stretch_b)] {
+            if let Some((u, true)) = partner {
+                for i in 0..D {
+                    vals[v][i] += stretch * (original[v][i] - original[*u][i]);
+                }
+            }
+        }
+    }
+}
+
 /// Important helper function to add the vertices and indices of each voxel into the running count of vertices
 /// and indices, preserving their attributes, and (important!) assigning a custom offset to the
 /// position attributes, we are assuming this is only needed for the position attributes (because
@@ -227,6 +512,10 @@ fn add_vertices_normal_cube(
     }
 
     for (id, vals) in vertices.iter_mut() {
+        if id.id == ATTRIBUTE_TILE_BOUNDS.id {
+            vals.extend(&tile_bounds(voxel, &final_vertices));
+            continue;
+        }
         let mut att = voxel
             .attribute(id.id)
             .expect(format!("Couldn't retrieve voxel mesh attribute {:?}.", id).as_str())
@@ -238,3 +527,287 @@ fn add_vertices_normal_cube(
     }
     indices_main.extend(indices_to_save);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{meshify_custom_voxels, update_custom_mesh};
+    use bevy_asset::Handle;
+
+    /// The meshes of the blocks, and the shape of the custom blocks
+    struct TestReg(Vec<BlockMesh>, BlockShape);
+
+    impl MeshRegistry<BlockId> for TestReg {
+        fn get_block_mesh_ref(&self, block: &BlockId) -> BlockMeshRef<'_> {
+            self.0[*block as usize].as_ref()
+        }
+
+        fn get_block_mesh_handle(&self, _block: &BlockId) -> Handle<Mesh> {
+            Handle::default()
+        }
+
+        fn get_block_mesh_type(&self, block: &BlockId) -> BlockMeshType {
+            self.0[*block as usize].get_type()
+        }
+
+        fn get_block_shape(&self, block: &BlockId) -> &BlockShape {
+            match self.get_block_mesh_type(block) {
+                BlockMeshType::Cube | BlockMeshType::TranslucentCube => &FULL_SHAPE,
+                BlockMeshType::Custom => &self.1,
+                _ => &EMPTY_SHAPE,
+            }
+        }
+    }
+
+    fn test_reg() -> TestReg {
+        let cube = generate_cube_mesh(
+            [1.0; 3],
+            [4, 4],
+            CubeTextureCords::uniform([0, 0]),
+            [0.0; 3],
+            0.0,
+            Some(1.0),
+            1.0,
+        );
+        let glass = cube.clone().into_translucent();
+        let log = generate_cube_mesh(
+            [1.0; 3],
+            [4, 4],
+            CubeTextureCords::uniform([0, 0]).with_face(Face::Top, [1, 0]),
+            [0.0; 3],
+            0.0,
+            Some(1.0),
+            1.0,
+        );
+        let slab = generate_cube_mesh(
+            [1.0, 0.5, 1.0],
+            [4, 4],
+            CubeTextureCords::uniform([0, 0]),
+            [0.0, -0.25, 0.0],
+            0.0,
+            Some(1.0),
+            1.0,
+        );
+        let slab = BlockMesh::from_custom_mesh(slab.as_option().unwrap());
+        TestReg(
+            vec![BlockMesh::Air, cube, glass, log, slab],
+            BlockShape::slab(),
+        )
+    }
+
+    #[test]
+    fn test_translucent_culling_and_sorting() {
+        let reg = test_reg();
+        let dims = Dimensions::new(3, 1, 1);
+        let mut grid = Grid::<BlockId, 3>::new([2, 2, 1], dims);
+
+        let (opaque_mesh, _) =
+            meshify_cubic_voxels(&[], &grid, &reg, MeshingAlgorithm::Culling, None, |_| {
+                Orientation::default()
+            })
+            .unwrap();
+        // The stone keeps the face next to the glass
+        assert_eq!(opaque_mesh.count_vertices(), 6 * 4);
+
+        let (mut mesh, mut md) = meshify_translucent_cubic_voxels(
+            &[],
+            &grid,
+            &reg,
+            MeshingAlgorithm::Culling,
+            None,
+            |_| Orientation::default(),
+        )
+        .unwrap();
+        // The face between the two glass blocks is culled, the face next to the stone isn't
+        assert_eq!(mesh.count_vertices(), 10 * 4);
+        assert!(!md.quad_exists([0, 0, 0].into(), Face::Right));
+        assert!(md.quad_exists([1, 0, 0].into(), Face::Right));
+
+        sort_quads_back_to_front(&mut mesh, &mut md, Vec3::new(10.0, 0.5, 0.5));
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("Expected Float32x3 positions");
+        };
+        // The furthest quad is the left face of the first glass block
+        assert!(positions[0..4].iter().all(|pos| pos[0] == -0.5));
+        assert_eq!(mesh.count_vertices(), 10 * 4);
+        assert!(md.quad_exists([0, 0, 0].into(), Face::Left));
+
+        let block_pos = BlockPos::new(1, 0, 0);
+        let surrounding_blocks = grid.get_neighbors(block_pos);
+        grid.set_block(0, block_pos).unwrap();
+        md.log(BlockMeshChange::Broken, block_pos, 2, surrounding_blocks);
+        update_cube_mesh(&mut mesh, &mut md, &reg, |_| Orientation::default());
+
+        let Some(Indices::U32(indices)) = mesh.indices() else {
+            panic!("Expected U32 indices format");
+        };
+        assert_eq!(mesh.count_vertices(), 6 * 4);
+        assert_eq!(indices.len(), 6 * 6);
+        assert!(md.quad_exists([0, 0, 0].into(), Face::Right));
+    }
+
+    #[test]
+    fn test_greedy_meshing_merges_and_splits_quads() {
+        let reg = test_reg();
+        let dims = Dimensions::new(4, 2, 4);
+        let mut grid = Grid::<BlockId, 32>::new([0; 32], dims);
+        for block_pos in grid.iter_blocks_on_edge(Face::Bottom).collect::<Vec<_>>() {
+            grid.set_block(1, block_pos).unwrap();
+        }
+
+        let (mut mesh, mut md) =
+            meshify_cubic_voxels(&[], &grid, &reg, MeshingAlgorithm::Greedy, None, |_| {
+                Orientation::default()
+            })
+            .unwrap();
+        // One quad per side of the 4x1x4 slab
+        assert_eq!(mesh.count_vertices(), 6 * 4);
+        assert!(md.quad_exists([2, 0, 2].into(), Face::Top));
+        // The merged quads span 4 tiles, but keep the bounds of the tile of the stone
+        let stone_tile = [0.0, 0.0, 0.25, 0.25];
+        let (
+            Some(VertexAttributeValues::Float32x2(uvs)),
+            Some(VertexAttributeValues::Float32x4(tile_bounds)),
+        ) = (
+            mesh.attribute(Mesh::ATTRIBUTE_UV_0),
+            mesh.attribute(ATTRIBUTE_TILE_BOUNDS),
+        )
+        else {
+            panic!("Expected Float32x2 uvs and Float32x4 tile bounds");
+        };
+        assert!(uvs.iter().any(|uv| uv[0] > 0.25 || uv[1] > 0.25));
+        assert_eq!(tile_bounds.len(), mesh.count_vertices());
+        assert!(tile_bounds.iter().all(|bounds| *bounds == stone_tile));
+
+        let block_pos = BlockPos::new(1, 0, 1);
+        let surrounding_blocks = grid.get_neighbors(block_pos);
+        grid.set_block(0, block_pos).unwrap();
+        md.log(BlockMeshChange::Broken, block_pos, 1, surrounding_blocks);
+        update_cube_mesh(&mut mesh, &mut md, &reg, |_| Orientation::default());
+
+        let Some(Indices::U32(indices)) = mesh.indices() else {
+            panic!("Expected U32 indices format");
+        };
+        assert_eq!(mesh.count_vertices() % 4, 0);
+        assert_eq!(indices.len(), mesh.count_vertices() / 4 * 6);
+        assert!(indices
+            .iter()
+            .all(|i| (*i as usize) < mesh.count_vertices()));
+        assert!(!md.quad_exists(block_pos, Face::Top));
+        assert!(md.quad_exists([2, 0, 1].into(), Face::Left));
+        assert!(md.quad_exists([1, 0, 2].into(), Face::Front));
+        assert!(md.quad_exists([3, 0, 3].into(), Face::Top));
+        let Some(VertexAttributeValues::Float32x4(tile_bounds)) =
+            mesh.attribute(ATTRIBUTE_TILE_BOUNDS)
+        else {
+            panic!("Expected Float32x4 tile bounds");
+        };
+        assert_eq!(tile_bounds.len(), mesh.count_vertices());
+        assert!(tile_bounds.iter().all(|bounds| *bounds == stone_tile));
+    }
+
+    #[test]
+    fn test_oriented_blocks() {
+        let reg = test_reg();
+        let dims = Dimensions::new(2, 1, 1);
+        let grid = Grid::<BlockId, 2>::new([3, 3], dims);
+        let lying_log = Orientation::axis(Axis::X);
+        assert!(matches!(lying_log.rotate_face(Face::Top), Face::Right));
+        assert!(matches!(lying_log.model_face(Face::Right), Face::Top));
+
+        // The logs have different orientations, so they aren't merged
+        let orientation_at = |pos: BlockPos| {
+            if pos.x == 1 {
+                lying_log
+            } else {
+                Orientation::default()
+            }
+        };
+        let (mesh, md) = meshify_cubic_voxels(
+            &[],
+            &grid,
+            &reg,
+            MeshingAlgorithm::Greedy,
+            None,
+            orientation_at,
+        )
+        .unwrap();
+        assert_eq!(mesh.count_vertices(), 10 * 4);
+        assert!(md.quad_exists([1, 0, 0].into(), Face::Right));
+        assert!(!md.quad_exists([1, 0, 0].into(), Face::Left));
+
+        // The top of the lying log faces right, the top of the standing log faces up
+        let (
+            Some(VertexAttributeValues::Float32x3(normals)),
+            Some(VertexAttributeValues::Float32x2(uvs)),
+        ) = (
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+            mesh.attribute(Mesh::ATTRIBUTE_UV_0),
+        )
+        else {
+            panic!("Expected Float32x3 normals and Float32x2 uvs");
+        };
+        // The quads whose vertices all lie in the top texture's tile
+        let top_textured_normals: Vec<_> = (0..mesh.count_vertices())
+            .step_by(4)
+            .filter(|&quad| uvs[quad..quad + 4].iter().all(|uv| uv[0] >= 0.25))
+            .map(|quad| normals[quad])
+            .collect();
+        assert_eq!(top_textured_normals, vec![[0.0, 1.0, 0.0], [1.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn test_partial_block_culling() {
+        let reg = test_reg();
+        let dims = Dimensions::new(2, 2, 1);
+        // Two cubes, and a slab on top of the first one
+        let mut grid = Grid::<BlockId, 4>::new([1, 1, 4, 0], dims);
+        let orientation_at = |_| Orientation::default();
+
+        let (mut mesh, mut md) = meshify_cubic_voxels(
+            &[],
+            &grid,
+            &reg,
+            MeshingAlgorithm::Culling,
+            None,
+            orientation_at,
+        )
+        .unwrap();
+        // The slab hides the top of the cube beneath it
+        assert_eq!(mesh.count_vertices(), 9 * 4);
+        assert!(!md.quad_exists([0, 0, 0].into(), Face::Top));
+        assert!(md.quad_exists([1, 0, 0].into(), Face::Top));
+
+        let (mut custom_mesh, mut custom_md) = meshify_custom_voxels(&reg, &grid, orientation_at);
+        let custom_indices_len = |mesh: &Mesh| mesh.indices().map_or(0, |indices| indices.len());
+        // The bottom of the slab is hidden by the cube
+        assert_eq!(custom_indices_len(&custom_mesh), 5 * 6);
+
+        // A cube next to the slab hides the side of the slab, but the slab doesn't hide its side
+        let block_pos = BlockPos::new(1, 1, 0);
+        grid.set_block(1, block_pos).unwrap();
+        md.log(
+            BlockMeshChange::Added,
+            block_pos,
+            1,
+            grid.get_neighbors(block_pos),
+        );
+        update_cube_mesh(&mut mesh, &mut md, &reg, orientation_at);
+        assert_eq!(mesh.count_vertices(), (9 + 5 - 1) * 4);
+        assert!(md.quad_exists(block_pos, Face::Left));
+        assert!(!md.quad_exists([1, 0, 0].into(), Face::Top));
+
+        custom_md.log_add(4, [0, 1, 0].into());
+        update_custom_mesh(
+            &reg,
+            &mut custom_mesh,
+            &mut custom_md,
+            &grid,
+            orientation_at,
+        );
+        assert_eq!(custom_indices_len(&custom_mesh), 4 * 6);
+        assert_eq!(custom_mesh.count_vertices(), 24);
+    }
+}
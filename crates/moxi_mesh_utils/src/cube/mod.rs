@@ -1,14 +1,62 @@
 mod gen;
 mod md;
 mod meshify;
+mod tiling;
 mod update;
 
 pub use gen::*;
 pub use md::*;
 pub use meshify::*;
+pub use tiling::*;
 pub use update::*;
 
-use crate::Face;
+use crate::{BlockInGrid, BlockMeshType, Face, MeshRegistry, MeshRegistryCommon, Orientation};
+
+/// The cubic blocks of a chunk are meshed in two layers, each with its own chunk mesh: the opaque
+/// [`cubes`](`BlockMeshType::Cube`) and the [`translucent cubes`](`BlockMeshType::TranslucentCube`),
+/// which are alpha blended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeLayer {
+    Opaque,
+    Translucent,
+}
+
+impl CubeLayer {
+    /// The type of the block meshes in the layer.
+    pub fn mesh_type(self) -> BlockMeshType {
+        match self {
+            CubeLayer::Opaque => BlockMeshType::Cube,
+            CubeLayer::Translucent => BlockMeshType::TranslucentCube,
+        }
+    }
+
+    /// Whether the block is meshed in this layer.
+    pub fn contains<B: BlockInGrid>(self, reg: &impl MeshRegistry<B>, block: &B) -> bool {
+        match self {
+            CubeLayer::Opaque => reg.is_cube(block),
+            CubeLayer::Translucent => reg.is_translucent_cube(block),
+        }
+    }
+
+    /// Whether `face` of `block` (a block of this layer), that touches `neighbor`, is hidden by
+    /// it. Opaque cubes are hidden by the faces of opaque blocks whose [`shape`](`crate::BlockShape`)
+    /// covers them (in the neighbor's orientation), so they can be seen through translucent cubes
+    /// and around slabs. Translucent cubes keep their faces next to opaque cubes, and are only
+    /// hidden by identical translucent cubes (the faces between two glass blocks aren't drawn).
+    pub fn is_face_culled<B: BlockInGrid>(
+        self,
+        reg: &impl MeshRegistry<B>,
+        block: &B,
+        face: Face,
+        neighbor: &B,
+        neighbor_orientation: Orientation,
+    ) -> bool {
+        match self {
+            CubeLayer::Opaque => reg.occludes_face(neighbor, neighbor_orientation, face.opposite()),
+            CubeLayer::Translucent => neighbor == block,
+        }
+    }
+}
 
 pub struct CubeTextureCords {
     pub top: AtlasCords,
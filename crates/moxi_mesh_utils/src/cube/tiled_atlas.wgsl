@@ -0,0 +1,145 @@
+// The shader of the TiledAtlasMaterial: the mesh shader and the StandardMaterial fragment shader,
+// except that the UVs are wrapped into the bounds of the atlas tile of their quad.
+
+#import bevy_pbr::{
+    mesh_functions,
+    forward_io::{VertexOutput, FragmentOutput},
+    view_transformations::position_world_to_clip,
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+    pbr_types::STANDARD_MATERIAL_FLAGS_UNLIT_BIT,
+}
+#import bevy_render::instance_index::get_instance_index
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+#ifdef VERTEX_POSITIONS
+    @location(0) position: vec3<f32>,
+#endif
+#ifdef VERTEX_NORMALS
+    @location(1) normal: vec3<f32>,
+#endif
+#ifdef VERTEX_UVS
+    @location(2) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_TANGENTS
+    @location(4) tangent: vec4<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    @location(5) color: vec4<f32>,
+#endif
+#ifdef VERTEX_TILE_BOUNDS
+    @location(10) tile_bounds: vec4<f32>,
+#endif
+};
+
+struct TiledVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    @location(1) world_normal: vec3<f32>,
+#ifdef VERTEX_UVS
+    @location(2) uv: vec2<f32>,
+#endif
+#ifdef VERTEX_TANGENTS
+    @location(3) world_tangent: vec4<f32>,
+#endif
+#ifdef VERTEX_COLORS
+    @location(4) color: vec4<f32>,
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    @location(5) @interpolate(flat) instance_index: u32,
+#endif
+#ifdef VERTEX_TILE_BOUNDS
+    @location(6) @interpolate(flat) tile_bounds: vec4<f32>,
+#endif
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> TiledVertexOutput {
+    var out: TiledVertexOutput;
+    var model = mesh_functions::get_model_matrix(vertex.instance_index);
+
+#ifdef VERTEX_NORMALS
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(
+        vertex.normal,
+        get_instance_index(vertex.instance_index)
+    );
+#endif
+
+#ifdef VERTEX_POSITIONS
+    out.world_position = mesh_functions::mesh_position_local_to_world(model, vec4<f32>(vertex.position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+#endif
+
+#ifdef VERTEX_UVS
+    out.uv = vertex.uv;
+#endif
+
+#ifdef VERTEX_TANGENTS
+    out.world_tangent = mesh_functions::mesh_tangent_local_to_world(
+        model,
+        vertex.tangent,
+        get_instance_index(vertex.instance_index)
+    );
+#endif
+
+#ifdef VERTEX_COLORS
+    out.color = vertex.color;
+#endif
+
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = get_instance_index(vertex.instance_index);
+#endif
+
+#ifdef VERTEX_TILE_BOUNDS
+    out.tile_bounds = vertex.tile_bounds;
+#endif
+
+#ifdef BASE_INSTANCE_WORKAROUND
+    // See the mesh shader of bevy_pbr, https://github.com/bevyengine/bevy/issues/10509
+    out.position.x += min(f32(get_instance_index(0u)), 0.0);
+#endif
+
+    return out;
+}
+
+@fragment
+fn fragment(
+    tiled: TiledVertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var in: VertexOutput;
+    in.position = tiled.position;
+    in.world_position = tiled.world_position;
+    in.world_normal = tiled.world_normal;
+#ifdef VERTEX_UVS
+    in.uv = tiled.uv;
+#ifdef VERTEX_TILE_BOUNDS
+    // A merged quad spans a tile per block, repeat the tile instead of sampling its neighbors.
+    let tile_min = tiled.tile_bounds.xy;
+    let tile_size = tiled.tile_bounds.zw - tiled.tile_bounds.xy;
+    in.uv = tile_min + fract((tiled.uv - tile_min) / tile_size) * tile_size;
+#endif
+#endif
+#ifdef VERTEX_TANGENTS
+    in.world_tangent = tiled.world_tangent;
+#endif
+#ifdef VERTEX_COLORS
+    in.color = tiled.color;
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    in.instance_index = tiled.instance_index;
+#endif
+
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var out: FragmentOutput;
+    if (pbr_input.material.flags & STANDARD_MATERIAL_FLAGS_UNLIT_BIT) == 0u {
+        out.color = apply_pbr_lighting(pbr_input);
+    } else {
+        out.color = pbr_input.material.base_color;
+    }
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+    return out;
+}
@@ -0,0 +1,112 @@
+//! The quads merged by [`MeshingAlgorithm::Greedy`] cover more than one block, and their UVs span
+//! one atlas tile per block. The bounds of the tile of each quad are kept in a vertex attribute,
+//! so the [`TiledAtlasMaterial`] can repeat the texture inside the tile, instead of sampling the
+//! tiles next to it in the atlas.
+
+use crate::*;
+use bevy_app::{App, Plugin};
+use bevy_asset::{load_internal_asset, Asset, Handle};
+use bevy_pbr::{
+    ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline,
+    MaterialPlugin, StandardMaterial,
+};
+use bevy_reflect::TypePath;
+use bevy_render::mesh::MeshVertexBufferLayout;
+use bevy_render::render_resource::{
+    AsBindGroup, RenderPipelineDescriptor, Shader, ShaderRef, SpecializedMeshPipelineError,
+};
+
+/// The bounds of the atlas tile of the quad a vertex belongs to: (min u, min v, max u, max v).
+/// Only the meshes generated with [`MeshingAlgorithm::Greedy`] have it.
+pub const ATTRIBUTE_TILE_BOUNDS: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_TileBounds", 862_304_517, VertexFormat::Float32x4);
+
+/// The location of [`ATTRIBUTE_TILE_BOUNDS`] in the vertex shader of the [`TiledAtlasMaterial`].
+const TILE_BOUNDS_SHADER_LOCATION: u32 = 10;
+
+const TILED_ATLAS_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(101_563_289_740_127_995_124_870_615_278_804_212_301);
+
+/// A [`StandardMaterial`] that repeats the texture of each quad inside its atlas tile, see
+/// [`TiledAtlas`].
+pub type TiledAtlasMaterial = ExtendedMaterial<StandardMaterial, TiledAtlas>;
+
+/// The extension of the [`TiledAtlasMaterial`]. The UVs of the meshes that have
+/// [`ATTRIBUTE_TILE_BOUNDS`] are wrapped into the bounds of their tile, the rest of the meshes are
+/// drawn like they are with the [`StandardMaterial`]. The texture is sampled as usual otherwise,
+/// so it shouldn't be mip-mapped (the seams between the repeated tiles would be blurred), and only
+/// the forward renderer wraps the UVs.
+#[derive(Asset, TypePath, AsBindGroup, Clone, Debug, Default)]
+pub struct TiledAtlas {}
+
+impl MaterialExtension for TiledAtlas {
+    fn vertex_shader() -> ShaderRef {
+        TILED_ATLAS_SHADER_HANDLE.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        TILED_ATLAS_SHADER_HANDLE.into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        _key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if !layout.contains(ATTRIBUTE_TILE_BOUNDS) {
+            return Ok(());
+        }
+        let tile_bounds = layout
+            .get_layout(&[ATTRIBUTE_TILE_BOUNDS.at_shader_location(TILE_BOUNDS_SHADER_LOCATION)])?;
+        descriptor.vertex.buffers[0]
+            .attributes
+            .extend(tile_bounds.attributes);
+        descriptor
+            .vertex
+            .shader_defs
+            .push("VERTEX_TILE_BOUNDS".into());
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader_defs.push("VERTEX_TILE_BOUNDS".into());
+        }
+        Ok(())
+    }
+}
+
+/// Adds the [`TiledAtlasMaterial`].
+pub struct TiledAtlasMaterialPlugin;
+
+impl Plugin for TiledAtlasMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            TILED_ATLAS_SHADER_HANDLE,
+            "tiled_atlas.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(MaterialPlugin::<TiledAtlasMaterial>::default());
+    }
+}
+
+/// The tile bounds of the vertices of the block mesh, which are sorted by the quads they are in
+/// (4 vertices each). The bounds of a quad are the smallest and largest UVs of its vertices.
+pub(crate) fn tile_bounds(block_mesh: &Mesh, vertices: &[u32]) -> VertexAttributeValues {
+    let Some(VertexAttributeValues::Float32x2(uvs)) = block_mesh.attribute(Mesh::ATTRIBUTE_UV_0)
+    else {
+        panic!("Unexpected vertex format for the uv attribute, expected Float32x2.");
+    };
+    let mut bounds = Vec::with_capacity(vertices.len());
+    for quad in vertices.chunks(4) {
+        let (min, max) = quad.iter().map(|v| uvs[*v as usize]).fold(
+            ([f32::MAX; 2], [f32::MIN; 2]),
+            |(min, max), [u, v]| {
+                (
+                    [min[0].min(u), min[1].min(v)],
+                    [max[0].max(u), max[1].max(v)],
+                )
+            },
+        );
+        bounds.resize(bounds.len() + quad.len(), [min[0], min[1], max[0], max[1]]);
+    }
+    VertexAttributeValues::Float32x4(bounds)
+}
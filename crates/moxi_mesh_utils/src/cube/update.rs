@@ -1,17 +1,23 @@
 use crate::*;
+use std::borrow::Cow;
 
+/// Applies the changes logged in the metadata to the mesh. `orientation_at` is the orientation of
+/// the block at a position, as in [`meshify_cubic_voxels`].
 pub fn update_cube_mesh<B: BlockInGrid>(
     mesh: &mut Mesh,
     metadata: &mut CubeMD<B>,
     reg: &impl MeshRegistry<B>,
+    orientation_at: impl Fn(BlockPos) -> Orientation,
 ) {
+    let center = reg.get_block_center();
     let mut min = usize::MAX;
     let mut max = usize::MIN;
     let voxel_dims = reg.get_block_dims();
+    let layer = metadata.layer;
     for (block, block_pos, change, surrounding_blocks) in metadata
         .changed_voxels
         .iter()
-        .filter(|(block, ..)| reg.is_cube(block))
+        .filter(|(block, ..)| layer.contains(reg, block))
     {
         let block_index = pos_to_index(*block_pos, metadata.dims).unwrap();
         if block_index < min {
@@ -25,28 +31,58 @@ pub fn update_cube_mesh<B: BlockInGrid>(
             block_pos.y as f32 * voxel_dims[1],
             block_pos.z as f32 * voxel_dims[2],
         );
+        let orientation_of_neighbor = |face: Face| {
+            neighbor_pos(*block_pos, face, metadata.dims)
+                .map_or(Orientation::default(), &orientation_at)
+        };
         let cube_neighbors: [bool; 6] = FACES
             .iter()
-            .map(|face| surrounding_blocks[*face].map_or(false, |b| reg.is_cube(&b)))
+            .map(|face| {
+                surrounding_blocks[*face].is_some_and(|b| {
+                    layer.is_face_culled(reg, block, *face, &b, orientation_of_neighbor(*face))
+                })
+            })
             .collect::<Vec<bool>>()
             .try_into()
             .unwrap();
 
-        let surrounding_block_meshes: Vec<(Face, &Mesh)> = {
-            let mut r: Vec<(Face, &Mesh)> = vec![];
+        let surrounding_block_meshes: Vec<(Face, Cow<Mesh>)> = {
+            let mut r: Vec<(Face, Cow<Mesh>)> = vec![];
             for face in FACES.iter() {
                 let neighbor = surrounding_blocks[*face];
                 match neighbor {
                     None => continue,
-                    Some(t) if reg.is_cube(&t) => {
-                        r.push((*face, reg.get_block_mesh_ref(&t).unwrap()));
+                    // The neighbors whose faces could have been hidden by the block, the faces that
+                    // weren't hidden are already in the mesh, so they won't be added again.
+                    Some(t) if layer.contains(reg, &t) => {
+                        let orientation = orientation_of_neighbor(*face);
+                        r.push((
+                            *face,
+                            oriented_mesh(reg.get_block_mesh_ref(&t).unwrap(), orientation, center),
+                        ));
                     }
                     _ => continue,
                 }
             }
             r
         };
-        let block_mesh = reg.get_block_mesh_ref(block).unwrap();
+        let block_mesh = oriented_mesh(
+            reg.get_block_mesh_ref(block).unwrap(),
+            orientation_at(*block_pos),
+            center,
+        );
+
+        if !metadata.vivi.merged.is_empty() {
+            split_merged_quads(
+                mesh,
+                &mut metadata.vivi,
+                &metadata.merged_blocks,
+                reg,
+                *block_pos,
+                metadata.dims,
+                &orientation_at,
+            );
+        }
 
         match *change {
             BlockMeshChange::Added => {
@@ -60,7 +96,7 @@ pub fn update_cube_mesh<B: BlockInGrid>(
                 add_voxel_after_gen(
                     cube_neighbors,
                     mesh,
-                    block_mesh,
+                    &block_mesh,
                     &mut metadata.vivi,
                     *block_pos,
                     reg.get_block_center(),
@@ -87,7 +123,10 @@ pub fn update_cube_mesh<B: BlockInGrid>(
                     mesh,
                     &mut metadata.vivi,
                     *block_pos,
-                    surrounding_block_meshes,
+                    surrounding_block_meshes
+                        .iter()
+                        .map(|(face, mesh)| (*face, mesh.as_ref()))
+                        .collect(),
                     reg.get_block_center(),
                     reg.get_block_dims(),
                     metadata.dims,
@@ -106,7 +145,7 @@ pub fn update_cube_mesh<B: BlockInGrid>(
                 add_voxel_after_gen(
                     cube_neighbors,
                     mesh,
-                    block_mesh,
+                    &block_mesh,
                     &mut metadata.vivi,
                     *block_pos,
                     reg.get_block_center(),
@@ -120,6 +159,122 @@ pub fn update_cube_mesh<B: BlockInGrid>(
     metadata.changed_voxels.clear();
 }
 
+/// Sort the quads of a cubic chunk mesh from the furthest to the closest to `view_pos` (relative
+/// to the chunk), so the quads of a [`translucent`](`CubeLayer::Translucent`) mesh are blended
+/// back to front when they are viewed from around `view_pos`. The quads are moved in the mesh, and
+/// the metadata is updated accordingly.
+pub fn sort_quads_back_to_front<B: BlockInGrid>(
+    mesh: &mut Mesh,
+    metadata: &mut CubeMD<B>,
+    view_pos: Vec3,
+) {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return;
+    };
+    let quad_distance = |quad: u32| {
+        let quad = quad as usize;
+        let center = positions[quad..quad + 4]
+            .iter()
+            .fold(Vec3::ZERO, |sum, pos| sum + Vec3::from(*pos))
+            / 4.0;
+        center.distance_squared(view_pos)
+    };
+    let mut quads: Vec<(u32, f32)> = (0..positions.len() as u32)
+        .step_by(4)
+        .map(|quad| (quad, quad_distance(quad)))
+        .collect();
+    quads.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    let Some(Indices::U32(indices)) = mesh.indices() else {
+        panic!("Expected U32 indices format");
+    };
+    let mut quad_triangles: Vec<Vec<u32>> = vec![vec![]; quads.len()];
+    for triangle in indices.chunks(3) {
+        quad_triangles[triangle[0] as usize / 4].extend_from_slice(triangle);
+    }
+    let mut new_vertices = HashMap::new();
+    let mut vertices_order = Vec::with_capacity(quads.len() * 4);
+    let mut new_indices = Vec::with_capacity(indices.len());
+    for (new_quad, (quad, _)) in quads.iter().enumerate() {
+        let new_quad = new_quad as u32 * 4;
+        new_vertices.insert(*quad, new_quad);
+        vertices_order.extend(*quad..*quad + 4);
+        new_indices.extend(
+            quad_triangles[*quad as usize / 4]
+                .iter()
+                .map(|i| i - quad + new_quad),
+        );
+    }
+
+    for (_, vals) in mesh.attributes_mut() {
+        *vals = vals.get_needed(&vertices_order);
+    }
+    mesh.set_indices(Some(Indices::U32(new_indices)));
+    metadata.vivi.move_quads(&new_vertices);
+}
+
+/// Split the merged quads (see [`MeshingAlgorithm::Greedy`]) that cover the block, or any of its
+/// neighbors, back into a quad per block. That way, the usual updating logic can remove / add
+/// the quads of these blocks individually.
+fn split_merged_quads<B: BlockInGrid>(
+    mesh: &mut Mesh,
+    vivi: &mut CubeVIVI,
+    merged_blocks: &HashMap<u32, B>,
+    reg: &impl MeshRegistry<B>,
+    block_pos: BlockPos,
+    dims: Dimensions,
+    orientation_at: &impl Fn(BlockPos) -> Orientation,
+) {
+    let voxel_dims = reg.get_block_dims();
+    let blocks_to_split = std::iter::once(block_pos).chain(
+        FACES
+            .iter()
+            .filter_map(|face| neighbor_pos(block_pos, *face, dims)),
+    );
+    for pos in blocks_to_split {
+        let block_index = pos_to_index(pos, dims).unwrap();
+        for face in FACES {
+            let Some((owner, members)) = vivi.get_merged_quad(face, block_index) else {
+                continue;
+            };
+            let covered_voxels: Vec<u32> = std::iter::once(owner).chain(members.clone()).collect();
+            let block = merged_blocks
+                .get(&owner)
+                .expect("Couldn't find the block of a merged quad");
+            let owner_pos = index_to_pos(owner as usize, dims).unwrap();
+            // Only blocks with the same orientation are merged
+            let block_mesh = oriented_mesh(
+                reg.get_block_mesh_ref(block).unwrap(),
+                orientation_at(owner_pos),
+                reg.get_block_center(),
+            );
+
+            let mut quad_to_remove = [false; 6];
+            quad_to_remove[face as usize] = true;
+            remove_voxel(mesh, vivi, owner_pos, quad_to_remove, dims);
+
+            let mut quads_to_keep = [true; 6];
+            quads_to_keep[face as usize] = false;
+            for voxel in covered_voxels {
+                let voxel_pos = index_to_pos(voxel as usize, dims).unwrap();
+                let position_offset = (Vec3::from(voxel_dims) * voxel_pos.as_vec3()).into();
+                add_voxel_after_gen(
+                    quads_to_keep,
+                    mesh,
+                    &block_mesh,
+                    vivi,
+                    voxel_pos,
+                    reg.get_block_center(),
+                    position_offset,
+                    dims,
+                );
+            }
+        }
+    }
+}
+
 // The function removes all quads facing a voxel.
 fn remove_quads_facing(
     mesh: &mut Mesh,
@@ -373,6 +528,10 @@ fn add_voxel_after_gen(
     indices_main.extend(indices_to_save);
 
     for (id, vals) in main_mesh.attributes_mut() {
+        if id == ATTRIBUTE_TILE_BOUNDS.id {
+            vals.extend(&tile_bounds(block_mesh, &final_vertices));
+            continue;
+        }
         let mut att = block_mesh
             .attribute(id)
             .expect(format!("Couldn't retrieve voxel mesh attribute {:?}.", id).as_str())
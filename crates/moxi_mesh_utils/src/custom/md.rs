@@ -3,7 +3,9 @@ use crate::*;
 
 /// A struct data structure specifically for `custom` chunk meshes (made up of [`BlockMeshType::Custom`] blocks) that
 /// keeps track of which vertices and indices belong to which block. This is used for updating the mesh at run-time.
-pub(crate) type CustomVIVI = HashMap<BlockPos, (VertexIndex, IndexIndex)>;
+/// The faces of a block that are hidden by its neighbors aren't in the mesh, so the amount of
+/// indices of each block is kept too.
+pub(crate) type CustomVIVI = HashMap<BlockPos, (VertexIndex, IndexIndex, IndexCount)>;
 
 /// Mesh meta-data struct for xsprite meshes (made up of [`BlockMeshType::XSprite`]).
 /// Holds all the information needed to update the mesh at run-time.
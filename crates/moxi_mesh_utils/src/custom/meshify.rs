@@ -2,10 +2,14 @@ use super::md::{CustomMD, CustomVIVI};
 use super::*;
 use crate::*;
 
-/// Meshify all of the [`custom`](`BlockMeshType::Custom`) blocks in a chunk grid.
+/// Meshify all of the [`custom`](`BlockMeshType::Custom`) blocks in a chunk grid. The mesh of each
+/// block is rotated by its orientation (`orientation_at`), see [`oriented_mesh`]. The triangles
+/// that lie on a face of the block are left out if the neighbor on that side hides the face (see
+/// [`BlockShape`]).
 pub fn meshify_custom_voxels<B: BlockInGrid, const N: usize>(
     reg: &impl MeshRegistry<B>,
     grid: &Grid<B, N>,
+    orientation_at: impl Fn(BlockPos) -> Orientation,
 ) -> (Mesh, CustomMD<B>) {
     let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
 
@@ -29,6 +33,11 @@ pub fn meshify_custom_voxels<B: BlockInGrid, const N: usize>(
     });
 
     for (block_pos, custom_mesh) in enumerated_custom_meshes {
+        let custom_mesh = oriented_mesh(
+            custom_mesh,
+            orientation_at(block_pos),
+            reg.get_block_center(),
+        );
         let total_vertices = vertices[0].1.len();
         let total_indices = indices.len();
 
@@ -38,11 +47,13 @@ pub fn meshify_custom_voxels<B: BlockInGrid, const N: usize>(
             block_pos.z as f32 * voxel_dims[2],
         );
 
-        // Offset and add the indices to the total indices.
-        let Indices::U32(ind) = custom_mesh.indices().expect("couldn't get indices data") else {
-            panic!("Expected U32 indices format");
-        };
-        let ind: Vec<u32> = ind.iter().map(|i| *i + total_vertices as u32).collect();
+        // Offset and add the visible indices to the total indices.
+        let hidden_faces = hidden_faces(reg, grid, block_pos, &orientation_at);
+        let ind: Vec<u32> = visible_indices(reg, &custom_mesh, hidden_faces)
+            .iter()
+            .map(|i| *i + total_vertices as u32)
+            .collect();
+        let indices_count = ind.len();
         // Add the vertices to the total vertices, offset the position attribute.
         for (id, vals) in vertices.iter_mut() {
             let mut att = custom_mesh
@@ -58,7 +69,11 @@ pub fn meshify_custom_voxels<B: BlockInGrid, const N: usize>(
 
         vivi.insert(
             block_pos,
-            (total_vertices as VertexIndex, total_indices as IndexIndex),
+            (
+                total_vertices as VertexIndex,
+                total_indices as IndexIndex,
+                indices_count as IndexCount,
+            ),
         );
     }
 
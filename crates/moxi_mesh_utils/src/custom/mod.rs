@@ -5,8 +5,70 @@ mod update;
 
 pub(self) type VertexIndex = u32;
 pub(self) type IndexIndex = u32;
+type IndexCount = u32;
 
 pub use gen::*;
 pub use md::*;
 pub use meshify::*;
 pub use update::*;
+
+use crate::*;
+
+/// The faces of the custom block at `block_pos` that are hidden by the neighbors inside the grid
+/// (see [`MeshRegistryCommon::occludes_face`]).
+pub(crate) fn hidden_faces<B: BlockInGrid>(
+    reg: &impl MeshRegistry<B>,
+    grid: &impl BlockGrid<B>,
+    block_pos: BlockPos,
+    orientation_at: &impl Fn(BlockPos) -> Orientation,
+) -> [bool; 6] {
+    FACES.map(|face| {
+        neighbor_pos(block_pos, face, grid.dims()).is_some_and(|neighbor_pos| {
+            grid.get_block(neighbor_pos).is_some_and(|neighbor| {
+                reg.occludes_face(&neighbor, orientation_at(neighbor_pos), face.opposite())
+            })
+        })
+    })
+}
+
+/// The indices of the triangles of a custom block mesh that aren't hidden. A triangle is hidden
+/// if it lies on a hidden face of the block.
+pub(crate) fn visible_indices<B: BlockInGrid>(
+    reg: &impl MeshRegistry<B>,
+    block_mesh: &Mesh,
+    hidden_faces: [bool; 6],
+) -> Vec<u32> {
+    let Some(Indices::U32(indices)) = block_mesh.indices() else {
+        panic!("Expected U32 indices format");
+    };
+    if hidden_faces == [false; 6] {
+        return indices.clone();
+    }
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        block_mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("Unexpected vertex format for position attribute, expected Float32x3.");
+    };
+    let (center, dims) = (reg.get_block_center(), reg.get_block_dims());
+    let lies_on_face = |triangle: &[u32], face: Face| {
+        let axis = match face {
+            Face::Right | Face::Left => 0,
+            Face::Top | Face::Bottom => 1,
+            Face::Back | Face::Front => 2,
+        };
+        let plane = center[axis] + face.normal()[axis] as f32 * dims[axis] / 2.0;
+        triangle
+            .iter()
+            .all(|i| (positions[*i as usize][axis] - plane).abs() < 1e-4)
+    };
+    indices
+        .chunks(3)
+        .filter(|triangle| {
+            !FACES
+                .iter()
+                .any(|face| hidden_faces[*face as usize] && lies_on_face(triangle, *face))
+        })
+        .flatten()
+        .copied()
+        .collect()
+}
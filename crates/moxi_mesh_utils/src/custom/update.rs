@@ -3,28 +3,56 @@ use super::*;
 use crate::*;
 
 /// Update the [`custom`](`BlockMeshType::Custom`) chunk mesh according to the metadata.
+/// `orientation_at` is the orientation of the block at a position, and `grid` is the chunk's
+/// grid (after the changes), as in [`meshify_custom_voxels`].
 pub fn update_custom_mesh<B: BlockInGrid>(
     reg: &impl MeshRegistry<B>,
     mesh: &mut Mesh,
     md: &mut CustomMD<B>,
+    grid: &impl BlockGrid<B>,
+    orientation_at: impl Fn(BlockPos) -> Orientation,
 ) {
     for (change, block, block_pos) in md.log.iter().filter(|(_, block, _)| reg.is_custom(block)) {
         match change {
-            BlockMeshChange::Added => add_custom_block(
-                mesh,
-                &mut md.vivi,
-                *block_pos,
-                reg.get_block_mesh_ref(block).unwrap(),
-                reg.get_block_dims().into(),
-            ),
-            BlockMeshChange::Broken => {
-                remove_custom_voxel(
+            BlockMeshChange::Added => {
+                // A block can be added again to hide (or show) its faces, after its neighbors
+                // changed.
+                if md.vivi.contains_key(block_pos) {
+                    remove_custom_voxel(
+                        mesh,
+                        &mut md.vivi,
+                        *block_pos,
+                        reg.get_block_mesh_vertex_count(block),
+                    );
+                }
+                let block_mesh = oriented_mesh(
+                    reg.get_block_mesh_ref(block).unwrap(),
+                    orientation_at(*block_pos),
+                    reg.get_block_center(),
+                );
+                let indices = visible_indices(
+                    reg,
+                    &block_mesh,
+                    hidden_faces(reg, grid, *block_pos, &orientation_at),
+                );
+                add_custom_block(
                     mesh,
                     &mut md.vivi,
                     *block_pos,
-                    reg.get_block_mesh_vertex_count(block),
-                    reg.get_block_mesh_indices_len(block),
-                );
+                    &block_mesh,
+                    indices,
+                    reg.get_block_dims().into(),
+                )
+            }
+            BlockMeshChange::Broken => {
+                if md.vivi.contains_key(block_pos) {
+                    remove_custom_voxel(
+                        mesh,
+                        &mut md.vivi,
+                        *block_pos,
+                        reg.get_block_mesh_vertex_count(block),
+                    );
+                }
             }
             _ => debug_assert!(
                 false,
@@ -41,9 +69,8 @@ fn remove_custom_voxel(
     vivi: &mut CustomVIVI,
     block_pos: BlockPos,
     vertex_count: usize,
-    indices_count: usize,
 ) {
-    let (vertex_start, index_start) = vivi.remove(&block_pos).unwrap();
+    let (vertex_start, index_start, indices_count) = vivi.remove(&block_pos).unwrap();
     // remove vertices
     for (_, vav) in mesh.attributes_mut() {
         for vertex in (vertex_start as usize..(vertex_start as usize + vertex_count)).rev() {
@@ -52,29 +79,38 @@ fn remove_custom_voxel(
     }
     // remove indices & offset the rest
     if let Some(Indices::U32(ref mut indices)) = mesh.indices_mut() {
-        for index in (index_start as usize..(index_start as usize + indices_count)).rev() {
+        for index in (index_start as usize..(index_start + indices_count) as usize).rev() {
             indices.remove(index);
         }
 
         // offset the indices that were affected by removing `vertex_count` amount of vertices
         for index in indices.iter_mut() {
-            if *index > vertex_start as u32 {
+            if *index > vertex_start {
                 *index -= vertex_count as u32;
             }
         }
     }
+
+    // the blocks that came after the removed block moved back
+    for (block_vertex_start, block_index_start, _) in vivi.values_mut() {
+        if *block_vertex_start > vertex_start {
+            *block_vertex_start -= vertex_count as VertexIndex;
+            *block_index_start -= indices_count;
+        }
+    }
 }
 
-/// Add an [`custom`](`BlockMeshType::custom`) block to the mesh.
+/// Add an [`custom`](`BlockMeshType::custom`) block to the mesh, with the given (visible) indices
+/// of the block's mesh.
 fn add_custom_block(
     mesh: &mut Mesh,
     vivi: &mut CustomVIVI,
     block_pos: BlockPos,
     voxel_mesh: &Mesh,
+    voxel_indices: Vec<u32>,
     voxel_dims: Vec3,
 ) {
     let ver_count = mesh.count_vertices();
-    let indices_count;
 
     let position_offset = voxel_dims * block_pos.as_vec3();
     // add the vertices
@@ -95,19 +131,17 @@ fn add_custom_block(
     let Some(Indices::U32(ref mut indices)) = mesh.indices_mut() else {
         panic!("Expected U32 indices format");
     };
-    indices_count = indices.len();
-    if let Some(Indices::U32(voxel_indices)) = voxel_mesh.indices() {
-        let indices_offset: Vec<u32> = voxel_indices
-            .clone()
-            .iter()
-            .map(|x| *x + ver_count as u32)
-            .collect();
-        indices.extend(indices_offset);
-    }
+    let indices_start = indices.len();
+    let indices_count = voxel_indices.len();
+    indices.extend(voxel_indices.iter().map(|x| *x + ver_count as u32));
 
     // add the block to the VIVI
     vivi.insert(
         block_pos,
-        (ver_count as VertexIndex, indices_count as IndexIndex),
+        (
+            ver_count as VertexIndex,
+            indices_start as IndexIndex,
+            indices_count as IndexCount,
+        ),
     );
 }
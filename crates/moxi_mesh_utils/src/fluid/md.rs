@@ -0,0 +1,42 @@
+use crate::*;
+
+/// A data structure specifically for `fluid` chunk meshes (made up of [`BlockMeshType::Fluid`]
+/// blocks) that keeps track of which vertices belong to which block: (first vertex, vertex count).
+pub(crate) type FluidVIVI = HashMap<BlockPos, (u32, u32)>;
+
+/// Mesh meta-data struct for fluid meshes (made up of [`BlockMeshType::Fluid`]).
+/// The surface of a fluid block depends on the levels of the fluid blocks around it, so instead of
+/// updating the mesh block by block, the changes are logged and the mesh is re-generated with
+/// [`meshify_fluid_voxels`](`super::meshify_fluid_voxels`) when it's [`outdated`](`FluidMD::is_outdated`).
+pub struct FluidMD<B: BlockInGrid> {
+    pub(crate) vivi: FluidVIVI,
+    pub(crate) log: Vec<(BlockMeshChange, B, BlockPos)>,
+}
+
+impl<B: BlockInGrid> FluidMD<B> {
+    /// Log a block break
+    pub fn log_break(&mut self, block: B, pos: BlockPos) {
+        self.log.push((BlockMeshChange::Broken, block, pos));
+    }
+
+    /// Log a block add
+    pub fn log_add(&mut self, block: B, pos: BlockPos) {
+        self.log.push((BlockMeshChange::Added, block, pos));
+    }
+
+    /// Log a change that reshapes a fluid block without adding or breaking it, like a change in
+    /// its level or in one of its neighbors.
+    pub fn log_update(&mut self, block: B, pos: BlockPos) {
+        self.log.push((BlockMeshChange::AddFaces, block, pos));
+    }
+
+    /// Whether the mesh has no fluid blocks.
+    pub fn is_empty(&self) -> bool {
+        self.vivi.is_empty()
+    }
+
+    /// Whether there were changes since the mesh was generated.
+    pub fn is_outdated(&self) -> bool {
+        !self.log.is_empty()
+    }
+}
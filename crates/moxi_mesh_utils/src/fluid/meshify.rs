@@ -0,0 +1,230 @@
+use super::md::{FluidMD, FluidVIVI};
+use super::*;
+use crate::*;
+use bevy_math::IVec3;
+
+/// Meshify all of the [`fluid`](`BlockMeshType::Fluid`) blocks in a chunk grid. `level_at` returns
+/// the level of the fluid block at a position, and `outside_at` returns the block and its level at
+/// a position outside of the chunk (up to one block away from its edges), or `None` if the chunk of
+/// the position isn't loaded. The surface of a fluid block is lowered to the
+/// [`height`](`fluid_height`) of its level, and its corners are shared with the neighboring blocks of
+/// the same fluid, so the surface slopes between blocks with different levels. A fluid block with
+/// the same fluid above it is full, and so are the corners it touches.
+///
+/// Faces between blocks of the same fluid, or facing a [`cube`](`BlockMeshType::Cube`), are
+/// culled (except for the surface, which is only covered by the same fluid), also across the
+/// borders of the chunk.
+pub fn meshify_fluid_voxels<B: BlockInGrid>(
+    reg: &impl MeshRegistry<B>,
+    grid: &impl BlockGrid<B>,
+    level_at: impl Fn(BlockPos) -> u8,
+    outside_at: impl Fn(IVec3) -> Option<(B, u8)>,
+) -> (Mesh, FluidMD<B>) {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+
+    let mut indices: Vec<u32> = vec![];
+    let mut vertices: Vec<(MeshVertexAttribute, VertexAttributeValues)> = vec![];
+    for att in reg.all_attributes().iter() {
+        vertices.push((att.clone(), VertexAttributeValues::new(att.format)));
+    }
+
+    let mut vivi = FluidVIVI::new();
+    let voxel_dims = reg.get_block_dims();
+    let center = reg.get_block_center();
+    let bottom = center[1] - voxel_dims[1] / 2.0;
+    let dims = grid.dims().as_ivec3();
+    let inside = |pos: IVec3| pos.cmpge(IVec3::ZERO).all() && pos.cmplt(dims).all();
+    let block_at = |pos: IVec3| {
+        if inside(pos) {
+            grid.get_block(pos.as_uvec3())
+        } else {
+            outside_at(pos).map(|(block, _)| block)
+        }
+    };
+    let fluid_level_at = |pos: IVec3| {
+        if inside(pos) {
+            level_at(pos.as_uvec3())
+        } else {
+            outside_at(pos).map_or(MAX_FLUID_LEVEL, |(_, level)| level)
+        }
+    };
+
+    for (block_pos, block) in grid.enumerate_blocks() {
+        let Some(fluid_mesh) = reg.get_block_mesh_ref(&block).get_if(BlockMeshType::Fluid) else {
+            continue;
+        };
+        let pos = block_pos.as_ivec3();
+        let covered = block_at(pos + IVec3::Y) == Some(block);
+        // The height of the corner of the surface that is between the block and its neighbors in
+        // the direction of (dx, dz).
+        let corner_height = |dx: i32, dz: i32| {
+            let (mut sum, mut count) = (0.0, 0);
+            for x in dx - 1..=dx {
+                for z in dz - 1..=dz {
+                    let cell = pos + IVec3::new(x, 0, z);
+                    if block_at(cell) != Some(block) {
+                        continue;
+                    }
+                    if block_at(cell + IVec3::Y) == Some(block) {
+                        return 1.0;
+                    }
+                    sum += fluid_height(fluid_level_at(cell));
+                    count += 1;
+                }
+            }
+            sum / count as f32
+        };
+        let heights = [
+            [corner_height(0, 0), corner_height(0, 1)],
+            [corner_height(1, 0), corner_height(1, 1)],
+        ];
+
+        let first_vertex = vertices[0].1.len();
+        let position_offset = Vec3::from(voxel_dims) * block_pos.as_vec3();
+        for face in FACES {
+            let culled = match face {
+                Face::Top => covered,
+                _ => block_at(pos + face.normal())
+                    .is_some_and(|neighbor| neighbor == block || reg.is_cube(&neighbor)),
+            };
+            if culled {
+                continue;
+            }
+            let face_vertices: Vec<u32> = (face as u32 * 4..face as u32 * 4 + 4).collect();
+            let base = vertices[0].1.len() as u32;
+            for (id, vals) in vertices.iter_mut() {
+                let mut att = fluid_mesh
+                    .attribute(id.id)
+                    .unwrap_or_else(|| panic!("Couldn't retrieve voxel mesh attribute {:?}.", id))
+                    .get_needed(&face_vertices);
+                if id.id == Mesh::ATTRIBUTE_POSITION.id {
+                    if let VertexAttributeValues::Float32x3(ref mut positions) = att {
+                        for [x, y, z] in positions.iter_mut() {
+                            if !covered && *y > center[1] {
+                                let (cx, cz) =
+                                    ((*x > center[0]) as usize, (*z > center[2]) as usize);
+                                *y = bottom + heights[cx][cz] * voxel_dims[1];
+                            }
+                        }
+                    }
+                    att = att.offset_all(position_offset.into());
+                }
+                vals.extend(&att);
+            }
+            indices.extend([0, 1, 3, 2, 3, 1].map(|i| base + i));
+        }
+        vivi.insert(
+            block_pos,
+            (
+                first_vertex as u32,
+                (vertices[0].1.len() - first_vertex) as u32,
+            ),
+        );
+    }
+
+    for (att, vals) in vertices {
+        mesh.insert_attribute(att, vals);
+    }
+    mesh.set_indices(Some(Indices::U32(indices)));
+    (mesh, FluidMD { vivi, log: vec![] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_asset::Handle;
+
+    struct TestReg(Vec<BlockMesh>);
+
+    impl MeshRegistry<BlockId> for TestReg {
+        fn get_block_mesh_ref(&self, block: &BlockId) -> BlockMeshRef<'_> {
+            self.0[*block as usize].as_ref()
+        }
+
+        fn get_block_mesh_handle(&self, _block: &BlockId) -> Handle<Mesh> {
+            Handle::default()
+        }
+
+        fn get_block_mesh_type(&self, block: &BlockId) -> BlockMeshType {
+            self.0[*block as usize].get_type()
+        }
+    }
+
+    #[test]
+    fn test_fluid_surface_slopes() {
+        let cube = generate_cube_mesh(
+            [1.0; 3],
+            [4, 4],
+            CubeTextureCords::uniform([0, 0]),
+            [0.0; 3],
+            0.0,
+            Some(1.0),
+            1.0,
+        );
+        let reg = TestReg(vec![BlockMesh::Air, cube.clone(), cube.into_fluid()]);
+        // A source block next to a flowing block, on top of stone.
+        let dims = Dimensions::new(2, 2, 1);
+        let mut grid = Grid::<BlockId, 4>::new([1; 4], dims);
+        grid.set_block(2, [0, 1, 0].into()).unwrap();
+        grid.set_block(2, [1, 1, 0].into()).unwrap();
+        let levels = |pos: BlockPos| if pos.x == 0 { MAX_FLUID_LEVEL } else { 4 };
+
+        let (mesh, md) = meshify_fluid_voxels(&reg, &grid, levels, |_| None);
+        // The faces between the fluid blocks, and facing the stone, are culled.
+        assert_eq!(mesh.count_vertices(), 2 * 4 * 4);
+        assert_eq!(md.vivi.len(), 2);
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("Expected Float32x3 positions");
+        };
+        let surface_height = |x: f32| {
+            positions
+                .iter()
+                .filter(|pos| pos[0] == x && pos[1] > 0.5)
+                .map(|pos| pos[1])
+                .fold(f32::MIN, f32::max)
+        };
+        let source = fluid_height(MAX_FLUID_LEVEL) + 0.5;
+        let flowing = fluid_height(4) + 0.5;
+        let shared = (fluid_height(MAX_FLUID_LEVEL) + fluid_height(4)) / 2.0 + 0.5;
+        assert!((surface_height(-0.5) - source).abs() < 1e-5);
+        assert!((surface_height(0.5) - shared).abs() < 1e-5);
+        assert!((surface_height(1.5) - flowing).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_fluid_across_chunks() {
+        let cube = generate_cube_mesh(
+            [1.0; 3],
+            [4, 4],
+            CubeTextureCords::uniform([0, 0]),
+            [0.0; 3],
+            0.0,
+            Some(1.0),
+            1.0,
+        );
+        let reg = TestReg(vec![BlockMesh::Air, cube.clone(), cube.into_fluid()]);
+        // A single flowing block, with a source block in the chunk to its right.
+        let grid = Grid::<BlockId, 1>::new([2], Dimensions::new(1, 1, 1));
+        let outside_at = |pos: IVec3| (pos == IVec3::X).then_some((2, MAX_FLUID_LEVEL));
+
+        let (mesh, _) = meshify_fluid_voxels(&reg, &grid, |_| 4, |_| None);
+        assert_eq!(mesh.count_vertices(), 6 * 4);
+        let (mesh, _) = meshify_fluid_voxels(&reg, &grid, |_| 4, outside_at);
+        // The face towards the other chunk is culled, and the surface slopes up towards it.
+        assert_eq!(mesh.count_vertices(), 5 * 4);
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("Expected Float32x3 positions");
+        };
+        let shared = (fluid_height(MAX_FLUID_LEVEL) + fluid_height(4)) / 2.0 - 0.5;
+        let border_height = positions
+            .iter()
+            .filter(|pos| pos[0] > 0.0)
+            .map(|pos| pos[1])
+            .fold(f32::MIN, f32::max);
+        assert!((border_height - shared).abs() < 1e-5);
+    }
+}
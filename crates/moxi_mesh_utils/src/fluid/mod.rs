@@ -0,0 +1,15 @@
+mod md;
+mod meshify;
+
+pub use md::*;
+pub use meshify::*;
+
+/// The level of a fluid source block, the highest level a fluid can have. Flowing fluid has a
+/// lower level, and a fluid with a level of 0 dries up.
+pub const MAX_FLUID_LEVEL: u8 = 8;
+
+/// The height of the surface of a fluid block with the given level, relative to the height of a
+/// block. Even a source block is a little lower than a full block.
+pub fn fluid_height(level: u8) -> f32 {
+    level.min(MAX_FLUID_LEVEL) as f32 / (MAX_FLUID_LEVEL + 1) as f32
+}
@@ -17,7 +17,10 @@ mod adj;
 mod block_mesh;
 mod cube;
 mod custom;
+mod fluid;
+mod light;
 mod mesh_reg;
+mod shape;
 mod sl;
 mod vav_utils;
 mod xsprite;
@@ -25,6 +28,7 @@ mod xsprite;
 pub(crate) use block_mesh::*;
 pub(crate) use cube::*;
 pub(crate) use mesh_reg::*;
+pub(crate) use shape::*;
 pub(crate) use sl::*;
 pub(crate) use vav_utils::*;
 
@@ -43,7 +47,10 @@ pub mod prelude {
     pub use super::block_mesh::*;
     pub use super::cube::*;
     pub use super::custom::*;
+    pub use super::fluid::*;
+    pub use super::light::*;
     pub use super::mesh_reg::*;
+    pub use super::shape::*;
     pub use super::sl::*;
     pub use super::xsprite::*;
     pub use bevy_render::primitives::Aabb;
@@ -0,0 +1,169 @@
+//! This module is responsible for baking the voxel light (see [`LightGrid`]) into the vertex
+//! colors of the chunk meshes. The color of every vertex is the color of the block mesh, darkened
+//! by the light level of the block the vertex is lit by.
+
+use crate::*;
+use bevy_math::IVec3;
+use custom::CustomMD;
+use fluid::FluidMD;
+use xsprite::XSpriteMD;
+
+/// How bright (0.0 - 1.0) a block with the given light level is.
+pub fn light_brightness(light_level: u8) -> f32 {
+    0.8_f32.powi((MAX_LIGHT - light_level.min(MAX_LIGHT)) as i32)
+}
+
+/// Bake the light into a [`cubic`](`BlockMeshType::Cube`) chunk mesh. Each quad is lit by the
+/// block it faces, quads of blocks that emit light are at least as bright as their emission.
+/// `light_at` returns the light level of a position relative to the chunk, the position can be
+/// outside of the chunk (one block away from its edges).
+pub fn bake_cube_light<B: BlockInGrid>(
+    mesh: &mut Mesh,
+    metadata: &CubeMD<B>,
+    reg: &impl MeshRegistry<B>,
+    light_reg: &impl LightRegistry<B>,
+    grid: &impl BlockGrid<B>,
+    light_at: impl Fn(IVec3) -> u8,
+) {
+    let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR)
+    else {
+        return;
+    };
+    for (block_index, quads) in metadata.vivi.vivi.iter().enumerate() {
+        if quads.is_empty() {
+            continue;
+        }
+        let block_pos = index_to_pos(block_index, metadata.dims).unwrap();
+        let block = grid.get_block(block_pos);
+        let emission = block.map_or(0, |block| light_reg.light_emission(&block));
+        let block_colors = block
+            .and_then(|block| {
+                reg.get_block_mesh_ref(&block)
+                    .get_if(metadata.layer.mesh_type())
+            })
+            .and_then(vertex_colors);
+        for quad in quads {
+            let face = face_from_u32(quad & REVERSE_OFFSET_CONST);
+            // Merged quads are lit by the block they belong to
+            if !metadata.vivi.owns_quad(face, block_index) {
+                continue;
+            }
+            let light_level = light_at(block_pos.as_ivec3() + face.normal()).max(emission);
+            let brightness = light_brightness(light_level);
+            let vertex = (quad & OFFSET_CONST) as usize;
+            for i in 0..4 {
+                let color = block_colors.map_or([1.0; 4], |c| c[face as usize * 4 + i]);
+                colors[vertex + i] = darken(color, brightness);
+            }
+        }
+    }
+}
+
+/// Bake the light into an [`xsprite`](`BlockMeshType::XSprite`) chunk mesh. Each block is lit
+/// by its own light level, or by its emission if it's brighter.
+pub fn bake_xsprite_light<B: BlockInGrid>(
+    mesh: &mut Mesh,
+    metadata: &XSpriteMD<B>,
+    reg: &impl MeshRegistry<B>,
+    light_reg: &impl LightRegistry<B>,
+    grid: &impl BlockGrid<B>,
+    light_at: impl Fn(IVec3) -> u8,
+) {
+    let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR)
+    else {
+        return;
+    };
+    for (block_index, (vertex_start, vertex_end, _, _)) in metadata.vivi.iter().enumerate() {
+        if vertex_end <= vertex_start {
+            continue;
+        }
+        let block_pos = index_to_pos(block_index, metadata.dims).unwrap();
+        let block = grid.get_block(block_pos);
+        let emission = block.map_or(0, |block| light_reg.light_emission(&block));
+        let block_colors = block
+            .and_then(|block| {
+                reg.get_block_mesh_ref(&block)
+                    .get_if(BlockMeshType::XSprite)
+            })
+            .and_then(vertex_colors);
+        let brightness = light_brightness(light_at(block_pos.as_ivec3()).max(emission));
+        for (i, vertex) in (*vertex_start..*vertex_end).enumerate() {
+            let color = block_colors
+                .and_then(|c| c.get(i).copied())
+                .unwrap_or([1.0; 4]);
+            colors[vertex] = darken(color, brightness);
+        }
+    }
+}
+
+/// Bake the light into a [`custom`](`BlockMeshType::Custom`) chunk mesh. Each block is lit by its
+/// own light level, or by its emission if it's brighter.
+pub fn bake_custom_light<B: BlockInGrid>(
+    mesh: &mut Mesh,
+    metadata: &CustomMD<B>,
+    reg: &impl MeshRegistry<B>,
+    light_reg: &impl LightRegistry<B>,
+    grid: &impl BlockGrid<B>,
+    light_at: impl Fn(IVec3) -> u8,
+) {
+    let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR)
+    else {
+        return;
+    };
+    for (block_pos, (vertex_start, ..)) in metadata.vivi.iter() {
+        let block = grid.get_block(*block_pos);
+        let emission = block.map_or(0, |block| light_reg.light_emission(&block));
+        let Some(block_colors) = block
+            .and_then(|block| reg.get_block_mesh_ref(&block).get_if(BlockMeshType::Custom))
+            .and_then(vertex_colors)
+        else {
+            continue;
+        };
+        let brightness = light_brightness(light_at(block_pos.as_ivec3()).max(emission));
+        for (i, color) in block_colors.iter().enumerate() {
+            colors[*vertex_start as usize + i] = darken(*color, brightness);
+        }
+    }
+}
+
+/// Bake the light into a [`fluid`](`BlockMeshType::Fluid`) chunk mesh. Each block is lit by its
+/// own light level, or by its emission if it's brighter.
+pub fn bake_fluid_light<B: BlockInGrid>(
+    mesh: &mut Mesh,
+    metadata: &FluidMD<B>,
+    reg: &impl MeshRegistry<B>,
+    light_reg: &impl LightRegistry<B>,
+    grid: &impl BlockGrid<B>,
+    light_at: impl Fn(IVec3) -> u8,
+) {
+    let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR)
+    else {
+        return;
+    };
+    for (block_pos, (vertex_start, vertex_count)) in metadata.vivi.iter() {
+        let block = grid.get_block(*block_pos);
+        let emission = block.map_or(0, |block| light_reg.light_emission(&block));
+        // The faces of a fluid block are filtered and reshaped, so the whole block is colored by
+        // the color of the first vertex of its mesh.
+        let color = block
+            .and_then(|block| reg.get_block_mesh_ref(&block).get_if(BlockMeshType::Fluid))
+            .and_then(vertex_colors)
+            .and_then(|c| c.first().copied())
+            .unwrap_or([1.0; 4]);
+        let brightness = light_brightness(light_at(block_pos.as_ivec3()).max(emission));
+        for vertex in *vertex_start..*vertex_start + *vertex_count {
+            colors[vertex as usize] = darken(color, brightness);
+        }
+    }
+}
+
+fn vertex_colors(mesh: &Mesh) -> Option<&Vec<[f32; 4]>> {
+    match mesh.attribute(Mesh::ATTRIBUTE_COLOR)? {
+        VertexAttributeValues::Float32x4(colors) => Some(colors),
+        _ => None,
+    }
+}
+
+fn darken([r, g, b, a]: [f32; 4], brightness: f32) -> [f32; 4] {
+    [r * brightness, g * brightness, b * brightness, a]
+}
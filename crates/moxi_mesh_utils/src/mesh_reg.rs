@@ -17,6 +17,15 @@ pub trait MeshRegistry<B: BlockInGrid> {
 
     /// Return the [`BlockMeshType`] of the block mesh.
     fn get_block_mesh_type(&self, block: &B) -> BlockMeshType;
+
+    /// Returns the [`BlockShape`] of the block. By default, cubes are full and the rest of the
+    /// blocks are empty.
+    fn get_block_shape(&self, block: &B) -> &BlockShape {
+        match self.get_block_mesh_type(block) {
+            BlockMeshType::Cube | BlockMeshType::TranslucentCube => &FULL_SHAPE,
+            _ => &EMPTY_SHAPE,
+        }
+    }
 }
 
 /// Trait of common functions for [`MeshRegistry`]s.
@@ -42,6 +51,14 @@ pub trait MeshRegistryCommon<B: BlockInGrid> {
 
     fn is_cube(&self, block: &B) -> bool;
 
+    fn is_translucent_cube(&self, block: &B) -> bool;
+
+    fn is_fluid(&self, block: &B) -> bool;
+
+    /// Whether the face of the block (placed in the orientation) hides the face of the neighbor it
+    /// touches. Only opaque blocks hide their neighbors.
+    fn occludes_face(&self, block: &B, orientation: Orientation, face: Face) -> bool;
+
     fn get_default_mesh(&self) -> BlockMeshRef<'static> {
         Self::DEFAULT_MESH
     }
@@ -106,4 +123,21 @@ impl<B: BlockInGrid, M: MeshRegistry<B>> MeshRegistryCommon<B> for M {
     fn is_cube(&self, block: &B) -> bool {
         self.get_block_mesh_ref(block).is_cube()
     }
+
+    fn is_translucent_cube(&self, block: &B) -> bool {
+        self.get_block_mesh_ref(block).is_translucent_cube()
+    }
+
+    fn is_fluid(&self, block: &B) -> bool {
+        self.get_block_mesh_ref(block).is_fluid()
+    }
+
+    fn occludes_face(&self, block: &B, orientation: Orientation, face: Face) -> bool {
+        match self.get_block_mesh_type(block) {
+            BlockMeshType::Cube | BlockMeshType::Custom => self
+                .get_block_shape(block)
+                .occludes_oriented(orientation, face),
+            _ => false,
+        }
+    }
 }
@@ -0,0 +1,193 @@
+//! The shape of a block, the boxes that make up its volume. The shape decides which faces of the
+//! block hide the faces of its neighbors, and what the block's colliders are.
+
+use crate::*;
+use std::borrow::Cow;
+
+/// A box inside a block, in block space: the block spans from `[0, 0, 0]` to `[1, 1, 1]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShapeBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl ShapeBox {
+    /// The box that fills the whole block.
+    pub const FULL: ShapeBox = ShapeBox {
+        min: [0.0; 3],
+        max: [1.0; 3],
+    };
+
+    pub const fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+        Self { min, max }
+    }
+
+    /// The box rotated by the orientation around the center of the block.
+    pub fn oriented(&self, orientation: Orientation) -> Self {
+        let rotate = |[x, y, z]: [f32; 3]| {
+            let [x, y, z] = orientation.rotate([x - 0.5, y - 0.5, z - 0.5]);
+            [x + 0.5, y + 0.5, z + 0.5]
+        };
+        let (a, b) = (rotate(self.min), rotate(self.max));
+        Self {
+            min: [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+            max: [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+        }
+    }
+}
+
+/// The shape of a block, made up of axis aligned boxes (in the block's default orientation).
+/// Full cubes are made up of a single [`ShapeBox::FULL`], partial blocks like slabs and stairs are
+/// made up of smaller boxes. A face of the block hides the face of the neighbor it touches only
+/// if the boxes cover all of it, so a slab hides the face beneath it, but not the sides of its
+/// neighbors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockShape {
+    pub boxes: Cow<'static, [ShapeBox]>,
+}
+
+/// The shape of full cubes.
+pub static FULL_SHAPE: BlockShape = BlockShape {
+    boxes: Cow::Borrowed(&[ShapeBox::FULL]),
+};
+
+/// The shape of blocks that have no volume, they don't hide any faces and have no colliders.
+pub static EMPTY_SHAPE: BlockShape = BlockShape {
+    boxes: Cow::Borrowed(&[]),
+};
+
+impl BlockShape {
+    pub fn new(boxes: Vec<ShapeBox>) -> Self {
+        Self {
+            boxes: Cow::Owned(boxes),
+        }
+    }
+
+    /// The bottom half of a block.
+    pub fn slab() -> Self {
+        Self::new(vec![ShapeBox::new([0.0; 3], [1.0, 0.5, 1.0])])
+    }
+
+    /// A bottom slab, and a step on its back half. The stairs go up towards the back, so they face
+    /// the front.
+    pub fn stairs() -> Self {
+        Self::new(vec![
+            ShapeBox::new([0.0; 3], [1.0, 0.5, 1.0]),
+            ShapeBox::new([0.0, 0.5, 0.5], [1.0; 3]),
+        ])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    /// Whether the face of the block is fully covered by the boxes.
+    pub fn occludes(&self, face: Face) -> bool {
+        let (n_axis, a_axis, b_axis) = match face {
+            Face::Top | Face::Bottom => (1, 0, 2),
+            Face::Right | Face::Left => (0, 2, 1),
+            Face::Back | Face::Front => (2, 0, 1),
+        };
+        let positive = matches!(face, Face::Top | Face::Right | Face::Back);
+        // The rectangles the boxes that touch the face cover on it
+        let rects: Vec<([f32; 2], [f32; 2])> = self
+            .boxes
+            .iter()
+            .filter(|b| {
+                if positive {
+                    b.max[n_axis] >= 1.0
+                } else {
+                    b.min[n_axis] <= 0.0
+                }
+            })
+            .map(|b| {
+                (
+                    [b.min[a_axis], b.min[b_axis]],
+                    [b.max[a_axis], b.max[b_axis]],
+                )
+            })
+            .collect();
+        if rects.is_empty() {
+            return false;
+        }
+
+        // Split the face into cells along the edges of the rectangles, each cell must be covered.
+        let breakpoints = |axis: usize| {
+            let mut points: Vec<f32> = rects
+                .iter()
+                .flat_map(|(min, max)| [min[axis], max[axis]])
+                .filter(|p| *p > 0.0 && *p < 1.0)
+                .chain([0.0, 1.0])
+                .collect();
+            points.sort_by(f32::total_cmp);
+            points.dedup();
+            points
+        };
+        let (points_a, points_b) = (breakpoints(0), breakpoints(1));
+        points_a.windows(2).all(|a| {
+            points_b.windows(2).all(|b| {
+                let center = [(a[0] + a[1]) / 2.0, (b[0] + b[1]) / 2.0];
+                rects
+                    .iter()
+                    .any(|(min, max)| (0..2).all(|i| min[i] <= center[i] && center[i] <= max[i]))
+            })
+        })
+    }
+
+    /// Whether the face of the block, placed in the orientation, is fully covered by the boxes.
+    pub fn occludes_oriented(&self, orientation: Orientation, face: Face) -> bool {
+        self.occludes(orientation.model_face(face))
+    }
+
+    /// The boxes of the block placed in the orientation, in the space of the block's mesh (see
+    /// [`MeshRegistryCommon::get_block_center`] and [`MeshRegistryCommon::get_block_dims`]), as
+    /// (min, max) corners.
+    pub fn mesh_space_boxes(
+        &self,
+        orientation: Orientation,
+        center: [f32; 3],
+        dims: [f32; 3],
+    ) -> impl Iterator<Item = ([f32; 3], [f32; 3])> + '_ {
+        let to_mesh_space =
+            move |p: [f32; 3]| [0, 1, 2].map(|i| center[i] + (p[i] - 0.5) * dims[i]);
+        self.boxes.iter().map(move |b| {
+            let b = b.oriented(orientation);
+            (to_mesh_space(b.min), to_mesh_space(b.max))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shape_occlusion() {
+        assert!(FACES.iter().all(|face| FULL_SHAPE.occludes(*face)));
+        assert!(FACES.iter().all(|face| !EMPTY_SHAPE.occludes(*face)));
+
+        let slab = BlockShape::slab();
+        assert!(slab.occludes(Face::Bottom));
+        assert!(!slab.occludes(Face::Top));
+        assert!(!slab.occludes(Face::Right));
+
+        let stairs = BlockShape::stairs();
+        assert!(stairs.occludes(Face::Bottom));
+        assert!(stairs.occludes(Face::Back));
+        assert!(!stairs.occludes(Face::Front));
+        assert!(!stairs.occludes(Face::Top));
+        // Facing right, the back of the stairs is on the left
+        let facing_right = Orientation::facing(Facing::Right);
+        assert!(stairs.occludes_oriented(facing_right, Face::Left));
+        assert!(!stairs.occludes_oriented(facing_right, Face::Back));
+        // Flipped on its side, the bottom of the slab is on the left
+        assert!(slab.occludes_oriented(Orientation::axis(Axis::X), Face::Left));
+
+        // Two halves make up a full face
+        let halves = BlockShape::new(vec![
+            ShapeBox::new([0.0; 3], [0.5, 1.0, 1.0]),
+            ShapeBox::new([0.5, 0.0, 0.0], [1.0; 3]),
+        ]);
+        assert!(FACES.iter().all(|face| halves.occludes(*face)));
+    }
+}
@@ -158,6 +158,10 @@ pub fn apply_smooth_lighting_with_connected_chunks<'a, T: BlockInGrid, const N:
                 let mut surrounding_blocks = [false; 3 * 3 * 3];
                 let cage_dims = UVec3::new(3, 3, 3);
                 let face = face_from_u32(q & REVERSE_OFFSET_CONST);
+                // Merged quads are shaded by the block they belong to
+                if !metadata.vivi.owns_quad(face, block_index) {
+                    continue;
+                }
 
                 if (matches!(face, Face::Bottom) || matches!(face, Face::Top))
                     && is_block_pos_on_edge(block_pos, face, dims)
@@ -13,6 +13,7 @@ pub fn meshify_xsprite_voxels<B: BlockInGrid, const N: usize>(
     let mut positions: Vec<[f32; 3]> = vec![];
     let mut uvs: Vec<[f32; 2]> = vec![];
     let mut normals: Vec<[f32; 3]> = vec![];
+    let mut colors: Vec<[f32; 4]> = vec![];
 
     // data structure similar to VIVI, to map voxel index
     let mut data_structure = vec![(usize::MIN, usize::MIN, u32::MIN, u32::MIN); grid.len()];
@@ -79,6 +80,7 @@ pub fn meshify_xsprite_voxels<B: BlockInGrid, const N: usize>(
 
         positions.extend(pos);
         normals.extend(nor);
+        colors.extend(col);
         uvs.extend(uv);
         indices.extend(ind);
 
@@ -89,6 +91,7 @@ pub fn meshify_xsprite_voxels<B: BlockInGrid, const N: usize>(
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
     mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
     mesh.set_indices(Some(Indices::U32(indices)));
 
     (
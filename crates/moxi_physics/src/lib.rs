@@ -1,29 +1,44 @@
 //! WIP, still not ready
 
 mod config_macro;
+mod merged_boxes;
 
 use bevy_app::Update;
 use bevy_ecs::{
     bundle::Bundle,
     component::Component,
     entity::Entity,
-    query::{With, Without},
+    event::EventReader,
+    query::{Or, With, Without},
+    schedule::IntoSystemConfigs,
     system::{Commands, Query, Res, Resource},
 };
 use bevy_xpbd_3d::{
     math::Vector3,
     parry::{bounding_volume::Aabb, na::Isometry3},
-    prelude::{Collider, CollisionLayers, PhysicsLayer, RigidBody, TriMeshFlags},
+    prelude::{Collider, CollisionLayers, PhysicsLayer, RigidBody, Rotation, TriMeshFlags},
 };
-use moxi_bpta::prelude::{Chunk, CurrentChunk, MeshReg, StaticBlockQuery, _Blocks};
-use moxi_mesh_utils::prelude::{Aabb as BevyAabb, MeshRegistryCommon};
-use moxi_utils::prelude::{chunk_distance, BlockPos, ChunkCords};
+use merged_boxes::merge_solid_blocks;
+use moxi_bpta::prelude::{
+    _Blocks, BlockRegionUpdateEvent, BlockWorldUpdateEvent, Chunk, CurrentChunk, MeshReg,
+    StaticBlockQuery, BLOCK_PLACED, BLOCK_REMOVED, BLOCK_STATE_CHANGED,
+};
+use moxi_mesh_utils::prelude::{
+    mesh_triangles, oriented_mesh, Aabb as BevyAabb, MeshRegistry, MeshRegistryCommon, FULL_SHAPE,
+};
+use moxi_utils::prelude::{chunk_distance, BlockGrid, BlockId, BlockPos, ChunkCords, Orientation};
+use std::collections::HashSet;
 
 #[derive(Default)]
 pub enum ColliderComputationMethod {
+    /// A trimesh made of the boxes of every block.
     #[default]
     ByBlockAabb,
+    /// A trimesh made of the mesh of every block.
     ByBlockMesh,
+    /// A compound of cuboids, the full blocks are greedily merged into as few boxes as possible
+    /// and the blocks buried in the terrain are left out.
+    ByMergedBoxes,
 }
 
 #[derive(Resource, Clone, Copy)]
@@ -49,9 +64,14 @@ pub struct _MoxiPhysicsPlugin<const N: usize> {
 #[derive(Component)]
 pub struct BlockNonCollidable;
 
+/// A chunk whose collider needs to be (re)computed.
 #[derive(Component)]
 pub struct AsyncChunkCollider;
 
+/// A chunk whose collider was computed. Chunks without any collidable blocks have no [`Collider`].
+#[derive(Component)]
+pub struct ComputedChunkCollider;
+
 #[derive(PhysicsLayer)]
 pub enum MoxiCollisionLayer {
     Terrain,
@@ -72,34 +92,44 @@ impl<const N: usize> bevy_app::Plugin for _MoxiPhysicsPlugin<N> {
             ColliderComputationMethod::ByBlockMesh => {
                 app.add_systems(Update, generate_collider_for_chunks_from_block_mesh::<N>);
             }
+            ColliderComputationMethod::ByMergedBoxes => {
+                app.add_systems(Update, generate_collider_for_chunks_from_merged_boxes::<N>);
+            }
         }
 
-        app.add_systems(Update, insert_async_collider_for_chunks);
+        app.add_systems(
+            Update,
+            (
+                remove_colliders_out_of_range,
+                insert_async_collider_for_chunks,
+                insert_async_collider_for_changed_chunks,
+            )
+                .chain(),
+        );
     }
 }
 
+/// Whether the colliders of a chunk should be computed.
+fn build_chunk_filter(
+    compute_colliders_for: ComputeCollidersFor,
+    current_chunk: ChunkCords,
+) -> impl Fn(&ChunkCords) -> bool {
+    let distance = match compute_colliders_for {
+        ComputeCollidersFor::AllChunks => i32::MAX,
+        ComputeCollidersFor::CurrentChunk => 0,
+        ComputeCollidersFor::ChunksNearPlayer { distance } => distance as i32,
+        ComputeCollidersFor::None => -1,
+    };
+    move |chunk_cords: &ChunkCords| chunk_distance(*chunk_cords, current_chunk) <= distance
+}
+
 fn insert_async_collider_for_chunks(
     mut commands: Commands,
-    chunks: Query<(&Chunk, Entity), (Without<Collider>, Without<AsyncChunkCollider>)>,
+    chunks: Query<(&Chunk, Entity), (Without<ComputedChunkCollider>, Without<AsyncChunkCollider>)>,
     compute_colliders_for: Res<ComputeCollidersFor>,
     current_chunk: Res<CurrentChunk>,
 ) {
-    fn build_chunk_filter(
-        distance: i32,
-        current_chunk: ChunkCords,
-    ) -> impl Fn(&ChunkCords) -> bool {
-        move |chunk_cords: &ChunkCords| chunk_distance(*chunk_cords, current_chunk) <= distance
-    }
-
-    let current_chunk = current_chunk.0;
-    let chunk_filter = match compute_colliders_for.into_inner() {
-        ComputeCollidersFor::AllChunks => build_chunk_filter(i32::MAX, current_chunk),
-        ComputeCollidersFor::CurrentChunk => build_chunk_filter(0, current_chunk),
-        ComputeCollidersFor::ChunksNearPlayer { distance } => {
-            build_chunk_filter(*distance as i32, current_chunk)
-        }
-        ComputeCollidersFor::None => return,
-    };
+    let chunk_filter = build_chunk_filter(*compute_colliders_for, current_chunk.0);
 
     chunks
         .iter()
@@ -111,12 +141,112 @@ fn insert_async_collider_for_chunks(
         });
 }
 
+/// Recompute the colliders of the chunks whose blocks were placed, broken or changed state (which
+/// might change their shape), one by one or in a region edit.
+fn insert_async_collider_for_changed_chunks(
+    mut commands: Commands,
+    mut block_world_updates: EventReader<BlockWorldUpdateEvent>,
+    mut block_region_updates: EventReader<BlockRegionUpdateEvent>,
+    chunks: Query<(&Chunk, Entity), With<ComputedChunkCollider>>,
+) {
+    let mut changed_chunks: HashSet<ChunkCords> = block_world_updates
+        .read()
+        .filter(|event| {
+            event.block_update().is_pure_and(|update_type| {
+                update_type == BLOCK_PLACED
+                    || update_type == BLOCK_REMOVED
+                    || update_type == BLOCK_STATE_CHANGED
+            })
+        })
+        .map(|event| event.chunk_cords())
+        .collect();
+    changed_chunks.extend(
+        block_region_updates
+            .read()
+            .flat_map(|event| event.chunks.iter().copied()),
+    );
+    if changed_chunks.is_empty() {
+        return;
+    }
+
+    for (chunk, chunk_entity) in chunks.iter() {
+        if changed_chunks.contains(&chunk.cords) {
+            commands
+                .entity(chunk_entity)
+                .insert(AsyncChunkCollider)
+                .remove::<ComputedChunkCollider>();
+        }
+    }
+}
+
+/// Remove the colliders of the chunks that are no longer in the [`ComputeCollidersFor`] range.
+fn remove_colliders_out_of_range(
+    mut commands: Commands,
+    chunks: Query<(&Chunk, Entity), Or<(With<ComputedChunkCollider>, With<AsyncChunkCollider>)>>,
+    compute_colliders_for: Res<ComputeCollidersFor>,
+    current_chunk: Res<CurrentChunk>,
+) {
+    let chunk_filter = build_chunk_filter(*compute_colliders_for, current_chunk.0);
+
+    for (chunk, chunk_entity) in chunks.iter() {
+        if !chunk_filter(&chunk.cords) {
+            commands.entity(chunk_entity).remove::<(
+                Collider,
+                ComputedChunkCollider,
+                AsyncChunkColliderBundle,
+            )>();
+        }
+    }
+}
+
 fn generate_collider_for_chunks_from_block_aabb<const N: usize>(
     mut commands: Commands,
     non_collidable_blocks_query: StaticBlockQuery<&BlockNonCollidable>,
     mesh_registry: Res<MeshReg>,
     chunks_to_generate_collider_for: Query<(Entity, &Chunk), With<AsyncChunkCollider>>,
     blocks: _Blocks<N>,
+) {
+    for (chunk_entity, Chunk { cords: chunk_cords }) in chunks_to_generate_collider_for.iter() {
+        if let Some(chunk_grid) = blocks.get_chunk_grid(*chunk_cords) {
+            let mut chunk_tri_mesh = ChunkTriMesh::new();
+            for (block_pos, block_id) in chunk_grid.enumerate_blocks() {
+                if mesh_registry.is_air(&block_id)
+                    || non_collidable_blocks_query
+                        .get_static_property(block_id)
+                        .is_some()
+                {
+                    continue;
+                }
+                // The colliders of a block are the boxes of its shape, the blocks without a shape
+                // collide with the bounding box of their mesh.
+                let shape = mesh_registry.get_block_shape(&block_id);
+                if shape.is_empty() {
+                    let block_aabb = mesh_registry.get_block_mesh_aabb(&block_id);
+                    chunk_tri_mesh.append_block_aabb(block_aabb, block_pos);
+                    continue;
+                }
+                let orientation = blocks
+                    .get_dynamic_property::<Orientation>(*chunk_cords, block_pos)
+                    .unwrap_or_default();
+                let (center, dims) = (
+                    mesh_registry.get_block_center(),
+                    mesh_registry.get_block_dims(),
+                );
+                for (min, max) in shape.mesh_space_boxes(orientation, center, dims) {
+                    chunk_tri_mesh.append_box(min, max, block_pos, dims);
+                }
+            }
+            insert_chunk_collider(&mut commands, chunk_entity, chunk_tri_mesh.into_collider());
+        }
+    }
+}
+
+fn generate_collider_for_chunks_from_block_mesh<const N: usize>(
+    mut commands: Commands,
+    non_collidable_blocks_query: StaticBlockQuery<&BlockNonCollidable>,
+    mesh_registry: Res<MeshReg>,
+    chunks_to_generate_collider_for: Query<(Entity, &Chunk), With<AsyncChunkCollider>>,
+    blocks: _Blocks<N>,
 ) {
     for (chunk_entity, Chunk { cords: chunk_cords }) in chunks_to_generate_collider_for.iter() {
         if let Some(chunk_grid) = blocks.get_chunk_grid(*chunk_cords) {
@@ -128,28 +258,123 @@ fn generate_collider_for_chunks_from_block_aabb<const N: usize>(
                 {
                     continue;
                 }
-                let block_aabb = mesh_registry.get_block_mesh_aabb(&block_id);
-                chunk_tri_mesh.append_block_aabb(block_aabb, block_pos);
+                let Some(block_mesh) = mesh_registry.get_block_mesh_ref(&block_id).as_option()
+                else {
+                    continue;
+                };
+                let orientation = blocks
+                    .get_dynamic_property::<Orientation>(*chunk_cords, block_pos)
+                    .unwrap_or_default();
+                let block_mesh =
+                    oriented_mesh(block_mesh, orientation, mesh_registry.get_block_center());
+                let (positions, triangles) = mesh_triangles(&block_mesh);
+                chunk_tri_mesh.append_triangles(
+                    positions,
+                    triangles,
+                    block_pos,
+                    mesh_registry.get_block_dims(),
+                );
             }
-            if chunk_tri_mesh.vertices.is_empty() {
-                continue;
+            insert_chunk_collider(&mut commands, chunk_entity, chunk_tri_mesh.into_collider());
+        }
+    }
+}
+
+fn generate_collider_for_chunks_from_merged_boxes<const N: usize>(
+    mut commands: Commands,
+    non_collidable_blocks_query: StaticBlockQuery<&BlockNonCollidable>,
+    mesh_registry: Res<MeshReg>,
+    chunks_to_generate_collider_for: Query<(Entity, &Chunk), With<AsyncChunkCollider>>,
+    blocks: _Blocks<N>,
+) {
+    let (center, dims) = (
+        mesh_registry.get_block_center(),
+        mesh_registry.get_block_dims(),
+    );
+    // The corner of the block at the origin of the chunk.
+    let origin = [0, 1, 2].map(|i| center[i] - dims[i] / 2.0);
+    let is_collidable = |block_id: BlockId| {
+        !mesh_registry.is_air(&block_id)
+            && non_collidable_blocks_query
+                .get_static_property(block_id)
+                .is_none()
+    };
+
+    for (chunk_entity, Chunk { cords: chunk_cords }) in chunks_to_generate_collider_for.iter() {
+        if let Some(chunk_grid) = blocks.get_chunk_grid(*chunk_cords) {
+            let mut boxes = Vec::new();
+            let is_full_block = |block_pos: BlockPos| {
+                let block_id = chunk_grid.get_block_or(block_pos, 0);
+                is_collidable(block_id) && *mesh_registry.get_block_shape(&block_id) == FULL_SHAPE
+            };
+            for (min, max) in merge_solid_blocks(chunk_grid.dims(), &is_full_block) {
+                let max = max + BlockPos::ONE;
+                boxes.push((
+                    [0, 1, 2].map(|i| origin[i] + min[i] as f32 * dims[i]),
+                    [0, 1, 2].map(|i| origin[i] + max[i] as f32 * dims[i]),
+                ));
+            }
+            // The blocks that aren't full collide with their own boxes.
+            for (block_pos, block_id) in chunk_grid.enumerate_blocks() {
+                if !is_collidable(block_id) || is_full_block(block_pos) {
+                    continue;
+                }
+                let offset = [0, 1, 2].map(|i| block_pos[i] as f32 * dims[i]);
+                let shape = mesh_registry.get_block_shape(&block_id);
+                if shape.is_empty() {
+                    let aabb = mesh_registry.get_block_mesh_aabb(&block_id);
+                    let (min, max) = (aabb.min(), aabb.max());
+                    boxes.push((
+                        [min.x + offset[0], min.y + offset[1], min.z + offset[2]],
+                        [max.x + offset[0], max.y + offset[1], max.z + offset[2]],
+                    ));
+                    continue;
+                }
+                let orientation = blocks
+                    .get_dynamic_property::<Orientation>(*chunk_cords, block_pos)
+                    .unwrap_or_default();
+                for (min, max) in shape.mesh_space_boxes(orientation, center, dims) {
+                    boxes.push((
+                        [0, 1, 2].map(|i| min[i] + offset[i]),
+                        [0, 1, 2].map(|i| max[i] + offset[i]),
+                    ));
+                }
             }
-            let chunk_collider = Collider::trimesh_with_config(
-                chunk_tri_mesh.vertices,
-                chunk_tri_mesh.indices,
-                TriMeshFlags::MERGE_DUPLICATE_VERTICES,
-            );
 
-            commands
-                .entity(chunk_entity)
-                .insert(chunk_collider)
-                .remove::<AsyncChunkCollider>();
+            let chunk_collider = (!boxes.is_empty()).then(|| {
+                Collider::compound(
+                    boxes
+                        .into_iter()
+                        .map(|(min, max)| {
+                            let center = [0, 1, 2].map(|i| (min[i] + max[i]) / 2.0);
+                            (
+                                Vector3::from(center),
+                                Rotation::default(),
+                                Collider::cuboid(max[0] - min[0], max[1] - min[1], max[2] - min[2]),
+                            )
+                        })
+                        .collect(),
+                )
+            });
+            insert_chunk_collider(&mut commands, chunk_entity, chunk_collider);
         }
     }
 }
 
-fn generate_collider_for_chunks_from_block_mesh<const N: usize>() {
-    unimplemented!()
+/// Insert the computed collider into the chunk, replacing its old collider.
+fn insert_chunk_collider(
+    commands: &mut Commands,
+    chunk_entity: Entity,
+    chunk_collider: Option<Collider>,
+) {
+    let mut chunk_commands = commands.entity(chunk_entity);
+    chunk_commands
+        .insert(ComputedChunkCollider)
+        .remove::<AsyncChunkCollider>();
+    match chunk_collider {
+        Some(chunk_collider) => chunk_commands.insert(chunk_collider),
+        None => chunk_commands.remove::<Collider>(),
+    };
 }
 
 #[derive(Bundle)]
@@ -185,18 +410,66 @@ impl ChunkTriMesh {
         }
     }
 
+    /// The collider made of the triangles, if there are any.
+    fn into_collider(self) -> Option<Collider> {
+        (!self.vertices.is_empty()).then(|| {
+            Collider::trimesh_with_config(
+                self.vertices,
+                self.indices,
+                TriMeshFlags::MERGE_DUPLICATE_VERTICES,
+            )
+        })
+    }
+
     fn append_block_aabb(&mut self, aabb: BevyAabb, block_pos: BlockPos) {
         let p_aabb = bevy_aabb_to_parry_aabb(aabb);
-        p_aabb.transform_by(&Isometry3::translation(
-            block_pos.x as f32,
-            block_pos.y as f32,
-            block_pos.z as f32,
+        let (min, max) = (p_aabb.mins, p_aabb.maxs);
+        self.append_box(
+            [min.x, min.y, min.z],
+            [max.x, max.y, max.z],
+            block_pos,
+            [1.0; 3],
+        );
+    }
+
+    /// Append a box (in the space of the block's mesh) of the block at `block_pos`.
+    fn append_box(&mut self, min: [f32; 3], max: [f32; 3], block_pos: BlockPos, dims: [f32; 3]) {
+        let p_aabb = Aabb::new(min.into(), max.into()).transform_by(&Isometry3::translation(
+            block_pos.x as f32 * dims[0],
+            block_pos.y as f32 * dims[1],
+            block_pos.z as f32 * dims[2],
         ));
         let (vers, inds) = p_aabb.to_trimesh();
         let vers: Vec<Vector3> = vers
             .iter()
             .map(|v| Vector3::new(v.x, v.y, v.z))
             .collect::<Vec<Vector3>>();
+        self.append(vers, inds);
+    }
+
+    /// Append the triangles of a block's mesh (in the space of the block's mesh) of the block at
+    /// `block_pos`.
+    fn append_triangles(
+        &mut self,
+        positions: Vec<[f32; 3]>,
+        triangles: Vec<[u32; 3]>,
+        block_pos: BlockPos,
+        dims: [f32; 3],
+    ) {
+        let vers = positions
+            .iter()
+            .map(|[x, y, z]| {
+                Vector3::new(
+                    x + block_pos.x as f32 * dims[0],
+                    y + block_pos.y as f32 * dims[1],
+                    z + block_pos.z as f32 * dims[2],
+                )
+            })
+            .collect::<Vec<Vector3>>();
+        self.append(vers, triangles);
+    }
+
+    fn append(&mut self, vers: Vec<Vector3>, inds: Vec<[u32; 3]>) {
         let inds = inds
             .iter()
             .map(|[a, b, c]| {
@@ -0,0 +1,167 @@
+//! Greedily merging the solid blocks of a chunk into axis aligned boxes.
+
+use moxi_utils::prelude::{BlockPos, Dimensions};
+
+/// Merge the solid blocks of a chunk into boxes, given as their (min, max) blocks (inclusive).
+/// Every solid block that isn't fully enclosed by other solid blocks (of the chunk) ends up in a
+/// box. Enclosed blocks are only used to grow the boxes of exposed blocks, so the insides of the
+/// terrain don't get any boxes of their own. The boxes don't overlap.
+pub(crate) fn merge_solid_blocks(
+    dims: Dimensions,
+    is_solid: impl Fn(BlockPos) -> bool,
+) -> Vec<(BlockPos, BlockPos)> {
+    let index = |pos: BlockPos| (pos.x + pos.z * dims.x + pos.y * dims.x * dims.z) as usize;
+    let len = (dims.x * dims.y * dims.z) as usize;
+    let mut solid = vec![false; len];
+    for y in 0..dims.y {
+        for z in 0..dims.z {
+            for x in 0..dims.x {
+                let pos = BlockPos::new(x, y, z);
+                solid[index(pos)] = is_solid(pos);
+            }
+        }
+    }
+    let is_enclosed = |pos: BlockPos| {
+        let neighbors = [
+            (pos.x > 0).then(|| pos - BlockPos::X),
+            (pos.x + 1 < dims.x).then(|| pos + BlockPos::X),
+            (pos.y > 0).then(|| pos - BlockPos::Y),
+            (pos.y + 1 < dims.y).then(|| pos + BlockPos::Y),
+            (pos.z > 0).then(|| pos - BlockPos::Z),
+            (pos.z + 1 < dims.z).then(|| pos + BlockPos::Z),
+        ];
+        // The blocks on the edge of the chunk are never enclosed, the neighboring chunk might
+        // change without this chunk's collider being recomputed.
+        neighbors
+            .iter()
+            .all(|neighbor| neighbor.is_some_and(|neighbor| solid[index(neighbor)]))
+    };
+
+    let mut covered = vec![false; len];
+    let mut boxes = Vec::new();
+    for y in 0..dims.y {
+        for z in 0..dims.z {
+            for x in 0..dims.x {
+                let min = BlockPos::new(x, y, z);
+                if !solid[index(min)] || covered[index(min)] || is_enclosed(min) {
+                    continue;
+                }
+                let is_free = |pos: BlockPos| solid[index(pos)] && !covered[index(pos)];
+                let is_free_box = |min: BlockPos, max: BlockPos| {
+                    (min.y..=max.y).all(|y| {
+                        (min.z..=max.z)
+                            .all(|z| (min.x..=max.x).all(|x| is_free(BlockPos::new(x, y, z))))
+                    })
+                };
+
+                // Grow the box along x, then z, then y.
+                let mut max = min;
+                while max.x + 1 < dims.x && is_free(max + BlockPos::X) {
+                    max.x += 1;
+                }
+                while max.z + 1 < dims.z
+                    && is_free_box(
+                        BlockPos::new(min.x, min.y, max.z + 1),
+                        BlockPos::new(max.x, min.y, max.z + 1),
+                    )
+                {
+                    max.z += 1;
+                }
+                while max.y + 1 < dims.y
+                    && is_free_box(
+                        BlockPos::new(min.x, max.y + 1, min.z),
+                        BlockPos::new(max.x, max.y + 1, max.z),
+                    )
+                {
+                    max.y += 1;
+                }
+
+                for y in min.y..=max.y {
+                    for z in min.z..=max.z {
+                        for x in min.x..=max.x {
+                            covered[index(BlockPos::new(x, y, z))] = true;
+                        }
+                    }
+                }
+                boxes.push((min, max));
+            }
+        }
+    }
+    boxes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that every exposed solid block is in exactly one box, and that the boxes only
+    /// contain solid blocks.
+    fn check_boxes(
+        dims: Dimensions,
+        is_solid: impl Fn(BlockPos) -> bool,
+        boxes: &[(BlockPos, BlockPos)],
+    ) {
+        for y in 0..dims.y {
+            for z in 0..dims.z {
+                for x in 0..dims.x {
+                    let pos = BlockPos::new(x, y, z);
+                    let count = boxes
+                        .iter()
+                        .filter(|(min, max)| pos.cmpge(*min).all() && pos.cmple(*max).all())
+                        .count();
+                    let on_edge = [x, y, z].contains(&0)
+                        || x + 1 == dims.x
+                        || y + 1 == dims.y
+                        || z + 1 == dims.z;
+                    let exposed = on_edge
+                        || [
+                            pos - BlockPos::X,
+                            pos + BlockPos::X,
+                            pos - BlockPos::Y,
+                            pos + BlockPos::Y,
+                            pos - BlockPos::Z,
+                            pos + BlockPos::Z,
+                        ]
+                        .iter()
+                        .any(|neighbor| !is_solid(*neighbor));
+                    match (is_solid(pos), exposed) {
+                        (false, _) => assert_eq!(count, 0, "{pos}"),
+                        (true, true) => assert_eq!(count, 1, "{pos}"),
+                        (true, false) => assert!(count <= 1, "{pos}"),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_solid_blocks() {
+        let dims = Dimensions::new(4, 4, 4);
+        // A solid chunk is a single box.
+        assert_eq!(
+            merge_solid_blocks(dims, |_| true),
+            vec![(BlockPos::ZERO, BlockPos::new(3, 3, 3))]
+        );
+        assert!(merge_solid_blocks(dims, |_| false).is_empty());
+
+        // The bottom half with a pillar on top.
+        let is_solid = |pos: BlockPos| pos.y < 2 || (pos.x == 1 && pos.z == 1);
+        let boxes = merge_solid_blocks(dims, is_solid);
+        assert_eq!(boxes.len(), 2);
+        check_boxes(dims, is_solid, &boxes);
+
+        // A filled cube in the middle of the chunk is a single box.
+        let dims = Dimensions::new(5, 5, 5);
+        let in_cube =
+            |pos: BlockPos| pos.cmpge(BlockPos::ONE).all() && pos.cmple(BlockPos::splat(3)).all();
+        assert_eq!(
+            merge_solid_blocks(dims, in_cube),
+            vec![(BlockPos::ONE, BlockPos::splat(3))]
+        );
+
+        // Some noisy terrain.
+        let dims = Dimensions::new(8, 8, 8);
+        let is_solid = |pos: BlockPos| pos.y < 3 || (pos.x * 7 + pos.y * 13 + pos.z * 5) % 3 == 1;
+        check_boxes(dims, is_solid, &merge_solid_blocks(dims, is_solid));
+    }
+}
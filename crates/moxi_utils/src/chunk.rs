@@ -4,9 +4,10 @@ use bevy_math::prelude::*;
 
 /// Dimensions of a chunk, (width, height, length)
 pub type Dimensions = UVec3;
-/// Chunk coordinates, (x, z)
-pub type ChunkCords = IVec2;
+/// Chunk coordinates, (x, y, z)
+pub type ChunkCords = IVec3;
 /// Chunk grid
+#[derive(Clone)]
 pub struct Grid<T: BlockInGrid, const N: usize> {
     pub dims: Dimensions,
     grid: [T; N],
@@ -194,6 +195,80 @@ impl<T: BlockInGrid, const N: usize> Grid<T, N> {
     }
 }
 
+/// Access to the blocks of a chunk grid, regardless of how the blocks are stored. Implemented by
+/// [`Grid`] and by [`PalettedGrid`].
+pub trait BlockGrid<T: BlockInGrid> {
+    fn dims(&self) -> Dimensions;
+
+    fn get_block(&self, block_pos: BlockPos) -> Option<T>;
+
+    #[allow(clippy::result_unit_err)]
+    fn set_block(&mut self, block: T, block_pos: BlockPos) -> Result<(), ()>;
+
+    fn get_block_or(&self, block_pos: BlockPos, default: T) -> T {
+        self.get_block(block_pos).unwrap_or(default)
+    }
+
+    fn get_neighbor_of(&self, block_pos: BlockPos, face: Face) -> Option<T> {
+        neighbor_pos(block_pos, face, self.dims()).and_then(|pos| self.get_block(pos))
+    }
+
+    fn get_neighbor_of_or(&self, block_pos: BlockPos, face: Face, default: T) -> T {
+        self.get_neighbor_of(block_pos, face).unwrap_or(default)
+    }
+
+    fn enumerate_blocks_on_edge(&self, edge: Face) -> impl Iterator<Item = (BlockPos, T)> + '_ {
+        iter_blocks_on_edge(edge, self.dims()).map(|pos| (pos, self.get_block(pos).unwrap()))
+    }
+
+    fn iter_blocks_on_edge(&self, edge: Face) -> impl Iterator<Item = BlockPos> {
+        iter_blocks_on_edge(edge, self.dims())
+    }
+
+    fn enumerate_blocks(&self) -> impl Iterator<Item = (BlockPos, T)> + '_ {
+        let dims = self.dims();
+        (0..self.len()).map(move |i| {
+            let pos = index_to_pos(i, dims).unwrap();
+            (pos, self.get_block(pos).unwrap())
+        })
+    }
+
+    fn get_neighbors(&self, block_pos: BlockPos) -> SurroundingBlocks<T> {
+        FACES.map(|face| self.get_neighbor_of(block_pos, face))
+    }
+
+    fn get_neighbors_or(&self, block_pos: BlockPos, default: T) -> [T; 6] {
+        FACES.map(|face| self.get_neighbor_of_or(block_pos, face, default))
+    }
+
+    fn enumerate_neighbors(&self, block_pos: BlockPos) -> [(Face, Option<T>); 6] {
+        FACES.map(|face| (face, self.get_neighbor_of(block_pos, face)))
+    }
+
+    fn len(&self) -> usize {
+        let dims = self.dims();
+        (dims.x * dims.y * dims.z) as usize
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: BlockInGrid, const N: usize> BlockGrid<T> for Grid<T, N> {
+    fn dims(&self) -> Dimensions {
+        self.dims
+    }
+
+    fn get_block(&self, block_pos: BlockPos) -> Option<T> {
+        Grid::get_block(self, block_pos)
+    }
+
+    fn set_block(&mut self, block: T, block_pos: BlockPos) -> Result<(), ()> {
+        Grid::set_block(self, block, block_pos)
+    }
+}
+
 pub fn neighbor_across_chunk(
     mut block_pos: BlockPos,
     face: Face,
@@ -217,7 +292,14 @@ pub fn neighbor_across_chunk(
                 block_pos.z = dims.z - 1;
                 block_pos
             }),
-            _ => None,
+            Face::Top => Some({
+                block_pos.y = 0;
+                block_pos
+            }),
+            Face::Bottom => Some({
+                block_pos.y = dims.y - 1;
+                block_pos
+            }),
         };
     }
     None
@@ -306,17 +388,20 @@ pub fn neighbor_pos(mut block_pos: BlockPos, face: Face, dims: Dimensions) -> Op
 /// Assumes each block is [1, 1, 1]
 pub fn point_to_chunk_cords(point: Vec3, chunk_dims: Dimensions) -> ChunkCords {
     let chunk_width = chunk_dims.x;
+    let chunk_height = chunk_dims.y;
     let chunk_length = chunk_dims.z;
     let x = point.x + 0.5;
+    let y = point.y + 0.5;
     let z = point.z + 0.5;
     [
         (x / chunk_width as f32 + (x.signum() - 1.0) / 2.0) as i32,
+        (y / chunk_height as f32 + (y.signum() - 1.0) / 2.0) as i32,
         (z / chunk_length as f32 + (z.signum() - 1.0) / 2.0) as i32,
     ]
     .into()
 }
 
-/// the bool is for whether or not the pos is within the height bounds
+/// Assumes each block is [1, 1, 1]
 pub fn point_to_global_block_pos(point: Vec3, chunk_dims: Dimensions) -> BlockGlobalPos {
     let chunk_width = chunk_dims.x;
     let chunk_length = chunk_dims.z;
@@ -328,17 +413,12 @@ pub fn point_to_global_block_pos(point: Vec3, chunk_dims: Dimensions) -> BlockGl
     let y = point.y + 0.5;
 
     let block_pos = [
-        (x - chunk_cords[0] as f32 * chunk_width as f32) as u32,
-        (y as u32).max(0).min(chunk_height),
-        (z - chunk_cords[1] as f32 * chunk_length as f32) as u32,
+        ((x - chunk_cords.x as f32 * chunk_width as f32) as u32).min(chunk_width - 1),
+        ((y - chunk_cords.y as f32 * chunk_height as f32) as u32).min(chunk_height - 1),
+        ((z - chunk_cords.z as f32 * chunk_length as f32) as u32).min(chunk_length - 1),
     ];
 
-    let flag = y >= 0.0 && y <= chunk_height as f32;
-    BlockGlobalPos {
-        pos: block_pos.into(),
-        cords: chunk_cords,
-        valid: flag,
-    }
+    BlockGlobalPos::new(block_pos.into(), chunk_cords)
 }
 
 pub fn global_block_pos_to_block_trans(
@@ -346,17 +426,7 @@ pub fn global_block_pos_to_block_trans(
     block_dims: Vec3,
     dims: Dimensions,
 ) -> BlockTrans {
-    let [u, v] = [
-        global_pos.cords.x as f32 * dims.x as f32,
-        global_pos.cords.y as f32 * dims.z as f32,
-    ];
-
-    block_dims
-        * Vec3::new(
-            u + global_pos.pos.x as f32,
-            global_pos.pos.y as f32,
-            v + global_pos.pos.z as f32,
-        )
+    block_dims * (global_pos.cords.as_vec3() * dims.as_vec3() + global_pos.pos.as_vec3())
 }
 
 pub fn global_enumerate_neighboring_blocks(
@@ -377,13 +447,13 @@ pub fn global_neighbor(
         return BlockGlobalPos {
             pos: neighbor_pos,
             cords: global_pos.cords,
-            valid: dims.y > neighbor_pos.y,
+            valid: global_pos.valid,
         };
     } else if let Some(neighbor_pos) = neighbor_across_chunk(global_pos.pos, face, dims) {
         return BlockGlobalPos {
             pos: neighbor_pos,
             cords: adj_chunk(global_pos.cords, face),
-            valid: dims.y > neighbor_pos.y,
+            valid: global_pos.valid,
         };
     }
     global_pos.valid = false;
@@ -392,14 +462,21 @@ pub fn global_neighbor(
 
 pub fn adj_chunk(chunk_cords: ChunkCords, face: Face) -> ChunkCords {
     match face {
-        Face::Top | Face::Bottom => chunk_cords,
-        Face::Back => chunk_cords + IVec2::from([0, 1]),
-        Face::Front => chunk_cords - IVec2::from([0, 1]),
-        Face::Right => chunk_cords + IVec2::from([1, 0]),
-        Face::Left => chunk_cords - IVec2::from([1, 0]),
+        Face::Top => chunk_cords + IVec3::Y,
+        Face::Bottom => chunk_cords - IVec3::Y,
+        Face::Back => chunk_cords + IVec3::Z,
+        Face::Front => chunk_cords - IVec3::Z,
+        Face::Right => chunk_cords + IVec3::X,
+        Face::Left => chunk_cords - IVec3::X,
     }
 }
 
+/// The horizontal distance (in chunks) between two chunks, ignores the height of the chunks.
 pub fn chunk_distance(chunk1: ChunkCords, chunk2: ChunkCords) -> i32 {
-    (chunk1.x - chunk2.x).abs().max((chunk1.y - chunk2.y).abs())
+    (chunk1.x - chunk2.x).abs().max((chunk1.z - chunk2.z).abs())
+}
+
+/// The vertical distance (in chunks) between two chunks.
+pub fn vertical_chunk_distance(chunk1: ChunkCords, chunk2: ChunkCords) -> i32 {
+    (chunk1.y - chunk2.y).abs()
 }
@@ -1,5 +1,7 @@
 //! Utility functions for working with faces.
 
+use bevy_math::IVec3;
+
 #[derive(Copy, Clone, Debug)]
 pub enum Face {
     Top,
@@ -72,6 +74,18 @@ impl Face {
         }
     }
 
+    /// The direction the face is facing.
+    pub fn normal(&self) -> IVec3 {
+        match *self {
+            Face::Top => IVec3::Y,
+            Face::Bottom => IVec3::NEG_Y,
+            Face::Right => IVec3::X,
+            Face::Left => IVec3::NEG_X,
+            Face::Back => IVec3::Z,
+            Face::Front => IVec3::NEG_Z,
+        }
+    }
+
     pub fn is_vertical(&self) -> bool {
         match *self {
             Face::Top | Face::Bottom => true,
@@ -3,12 +3,20 @@
 pub mod chunk;
 pub mod dir;
 pub mod face;
+pub mod light;
+pub mod orientation;
+pub mod palette;
+pub mod raycast;
 
 pub mod prelude {
     pub use super::block::*;
     pub use super::chunk::*;
     pub use super::dir::*;
     pub use super::face::*;
+    pub use super::light::*;
+    pub use super::orientation::*;
+    pub use super::palette::*;
+    pub use super::raycast::*;
 }
 
 pub mod block {
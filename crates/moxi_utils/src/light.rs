@@ -0,0 +1,407 @@
+//! Voxel light. Every block holds two light levels (0 - [`MAX_LIGHT`]): block light, that spreads
+//! from blocks that emit light, and sunlight, that is cast down from the sky. Light spreads to the
+//! neighboring blocks (through all 6 faces), losing at least one level per block, and more
+//! through blocks that absorb light (see [`LightRegistry`]). Sunlight at full strength doesn't
+//! lose any light while going straight down through blocks that don't absorb light.
+use std::collections::VecDeque;
+
+use crate::prelude::*;
+
+/// The highest light level.
+pub const MAX_LIGHT: u8 = 15;
+
+/// The two kinds of light every block holds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LightChannel {
+    /// Light that spreads from blocks that emit light.
+    Block,
+    /// Light that is cast down from the sky.
+    Sun,
+}
+
+pub const LIGHT_CHANNELS: [LightChannel; 2] = [LightChannel::Block, LightChannel::Sun];
+
+/// A registry of how blocks interact with light.
+pub trait LightRegistry<B: BlockInGrid> {
+    /// The light level (0 - [`MAX_LIGHT`]) that the block emits.
+    fn light_emission(&self, block: &B) -> u8;
+
+    /// How many light levels are lost when light spreads into the block, on top of the one level
+    /// light always loses per block. 0 lets light through, [`MAX_LIGHT`] blocks it completely.
+    fn light_opacity(&self, block: &B) -> u8;
+}
+
+/// The light levels of all of the blocks in a chunk. Each block takes one byte, the block light
+/// is stored in the low 4 bits and the sunlight in the high 4 bits.
+#[derive(Clone)]
+pub struct LightGrid<const N: usize> {
+    pub dims: Dimensions,
+    data: Vec<u8>,
+}
+
+impl<const N: usize> LightGrid<N> {
+    /// A grid with no light at all.
+    pub fn new(dims: Dimensions) -> Self {
+        Self {
+            dims,
+            data: vec![0; N],
+        }
+    }
+
+    pub fn get_light(&self, block_pos: BlockPos, channel: LightChannel) -> Option<u8> {
+        let light = self.data[pos_to_index(block_pos, self.dims)?];
+        Some(match channel {
+            LightChannel::Block => light & 0x0F,
+            LightChannel::Sun => light >> 4,
+        })
+    }
+
+    /// The brightest of the block light and the sunlight of the block.
+    pub fn get_max_light(&self, block_pos: BlockPos) -> Option<u8> {
+        let light = self.data[pos_to_index(block_pos, self.dims)?];
+        Some((light & 0x0F).max(light >> 4))
+    }
+
+    #[allow(clippy::result_unit_err)]
+    pub fn set_light(
+        &mut self,
+        block_pos: BlockPos,
+        channel: LightChannel,
+        level: u8,
+    ) -> Result<(), ()> {
+        let light = &mut self.data[pos_to_index(block_pos, self.dims).ok_or(())?];
+        let level = level.min(MAX_LIGHT);
+        *light = match channel {
+            LightChannel::Block => (*light & 0xF0) | level,
+            LightChannel::Sun => (*light & 0x0F) | (level << 4),
+        };
+        Ok(())
+    }
+}
+
+/// Access to the blocks and the light of all of the loaded chunks, light spreads through it
+/// across chunk borders.
+pub trait LightWorld<B: BlockInGrid> {
+    /// The dimensions of a chunk.
+    fn dims(&self) -> Dimensions;
+
+    /// `None` if the chunk isn't loaded.
+    fn get_block(&self, pos: BlockGlobalPos) -> Option<B>;
+
+    /// `None` if the chunk isn't loaded.
+    fn get_light(&self, pos: BlockGlobalPos, channel: LightChannel) -> Option<u8>;
+
+    /// Set the light of a block, does nothing if the chunk isn't loaded.
+    fn set_light(&mut self, pos: BlockGlobalPos, channel: LightChannel, level: u8);
+
+    /// Whether there is nothing but sky above the chunk, so the sunlight shines into its top.
+    fn is_sky_exposed(&self, chunk_cords: ChunkCords) -> bool;
+}
+
+/// Compute the light of a single chunk, without any light from its neighbors. If `sky_exposed`,
+/// the sunlight shines into the top of the chunk.
+pub fn light_chunk<B: BlockInGrid, const N: usize>(
+    grid: &impl BlockGrid<B>,
+    reg: &impl LightRegistry<B>,
+    sky_exposed: bool,
+) -> LightGrid<N> {
+    let mut world = SingleChunk {
+        grid,
+        light: LightGrid::new(grid.dims()),
+        sky_exposed,
+    };
+    for channel in LIGHT_CHANNELS {
+        let mut seeds = vec![];
+        for (block_pos, _) in grid.enumerate_blocks() {
+            let pos = BlockGlobalPos::new(block_pos, ChunkCords::ZERO);
+            let source = source_light(&world, reg, pos, channel);
+            if source > 0 {
+                world.set_light(pos, channel, source);
+                seeds.push(pos);
+            }
+        }
+        propagate_light(&mut world, reg, channel, seeds);
+    }
+    world.light
+}
+
+/// Update the light around a block that was changed (the new block should already be in the
+/// world). The light of the old block is removed, and the light around it spreads again.
+pub fn update_light<B: BlockInGrid>(
+    world: &mut impl LightWorld<B>,
+    reg: &impl LightRegistry<B>,
+    pos: BlockGlobalPos,
+) {
+    let dims = world.dims();
+    for channel in LIGHT_CHANNELS {
+        let mut seeds = remove_light(world, reg, pos, channel);
+        let source = source_light(world, reg, pos, channel);
+        if source > 0 {
+            world.set_light(pos, channel, source);
+            seeds.push(pos);
+        }
+        // The light of the neighbors spreads back into the block (if it lets light through).
+        seeds.extend(FACES.map(|face| global_neighbor(pos, face, dims)));
+        propagate_light(world, reg, channel, seeds);
+    }
+}
+
+/// Spread the light across the border between a chunk and its neighbor in the direction of
+/// `face`, both ways. Should be called once both of the chunks are loaded.
+pub fn light_chunk_border<B: BlockInGrid>(
+    world: &mut impl LightWorld<B>,
+    reg: &impl LightRegistry<B>,
+    chunk_cords: ChunkCords,
+    face: Face,
+) {
+    let dims = world.dims();
+    let adj_chunk_cords = adj_chunk(chunk_cords, face);
+    let seeds: Vec<BlockGlobalPos> = iter_blocks_on_edge(face, dims)
+        .flat_map(|block_pos| {
+            let adj_block_pos = neighbor_across_chunk(block_pos, face, dims).unwrap();
+            [
+                BlockGlobalPos::new(block_pos, chunk_cords),
+                BlockGlobalPos::new(adj_block_pos, adj_chunk_cords),
+            ]
+        })
+        .collect();
+    for channel in LIGHT_CHANNELS {
+        propagate_light(world, reg, channel, seeds.iter().copied());
+    }
+}
+
+/// Flood fill the light from the `seeds` to their neighbors, and from them to their neighbors,
+/// and so on. Light only ever increases.
+pub fn propagate_light<B: BlockInGrid>(
+    world: &mut impl LightWorld<B>,
+    reg: &impl LightRegistry<B>,
+    channel: LightChannel,
+    seeds: impl IntoIterator<Item = BlockGlobalPos>,
+) {
+    let dims = world.dims();
+    let mut queue: VecDeque<BlockGlobalPos> = seeds.into_iter().collect();
+    while let Some(pos) = queue.pop_front() {
+        let Some(level) = world.get_light(pos, channel) else {
+            continue;
+        };
+        if level <= 1 {
+            continue;
+        }
+        for face in FACES {
+            let neighbor = global_neighbor(pos, face, dims);
+            let Some(block) = world.get_block(neighbor) else {
+                continue;
+            };
+            let neighbor_level = spread(level, reg.light_opacity(&block), channel, face);
+            if neighbor_level > world.get_light(neighbor, channel).unwrap_or(MAX_LIGHT) {
+                world.set_light(neighbor, channel, neighbor_level);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+}
+
+/// Remove the light of a block, and all of the light that spread from it. Returns the blocks
+/// the light should spread again from, to fill in the gaps.
+fn remove_light<B: BlockInGrid>(
+    world: &mut impl LightWorld<B>,
+    reg: &impl LightRegistry<B>,
+    pos: BlockGlobalPos,
+    channel: LightChannel,
+) -> Vec<BlockGlobalPos> {
+    let dims = world.dims();
+    let mut seeds = vec![];
+    let Some(level) = world.get_light(pos, channel) else {
+        return seeds;
+    };
+    world.set_light(pos, channel, 0);
+    let mut queue = VecDeque::from([(pos, level)]);
+    while let Some((pos, level)) = queue.pop_front() {
+        for face in FACES {
+            let neighbor = global_neighbor(pos, face, dims);
+            let Some(neighbor_level) = world.get_light(neighbor, channel) else {
+                continue;
+            };
+            if neighbor_level == 0 {
+                continue;
+            }
+            let lit_by_pos = neighbor_level < level
+                || (channel == LightChannel::Sun
+                    && matches!(face, Face::Bottom)
+                    && level == MAX_LIGHT
+                    && neighbor_level == MAX_LIGHT);
+            if lit_by_pos {
+                world.set_light(neighbor, channel, 0);
+                queue.push_back((neighbor, neighbor_level));
+                // Blocks that are light sources keep their own light.
+                let source = source_light(world, reg, neighbor, channel);
+                if source > 0 {
+                    world.set_light(neighbor, channel, source);
+                    seeds.push(neighbor);
+                }
+            } else {
+                seeds.push(neighbor);
+            }
+        }
+    }
+    seeds
+}
+
+/// The light the block has regardless of its neighbors: the light it emits, or the sunlight
+/// that shines into it straight from the sky.
+fn source_light<B: BlockInGrid>(
+    world: &impl LightWorld<B>,
+    reg: &impl LightRegistry<B>,
+    pos: BlockGlobalPos,
+    channel: LightChannel,
+) -> u8 {
+    let Some(block) = world.get_block(pos) else {
+        return 0;
+    };
+    match channel {
+        LightChannel::Block => reg.light_emission(&block).min(MAX_LIGHT),
+        LightChannel::Sun => {
+            let dims = world.dims();
+            if pos.pos.y == dims.y - 1 && world.is_sky_exposed(pos.cords) {
+                spread(MAX_LIGHT, reg.light_opacity(&block), channel, Face::Bottom)
+            } else {
+                0
+            }
+        }
+    }
+}
+
+/// The light level of a block that light of `level` spreads into, through its `face`.
+fn spread(level: u8, opacity: u8, channel: LightChannel, face: Face) -> u8 {
+    if channel == LightChannel::Sun
+        && matches!(face, Face::Bottom)
+        && level == MAX_LIGHT
+        && opacity == 0
+    {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(opacity.max(1))
+    }
+}
+
+/// A world made up of a single chunk, at [`ChunkCords::ZERO`].
+struct SingleChunk<'a, G, const N: usize> {
+    grid: &'a G,
+    light: LightGrid<N>,
+    sky_exposed: bool,
+}
+
+impl<'a, B: BlockInGrid, G: BlockGrid<B>, const N: usize> LightWorld<B> for SingleChunk<'a, G, N> {
+    fn dims(&self) -> Dimensions {
+        self.grid.dims()
+    }
+
+    fn get_block(&self, pos: BlockGlobalPos) -> Option<B> {
+        (pos.cords == ChunkCords::ZERO)
+            .then(|| self.grid.get_block(pos.pos))
+            .flatten()
+    }
+
+    fn get_light(&self, pos: BlockGlobalPos, channel: LightChannel) -> Option<u8> {
+        (pos.cords == ChunkCords::ZERO)
+            .then(|| self.light.get_light(pos.pos, channel))
+            .flatten()
+    }
+
+    fn set_light(&mut self, pos: BlockGlobalPos, channel: LightChannel, level: u8) {
+        if pos.cords == ChunkCords::ZERO {
+            let _ = self.light.set_light(pos.pos, channel, level);
+        }
+    }
+
+    fn is_sky_exposed(&self, _chunk_cords: ChunkCords) -> bool {
+        self.sky_exposed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 0 is air, 1 is stone, 2 is a torch.
+    struct TestReg;
+
+    impl LightRegistry<BlockId> for TestReg {
+        fn light_emission(&self, block: &BlockId) -> u8 {
+            if *block == 2 {
+                14
+            } else {
+                0
+            }
+        }
+
+        fn light_opacity(&self, block: &BlockId) -> u8 {
+            if *block == 1 {
+                MAX_LIGHT
+            } else {
+                0
+            }
+        }
+    }
+
+    #[test]
+    fn test_light() {
+        let dims = Dimensions::new(8, 8, 8);
+        let mut grid = PalettedGrid::<BlockId, 512>::new(0, dims);
+        // A stone roof at y = 6, over the whole chunk except for (0, 6, 0).
+        for x in 0..8 {
+            for z in 0..8 {
+                if x + z > 0 {
+                    grid.set_block(1, [x, 6, z].into()).unwrap();
+                }
+            }
+        }
+        grid.set_block(2, [4, 0, 4].into()).unwrap();
+        let light = light_chunk::<BlockId, 512>(&grid, &TestReg, true);
+        let sun = |light: &LightGrid<512>, pos: [u32; 3]| {
+            light.get_light(pos.into(), LightChannel::Sun).unwrap()
+        };
+        let block = |light: &LightGrid<512>, pos: [u32; 3]| {
+            light.get_light(pos.into(), LightChannel::Block).unwrap()
+        };
+        assert_eq!(sun(&light, [3, 7, 3]), MAX_LIGHT);
+        assert_eq!(sun(&light, [3, 6, 3]), 0);
+        // Full sunlight goes straight down through the hole, and spreads from there.
+        assert_eq!(sun(&light, [0, 0, 0]), MAX_LIGHT);
+        assert_eq!(sun(&light, [1, 0, 0]), MAX_LIGHT - 1);
+        assert_eq!(sun(&light, [2, 5, 1]), MAX_LIGHT - 3);
+        assert_eq!(block(&light, [4, 0, 4]), 14);
+        assert_eq!(block(&light, [4, 2, 5]), 11);
+        assert_eq!(block(&light, [3, 7, 3]), 0);
+
+        let mut world = SingleChunk {
+            grid: &grid,
+            light,
+            sky_exposed: true,
+        };
+        let at = |pos: [u32; 3]| BlockGlobalPos::new(pos.into(), ChunkCords::ZERO);
+        // Cover the hole, the sunlight under the roof is gone.
+        let mut grid = grid.clone();
+        grid.set_block(1, [0, 6, 0].into()).unwrap();
+        world.grid = &grid;
+        update_light(&mut world, &TestReg, at([0, 6, 0]));
+        assert_eq!(sun(&world.light, [0, 0, 0]), 0);
+        assert_eq!(sun(&world.light, [2, 5, 1]), 0);
+        assert_eq!(sun(&world.light, [0, 7, 0]), MAX_LIGHT);
+        // Remove the torch, the block light is gone.
+        let mut grid = grid.clone();
+        grid.set_block(0, [4, 0, 4].into()).unwrap();
+        world.grid = &grid;
+        update_light(&mut world, &TestReg, at([4, 0, 4]));
+        assert!(grid
+            .enumerate_blocks()
+            .all(|(pos, _)| block(&world.light, pos.to_array()) == 0));
+        // Break a hole in the roof again, the sunlight comes back.
+        let mut grid = grid.clone();
+        grid.set_block(0, [7, 6, 7].into()).unwrap();
+        world.grid = &grid;
+        update_light(&mut world, &TestReg, at([7, 6, 7]));
+        assert_eq!(sun(&world.light, [7, 0, 7]), MAX_LIGHT);
+        assert_eq!(sun(&world.light, [6, 0, 7]), MAX_LIGHT - 1);
+    }
+}
@@ -0,0 +1,145 @@
+//! The orientation of a block, used to rotate the mesh of a block at a position.
+
+use crate::face::Face;
+
+/// The axis the vertical (Y) axis of a block's mesh is aligned to, like the axis of a log.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Axis {
+    X,
+    #[default]
+    Y,
+    Z,
+}
+
+/// The horizontal direction the front of a block's mesh is facing.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Facing {
+    #[default]
+    Front,
+    Right,
+    Back,
+    Left,
+}
+
+impl Facing {
+    /// The face of the block the facing points to.
+    pub fn face(&self) -> Face {
+        match *self {
+            Facing::Front => Face::Front,
+            Facing::Right => Face::Right,
+            Facing::Back => Face::Back,
+            Facing::Left => Face::Left,
+        }
+    }
+}
+
+/// The orientation of a block. The block's mesh is first rotated so its Y axis is aligned to
+/// `axis`, and then rotated around the Y axis so its front faces `facing`.
+/// The default orientation leaves the mesh as it was defined.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Orientation {
+    pub axis: Axis,
+    pub facing: Facing,
+}
+
+impl Orientation {
+    pub fn new(axis: Axis, facing: Facing) -> Self {
+        Self { axis, facing }
+    }
+
+    /// An orientation facing the given direction, aligned to the Y axis.
+    pub fn facing(facing: Facing) -> Self {
+        Self {
+            axis: Axis::Y,
+            facing,
+        }
+    }
+
+    /// An orientation aligned to the given axis, facing [`Facing::Front`].
+    pub fn axis(axis: Axis) -> Self {
+        Self {
+            axis,
+            facing: Facing::Front,
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Rotates a vector (relative to the center of the block) by the orientation. All rotations
+    /// are by multiples of 90 degrees, so the result is exact.
+    pub fn rotate(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let [x, y, z] = match self.axis {
+            Axis::X => [y, -x, z],
+            Axis::Y => [x, y, z],
+            Axis::Z => [x, -z, y],
+        };
+        match self.facing {
+            Facing::Front => [x, y, z],
+            Facing::Right => [-z, y, x],
+            Facing::Back => [-x, y, -z],
+            Facing::Left => [z, y, -x],
+        }
+    }
+
+    /// Rotates a vector by the inverse of the orientation.
+    pub fn rotate_inverse(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let [x, y, z] = match self.facing {
+            Facing::Front => [x, y, z],
+            Facing::Right => [z, y, -x],
+            Facing::Back => [-x, y, -z],
+            Facing::Left => [-z, y, x],
+        };
+        match self.axis {
+            Axis::X => [-y, x, z],
+            Axis::Y => [x, y, z],
+            Axis::Z => [x, z, -y],
+        }
+    }
+
+    /// The face in the world a face of the block's mesh ends up at.
+    pub fn rotate_face(&self, face: Face) -> Face {
+        face_from_normal(self.rotate(face.normal().as_vec3().into()))
+    }
+
+    /// The face of the block's mesh that ends up at the given face in the world. The inverse
+    /// of [`Orientation::rotate_face`].
+    pub fn model_face(&self, face: Face) -> Face {
+        face_from_normal(self.rotate_inverse(face.normal().as_vec3().into()))
+    }
+}
+
+fn face_from_normal(normal: [f32; 3]) -> Face {
+    match normal {
+        [x, _, _] if x > 0.5 => Face::Right,
+        [x, _, _] if x < -0.5 => Face::Left,
+        [_, y, _] if y > 0.5 => Face::Top,
+        [_, y, _] if y < -0.5 => Face::Bottom,
+        [_, _, z] if z > 0.5 => Face::Back,
+        _ => Face::Front,
+    }
+}
+
+impl From<u8> for Orientation {
+    fn from(value: u8) -> Self {
+        let axis = match value / 4 {
+            0 => Axis::X,
+            2 => Axis::Z,
+            _ => Axis::Y,
+        };
+        let facing = match value % 4 {
+            1 => Facing::Right,
+            2 => Facing::Back,
+            3 => Facing::Left,
+            _ => Facing::Front,
+        };
+        Self { axis, facing }
+    }
+}
+
+impl From<Orientation> for u8 {
+    fn from(orientation: Orientation) -> Self {
+        orientation.axis as u8 * 4 + orientation.facing as u8
+    }
+}
@@ -0,0 +1,272 @@
+//! Palette compressed chunk grids.
+use crate::prelude::*;
+
+/// A chunk grid that stores every distinct block once, in a palette, and for each position in the
+/// grid only the index of its block in the palette. The indices are bit-packed into `u64`s, using
+/// as few bits as the size of the palette allows, so a chunk that only has air and stone uses 1
+/// bit per block, and a chunk with a single kind of block uses no memory for its blocks at all.
+/// Has the same API as [`Grid`] through the [`BlockGrid`] trait, but is slower to access.
+#[derive(Clone)]
+pub struct PalettedGrid<T: BlockInGrid, const N: usize> {
+    pub dims: Dimensions,
+    palette: Vec<T>,
+    /// How many blocks in the grid point to each palette entry. Entries that aren't pointed to
+    /// anymore are reused by new blocks.
+    counts: Vec<u32>,
+    bits_per_block: u32,
+    data: Vec<u64>,
+}
+
+impl<T: BlockInGrid, const N: usize> PalettedGrid<T, N> {
+    /// A grid filled with `block`.
+    pub fn new(block: T, dims: Dimensions) -> Self {
+        Self {
+            dims,
+            palette: vec![block],
+            counts: vec![N as u32],
+            bits_per_block: 0,
+            data: vec![],
+        }
+    }
+
+    pub fn from_grid(grid: &Grid<T, N>) -> Self {
+        let mut paletted_grid = Self::new(grid.get_block(BlockPos::ZERO).unwrap(), grid.dims);
+        for (block_pos, block) in grid.enumerate_blocks() {
+            paletted_grid.set_block(block, block_pos).unwrap();
+        }
+        paletted_grid
+    }
+
+    pub fn to_grid(&self) -> Grid<T, N> {
+        let mut grid = [self.palette[0]; N];
+        for (i, block) in grid.iter_mut().enumerate() {
+            *block = self.palette[self.read_index(i)];
+        }
+        Grid::new(grid, self.dims)
+    }
+
+    /// Build a grid from its raw parts, as returned by [`palette`](`Self::palette`),
+    /// [`bits_per_block`](`Self::bits_per_block`) and [`packed_data`](`Self::packed_data`).
+    /// Returns `None` if the parts don't make up a valid grid of size `N`.
+    pub fn from_raw_parts(
+        dims: Dimensions,
+        palette: Vec<T>,
+        bits_per_block: u32,
+        data: Vec<u64>,
+    ) -> Option<Self> {
+        if (dims.x * dims.y * dims.z) as usize != N
+            || palette.is_empty()
+            || bits_per_block > 32
+            || (bits_per_block == 0 && palette.len() > 1)
+            || (bits_per_block > 0 && palette.len() > 1 << bits_per_block)
+            || data.len() != packed_len::<N>(bits_per_block)
+        {
+            return None;
+        }
+        let mut grid = Self {
+            dims,
+            counts: vec![0; palette.len()],
+            palette,
+            bits_per_block,
+            data,
+        };
+        for i in 0..N {
+            let palette_index = grid.read_index(i);
+            *grid.counts.get_mut(palette_index)? += 1;
+        }
+        Some(grid)
+    }
+
+    /// The distinct blocks in the grid. May also hold blocks that were in the grid at some point
+    /// but were since replaced, use [`compact`](`Self::compact`) to remove them.
+    pub fn palette(&self) -> &[T] {
+        &self.palette
+    }
+
+    pub fn bits_per_block(&self) -> u32 {
+        self.bits_per_block
+    }
+
+    /// The indices into the palette of all of the blocks, in index order, bit-packed. The first
+    /// index of every `u64` is in its least significant bits, indices never span two `u64`s.
+    pub fn packed_data(&self) -> &[u64] {
+        &self.data
+    }
+
+    /// Remove the blocks that are no longer in the grid from the palette, and use as few bits
+    /// per block as possible.
+    pub fn compact(&mut self) {
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = vec![];
+        let mut counts = vec![];
+        for (i, (block, count)) in self.palette.iter().zip(&self.counts).enumerate() {
+            if *count > 0 {
+                remap[i] = palette.len();
+                palette.push(*block);
+                counts.push(*count);
+            }
+        }
+        let indices: Vec<usize> = (0..N).map(|i| remap[self.read_index(i)]).collect();
+        self.palette = palette;
+        self.counts = counts;
+        self.bits_per_block = bits_for(self.palette.len());
+        self.data = vec![0; packed_len::<N>(self.bits_per_block)];
+        for (i, palette_index) in indices.into_iter().enumerate() {
+            self.write_index(i, palette_index);
+        }
+    }
+
+    /// The amount of bytes that are used to store the blocks (not including the struct itself).
+    pub fn heap_size(&self) -> usize {
+        self.palette.len() * std::mem::size_of::<T>()
+            + self.counts.len() * std::mem::size_of::<u32>()
+            + self.data.len() * std::mem::size_of::<u64>()
+    }
+
+    fn palette_index_of(&mut self, block: T) -> usize {
+        if let Some(palette_index) = self.palette.iter().position(|b| *b == block) {
+            return palette_index;
+        }
+        if let Some(palette_index) = self.counts.iter().position(|count| *count == 0) {
+            self.palette[palette_index] = block;
+            return palette_index;
+        }
+        self.palette.push(block);
+        self.counts.push(0);
+        let bits_per_block = bits_for(self.palette.len());
+        if bits_per_block > self.bits_per_block {
+            self.repack(bits_per_block);
+        }
+        self.palette.len() - 1
+    }
+
+    fn repack(&mut self, bits_per_block: u32) {
+        let indices: Vec<usize> = (0..N).map(|i| self.read_index(i)).collect();
+        self.bits_per_block = bits_per_block;
+        self.data = vec![0; packed_len::<N>(bits_per_block)];
+        for (i, palette_index) in indices.into_iter().enumerate() {
+            self.write_index(i, palette_index);
+        }
+    }
+
+    fn read_index(&self, block_index: usize) -> usize {
+        if self.bits_per_block == 0 {
+            return 0;
+        }
+        let per_word = 64 / self.bits_per_block as usize;
+        let shift = (block_index % per_word) as u32 * self.bits_per_block;
+        ((self.data[block_index / per_word] >> shift) & mask(self.bits_per_block)) as usize
+    }
+
+    fn write_index(&mut self, block_index: usize, palette_index: usize) {
+        if self.bits_per_block == 0 {
+            return;
+        }
+        let per_word = 64 / self.bits_per_block as usize;
+        let shift = (block_index % per_word) as u32 * self.bits_per_block;
+        let word = &mut self.data[block_index / per_word];
+        *word &= !(mask(self.bits_per_block) << shift);
+        *word |= (palette_index as u64) << shift;
+    }
+}
+
+impl<T: BlockInGrid, const N: usize> BlockGrid<T> for PalettedGrid<T, N> {
+    fn dims(&self) -> Dimensions {
+        self.dims
+    }
+
+    fn get_block(&self, block_pos: BlockPos) -> Option<T> {
+        pos_to_index(block_pos, self.dims).map(|i| self.palette[self.read_index(i)])
+    }
+
+    fn set_block(&mut self, block: T, block_pos: BlockPos) -> Result<(), ()> {
+        let block_index = pos_to_index(block_pos, self.dims).ok_or(())?;
+        let old_palette_index = self.read_index(block_index);
+        if self.palette[old_palette_index] == block {
+            return Ok(());
+        }
+        self.counts[old_palette_index] -= 1;
+        let palette_index = self.palette_index_of(block);
+        self.counts[palette_index] += 1;
+        self.write_index(block_index, palette_index);
+        Ok(())
+    }
+}
+
+impl<T: BlockInGrid, const N: usize> From<&Grid<T, N>> for PalettedGrid<T, N> {
+    fn from(grid: &Grid<T, N>) -> Self {
+        Self::from_grid(grid)
+    }
+}
+
+/// The amount of bits needed to index a palette of size `palette_len`.
+fn bits_for(palette_len: usize) -> u32 {
+    usize::BITS - palette_len.saturating_sub(1).leading_zeros()
+}
+
+/// The amount of `u64`s needed to store `N` indices of `bits_per_block` bits.
+fn packed_len<const N: usize>(bits_per_block: u32) -> usize {
+    if bits_per_block == 0 {
+        return 0;
+    }
+    N.div_ceil(64 / bits_per_block as usize)
+}
+
+fn mask(bits_per_block: u32) -> u64 {
+    (1 << bits_per_block) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paletted_grid() {
+        let dims = Dimensions::new(4, 4, 4);
+        let mut grid = PalettedGrid::<BlockId, 64>::new(0, dims);
+        assert_eq!(grid.bits_per_block(), 0);
+        assert!(grid.enumerate_blocks().all(|(_, block)| block == 0));
+
+        let mut expected = [0; 64];
+        for (i, block) in expected.iter_mut().enumerate() {
+            *block = (i % 5) as BlockId;
+            let pos = index_to_pos(i, dims).unwrap();
+            grid.set_block(*block, pos).unwrap();
+        }
+        assert_eq!(grid.bits_per_block(), 3);
+        assert!(grid.set_block(1, [4, 0, 0].into()).is_err());
+        let expected = Grid::<BlockId, 64>::new(expected, dims);
+        assert!(grid
+            .enumerate_blocks()
+            .zip(expected.enumerate_blocks())
+            .all(|(a, b)| a == b));
+
+        // Replace every block except 0 and 1, the freed palette entries should be reused.
+        for (pos, block) in expected.enumerate_blocks() {
+            if block > 1 {
+                grid.set_block(1, pos).unwrap();
+            }
+        }
+        grid.set_block(9, [0, 0, 0].into()).unwrap();
+        assert_eq!(grid.palette().len(), 5);
+        grid.compact();
+        assert_eq!(grid.palette().len(), 3);
+        assert_eq!(grid.bits_per_block(), 2);
+        assert_eq!(grid.get_block([0, 0, 0].into()), Some(9));
+        assert_eq!(grid.get_block([1, 0, 0].into()), Some(1));
+        assert_eq!(grid.get_block([0, 1, 1].into()), Some(0));
+
+        let copy = PalettedGrid::<BlockId, 64>::from_raw_parts(
+            dims,
+            grid.palette().to_vec(),
+            grid.bits_per_block(),
+            grid.packed_data().to_vec(),
+        )
+        .unwrap();
+        let grid = grid.to_grid();
+        assert!(copy
+            .enumerate_blocks()
+            .zip(grid.enumerate_blocks())
+            .all(|(a, b)| a == b));
+    }
+}
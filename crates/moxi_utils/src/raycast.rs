@@ -0,0 +1,135 @@
+//! Casting rays through the block grid of the world, one block at a time (3D DDA).
+
+use crate::chunk::{BlockGlobalPos, Dimensions};
+use crate::face::Face;
+use bevy_math::{IVec3, Vec3};
+
+/// The block a ray hit.
+#[derive(Copy, Clone, Debug)]
+pub struct VoxelRayHit {
+    /// The position of the block, in blocks from the origin of the world.
+    pub block: IVec3,
+    /// The face of the block the ray entered through. If the ray started inside the block, the
+    /// face the ray would have entered through along its major axis.
+    pub face: Face,
+    /// The distance along the ray to the point it entered the block.
+    pub distance: f32,
+}
+
+/// Walks the blocks a ray goes through, in order, until `is_hit` returns true for one of them or
+/// the ray is longer than `max_distance`. Assumes each block is [1, 1, 1] and is centered on its
+/// position, like [`point_to_global_block_pos`](crate::chunk::point_to_global_block_pos).
+pub fn voxel_raycast(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    mut is_hit: impl FnMut(IVec3) -> bool,
+) -> Option<VoxelRayHit> {
+    let direction = direction.try_normalize()?;
+    let mut block = (origin + 0.5).floor().as_ivec3();
+    let step = IVec3::from_array([0, 1, 2].map(|i| match direction[i] {
+        d if d > 0.0 => 1,
+        d if d < 0.0 => -1,
+        _ => 0,
+    }));
+    // The distance along the ray to cross a whole block, on each axis.
+    let t_delta = direction.recip().abs();
+    // The distance along the ray to the next block, on each axis.
+    let mut t_max = Vec3::from_array([0, 1, 2].map(|i| match step[i] {
+        0 => f32::INFINITY,
+        s => (block[i] as f32 + 0.5 * s as f32 - origin[i]) / direction[i],
+    }));
+
+    let major_axis = min_axis(-direction.abs());
+    let mut face = entered_face(major_axis, step[major_axis]);
+    let mut distance = 0.0;
+    loop {
+        if is_hit(block) {
+            return Some(VoxelRayHit {
+                block,
+                face,
+                distance,
+            });
+        }
+        let axis = min_axis(t_max);
+        distance = t_max[axis];
+        if distance > max_distance {
+            return None;
+        }
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        face = entered_face(axis, step[axis]);
+    }
+}
+
+/// The axis with the smallest value.
+fn min_axis(v: Vec3) -> usize {
+    match (v.x <= v.y, v.x <= v.z, v.y <= v.z) {
+        (true, true, _) => 0,
+        (false, _, true) => 1,
+        _ => 2,
+    }
+}
+
+/// The face a ray moving along the axis (in the direction of `step`) enters a block through.
+fn entered_face(axis: usize, step: i32) -> Face {
+    match (axis, step > 0) {
+        (0, true) => Face::Left,
+        (0, false) => Face::Right,
+        (1, true) => Face::Bottom,
+        (1, false) => Face::Top,
+        (_, true) => Face::Front,
+        (_, false) => Face::Back,
+    }
+}
+
+/// The chunk and position in the chunk of a block, given its position in blocks from the origin
+/// of the world.
+pub fn block_to_global_block_pos(block: IVec3, chunk_dims: Dimensions) -> BlockGlobalPos {
+    let dims = chunk_dims.as_ivec3();
+    BlockGlobalPos::new(block.rem_euclid(dims).as_uvec3(), block.div_euclid(dims))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_voxel_raycast() {
+        let wall = |block: IVec3| block.x == 3;
+        let hit = voxel_raycast(Vec3::ZERO, Vec3::X, 10.0, wall).unwrap();
+        assert_eq!(hit.block, IVec3::new(3, 0, 0));
+        assert!(matches!(hit.face, Face::Left));
+        assert!((hit.distance - 2.5).abs() < 1e-5);
+        // Too far, and the wrong way.
+        assert!(voxel_raycast(Vec3::ZERO, Vec3::X, 2.0, wall).is_none());
+        assert!(voxel_raycast(Vec3::ZERO, Vec3::NEG_X, 10.0, wall).is_none());
+
+        // Looking down at the ground from an angle.
+        let ground = |block: IVec3| block.y < 0;
+        let hit = voxel_raycast(
+            Vec3::new(0.0, 2.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.3),
+            10.0,
+            ground,
+        )
+        .unwrap();
+        assert_eq!(hit.block.y, -1);
+        assert!(matches!(hit.face, Face::Top));
+
+        // Starting inside a block.
+        let hit = voxel_raycast(Vec3::new(3.2, 0.0, 0.0), Vec3::NEG_X, 10.0, wall).unwrap();
+        assert_eq!(hit.distance, 0.0);
+        assert!(matches!(hit.face, Face::Right));
+
+        assert!(voxel_raycast(Vec3::ZERO, Vec3::ZERO, 10.0, wall).is_none());
+    }
+
+    #[test]
+    fn test_block_to_global_block_pos() {
+        let dims = Dimensions::new(16, 16, 16);
+        let global_pos = block_to_global_block_pos(IVec3::new(-1, 17, 5), dims);
+        assert_eq!(global_pos.cords, IVec3::new(-1, 1, 0));
+        assert_eq!(global_pos.pos, crate::chunk::BlockPos::new(15, 1, 5));
+    }
+}
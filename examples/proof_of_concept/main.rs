@@ -38,7 +38,10 @@ fn main() {
                 }),
                 ..Default::default()
             }),
-        MoxiBptaPlugin::default(),
+        MoxiBptaPlugin::with_chunk_plugin(MoxiChunkPlugin {
+            render_distance: RenderDistance::new(10),
+            ..Default::default()
+        }),
         BlocksPlugin,
         PlayerPlugin,
         ChunksPlugin,
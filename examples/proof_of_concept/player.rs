@@ -14,7 +14,7 @@ use std::f32::consts::PI;
 
 use self::action::*;
 
-use super::{CHUNK_DIMS, HEIGHT, LENGTH, WIDTH};
+use super::{Blocks, CHUNK_DIMS, HEIGHT, LENGTH, WIDTH};
 use bevy::ecs::event::ManualEventReader;
 use bevy::input::mouse::MouseMotion;
 use bevy::window::{CursorGrabMode, PrimaryWindow};
@@ -26,7 +26,7 @@ use bevy::{
     },
     prelude::*,
 };
-use bevy_moxi::prelude::{ChunkCords, CurrentChunk, RENDER_DISTANCE};
+use bevy_moxi::prelude::{ChunkCords, CurrentChunk, RenderDistance};
 use bevy_xpbd_3d::prelude::*;
 pub use controller::*;
 use moxi_utils::prelude::*;
@@ -67,16 +67,19 @@ pub const SSAO_QUALITY_LEVEL: ScreenSpaceAmbientOcclusionQualityLevel =
     ScreenSpaceAmbientOcclusionQualityLevel::High;
 /// Default fog color
 pub const FOG_COLOR: Color = Color::rgb(0.65, 0.95, 1.0);
-/// Default fog falloff
-pub const FOG_FALLOFF: FogFalloff = FogFalloff::Linear {
-    start: ((RENDER_DISTANCE - 2) * WIDTH as i32) as f32,
-    end: ((RENDER_DISTANCE + 1) * WIDTH as i32) as f32,
-};
+/// Fog falloff for the render distance (in chunks)
+pub fn fog_falloff(render_distance: i32) -> FogFalloff {
+    FogFalloff::Linear {
+        start: ((render_distance - 2) * WIDTH as i32) as f32,
+        end: ((render_distance + 1) * WIDTH as i32) as f32,
+    }
+}
 /// Starting position of the player
 pub const STARTING_POS: [f32; 3] = [0.0, HEIGHT as f32 + 5.0, 0.0];
 /// Starting chunk of the player
 pub const STARTING_CHUNK: ChunkCords = ChunkCords::new(
     STARTING_POS[0] as i32 / WIDTH as i32,
+    0,
     STARTING_POS[2] as i32 / LENGTH as i32,
 );
 /// We don't want the camera to be exactly where the player's collider is, because that's the
@@ -89,16 +92,16 @@ pub const CAMERA_STARTING_POS: [f32; 3] = [0.0, 0.0 + CAMERA_HEIGHT_OFFSET, 0.0]
 /// The "reach" of the player, what is the largest distance from the player that a block can be and
 /// the player can break it / interact with it.
 pub const MAX_INTERACTION_DISTANCE: f32 = 6.0;
-/// Epsilon to offset a point by a little bit
-pub const SMALL_TRAVERSE: f32 = 0.001;
 /// Default Field of view
 pub const FOV: f32 = PI / 3.0;
 /// FOV while croching
 pub const CROUCH_FOV: f32 = FOV * 0.97;
 /// FOV while sprinting
 pub const SPRINT_FOV: f32 = FOV * 1.05;
-/// Distance which after the camera won't render anything
-pub const FAR: f32 = (RENDER_DISTANCE + 3) as f32 * WIDTH as f32;
+/// Distance which after the camera won't render anything, for the render distance (in chunks)
+pub fn far(render_distance: i32) -> f32 {
+    (render_distance + 3) as f32 * WIDTH as f32
+}
 /// Default player collider height
 pub const PLAYER_COLLIDER_HEIGHT: f32 = 1.15;
 /// Default player collider radius
@@ -179,7 +182,6 @@ pub enum PlayerGameMode {
 #[derive(Resource)]
 pub struct TargetBlock {
     pub ignore_flag: bool,
-    pub target_entity: Entity,
     pub chunk_cords: ChunkCords,
     pub block_pos: BlockPos,
     pub face_hit: Option<Face>,
@@ -260,8 +262,7 @@ impl Default for TargetBlock {
     fn default() -> Self {
         TargetBlock {
             ignore_flag: true,
-            target_entity: Entity::PLACEHOLDER,
-            chunk_cords: [0, 0].into(),
+            chunk_cords: [0, 0, 0].into(),
             block_pos: [0, 0, 0].into(),
             face_hit: None,
             ray_direction: Vec3::ONE,
@@ -319,15 +320,16 @@ pub struct PlayerCameraBundle {
     pub fog: FogSettings,
 }
 
-impl Default for PlayerCameraBundle {
-    fn default() -> Self {
+impl PlayerCameraBundle {
+    /// The camera of a player that sees up to the render distance (in chunks)
+    pub fn new(render_distance: i32) -> Self {
         Self {
             camera: PlayerCamera,
             camera_bundle: Camera3dBundle {
                 transform: Transform::from_translation(CAMERA_STARTING_POS.into()),
                 projection: Projection::Perspective(PerspectiveProjection {
                     fov: FOV,
-                    far: FAR,
+                    far: far(render_distance),
                     ..Default::default()
                 }),
                 ..Default::default()
@@ -335,7 +337,7 @@ impl Default for PlayerCameraBundle {
 
             fog: FogSettings {
                 color: FOG_COLOR,
-                falloff: FOG_FALLOFF,
+                falloff: fog_falloff(render_distance),
                 ..Default::default()
             },
         }
@@ -343,10 +345,10 @@ impl Default for PlayerCameraBundle {
 }
 
 /// Spawns the `Camera3dBundle` to be controlled
-fn setup_player(mut commands: Commands) {
+fn setup_player(mut commands: Commands, render_distance: Res<RenderDistance>) {
     let player_entity = commands.spawn(PhysicalPlayerBundle::default()).id();
     let camera_entity = commands
-        .spawn(PlayerCameraBundle::default())
+        .spawn(PlayerCameraBundle::new(render_distance.load_distance))
         .insert(TAA())
         .insert(SSAO())
         .id();
@@ -360,52 +362,20 @@ fn update_target_block(
     mut target_block: ResMut<TargetBlock>,
     camera_rotation_transform: Query<&Transform, With<PlayerCamera>>,
     camera_position_transform: Query<&Transform, With<PhysicalPlayer>>,
-    spatial_query: SpatialQuery,
+    blocks: Blocks,
 ) {
-    // let tran = camera_query.get_single().unwrap();
     if let (Ok(rot), Ok(pos)) = (
         camera_rotation_transform.get_single(),
         camera_position_transform.get_single(),
     ) {
         let forward = rot.forward();
         let pos = pos.translation + rot.translation;
-        if let Some(ray_hit) = spatial_query.cast_ray(
-            pos,
-            forward,
-            MAX_INTERACTION_DISTANCE,
-            false,
-            SpatialQueryFilter::new().with_masks(&[MoxiCollisionLayer::Terrain]),
-        ) {
-            let face = {
-                let mut to_return = None;
-                if ray_hit.normal == Vec3::Y {
-                    to_return = Some(Face::Top);
-                }
-                if ray_hit.normal == Vec3::NEG_Y {
-                    to_return = Some(Face::Bottom);
-                }
-                if ray_hit.normal == Vec3::X {
-                    to_return = Some(Face::Right);
-                }
-                if ray_hit.normal == Vec3::NEG_X {
-                    to_return = Some(Face::Left);
-                }
-                if ray_hit.normal == Vec3::Z {
-                    to_return = Some(Face::Back);
-                }
-                if ray_hit.normal == Vec3::NEG_Z {
-                    to_return = Some(Face::Front);
-                }
-                to_return
-            };
-            let impact_point = pos + ray_hit.time_of_impact * forward + SMALL_TRAVERSE * forward;
-            let global_pos = point_to_global_block_pos(impact_point, CHUNK_DIMS);
+        if let Some(ray_hit) = blocks.raycast(pos, forward, MAX_INTERACTION_DISTANCE) {
             *target_block = TargetBlock {
                 ignore_flag: false,
-                target_entity: ray_hit.entity,
-                chunk_cords: global_pos.cords,
-                block_pos: global_pos.pos,
-                face_hit: face,
+                chunk_cords: ray_hit.global_block_pos.cords,
+                block_pos: ray_hit.global_block_pos.pos,
+                face_hit: Some(ray_hit.face),
                 ray_direction: forward,
             };
         } else {
@@ -55,8 +55,8 @@ impl ChunkBuilder<BLOCKS_IN_CHUNK, BlockId> for MyChunkBuilder {
 }
 
 // System to setup the texture for the blocks. This is required but not enforced statically. The
-// game will panic if `CubeMeshMaterial`, `XSpriteMeshMaterial` or `CustomMeshMaterial` are not in
-// the world.
+// game will panic if `CubeMeshMaterial`, `TranslucentMeshMaterial`, `XSpriteMeshMaterial` or
+// `CustomMeshMaterial` are not in the world.
 fn setup_texture(
     mut commands: Commands,
     mut materials: ResMut<Assets<StandardMaterial>>,
@@ -65,11 +65,18 @@ fn setup_texture(
     let texture_handle: Handle<Image> = asset_server.load("blocks.png");
 
     let material_handle = materials.add(StandardMaterial {
-        base_color_texture: Some(texture_handle),
+        base_color_texture: Some(texture_handle.clone()),
         ..Default::default()
     });
     commands.insert_resource(CubeMeshMaterial(material_handle.clone()));
 
+    let translucent_material_handle = materials.add(StandardMaterial {
+        base_color_texture: Some(texture_handle),
+        alpha_mode: AlphaMode::Blend,
+        ..Default::default()
+    });
+    commands.insert_resource(TranslucentMeshMaterial(translucent_material_handle));
+
     commands.insert_resource(XSpriteMeshMaterial(material_handle.clone()));
 
     commands.insert_resource(CustomMeshMaterial(material_handle));
@@ -32,7 +32,7 @@ fn main() {
                 }),
                 ..Default::default()
             }),
-        _MoxiBptaPlugin::<BLOCKS_IN_CHUNK>,
+        _MoxiBptaPlugin::<BLOCKS_IN_CHUNK>::default(),
         BlocksPlugin,
         NoCameraPlayerPlugin,
         PlayerPlugin,